@@ -0,0 +1,174 @@
+//! Simulates thousands of charging stations sending CALLs concurrently and reports the
+//! throughput and latency distribution of the round trips.
+//!
+//! Every Authorize handler awaits a simulated database call. One additional station is served
+//! by a handler that is much slower than the others, which must not affect the latency seen by
+//! the remaining stations.
+//!
+//! Run with `cargo bench -p crush --bench stations`. The load can be tuned through the
+//! `CRUSH_BENCH_STATIONS`, `CRUSH_BENCH_CALLS` and `CRUSH_BENCH_HANDLER_DELAY_MS` environment
+//! variables.
+#![allow(
+    clippy::print_stdout,
+    reason = "the benchmark reports its results on stdout"
+)]
+#![allow(
+    unused_crate_dependencies,
+    reason = "the benchmark only uses a subset of the crate's dependencies"
+)]
+
+use std::{
+    env,
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crush::{
+    actions::Authorize,
+    rust_ocpp::v1_6::{
+        messages::authorize::AuthorizeResponse,
+        types::{AuthorizationStatus, IdTagInfo},
+    },
+    Config, CrushBuilder,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::{net::TcpStream, sync::Barrier, task::JoinSet, time::sleep};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const SLOW_STATION: &str = "slow-station";
+const SLOW_HANDLER_DELAY: Duration = Duration::from_secs(2);
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port available")
+}
+
+async fn station(
+    address: SocketAddr,
+    station_id: String,
+    calls: u64,
+    barrier: Arc<Barrier>,
+) -> Vec<Duration> {
+    let url = format!("ws://{address}/ocpp/{station_id}");
+    let (mut websocket, _) = connect_async(url).await.expect("station failed to connect");
+
+    barrier.wait().await;
+
+    let mut latencies = Vec::new();
+    for call in 0..calls {
+        let frame = format!(r#"[2,"{call}","Authorize",{{"idTag":"{station_id}"}}]"#);
+        let start = Instant::now();
+        websocket
+            .send(Message::Text(frame))
+            .await
+            .expect("failed to send CALL");
+        loop {
+            let message = websocket
+                .next()
+                .await
+                .expect("station lost its connection")
+                .expect("station failed to read a frame");
+            if message.is_text() {
+                break;
+            }
+        }
+        latencies.push(start.elapsed());
+    }
+    latencies
+}
+
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    let index = (sorted.len() * percentile / 1000).min(sorted.len().saturating_sub(1));
+    sorted.get(index).copied().unwrap_or_default()
+}
+
+#[tokio::main]
+async fn main() {
+    let stations = env_or("CRUSH_BENCH_STATIONS", 2000);
+    let calls = env_or("CRUSH_BENCH_CALLS", 20);
+    let handler_delay = Duration::from_millis(env_or("CRUSH_BENCH_HANDLER_DELAY_MS", 5));
+
+    let address = free_address();
+    let crush = CrushBuilder::new(Config::new(address))
+        .on_action::<Authorize, _>(move |context, _request| async move {
+            if context.station_id() == SLOW_STATION {
+                sleep(SLOW_HANDLER_DELAY).await;
+            } else {
+                sleep(handler_delay).await;
+            }
+            Ok(AuthorizeResponse {
+                id_tag_info: IdTagInfo {
+                    expiry_date: None,
+                    parent_id_tag: None,
+                    status: AuthorizationStatus::Accepted,
+                },
+            })
+        })
+        .try_build()
+        .await
+        .expect("failed to build crush");
+    tokio::spawn(crush.run());
+
+    while TcpStream::connect(address).await.is_err() {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    let station_count = usize::try_from(stations).expect("station count fits into usize");
+    let barrier = Arc::new(Barrier::new(station_count + 2));
+
+    let slow = tokio::spawn(station(
+        address,
+        SLOW_STATION.to_owned(),
+        1,
+        Arc::clone(&barrier),
+    ));
+
+    let mut tasks = JoinSet::new();
+    for index in 0..stations {
+        tasks.spawn(station(
+            address,
+            format!("CP{index:05}"),
+            calls,
+            Arc::clone(&barrier),
+        ));
+    }
+
+    barrier.wait().await;
+    let start = Instant::now();
+
+    let mut latencies = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        latencies.extend(result.expect("station task failed"));
+    }
+    let elapsed = start.elapsed();
+
+    let slow_latency = slow.await.expect("slow station task failed");
+
+    latencies.sort_unstable();
+    let total = latencies.len();
+    let throughput = u128::try_from(total).unwrap_or(u128::MAX) * 1000 / elapsed.as_millis().max(1);
+
+    println!("stations:        {stations} (+1 slow station)");
+    println!("calls/station:   {calls}");
+    println!("handler delay:   {handler_delay:?} (slow station: {SLOW_HANDLER_DELAY:?})");
+    println!("round trips:     {total} in {elapsed:?}");
+    println!("throughput:      {throughput} calls/s");
+    println!("latency p50:     {:?}", percentile(&latencies, 500));
+    println!("latency p90:     {:?}", percentile(&latencies, 900));
+    println!("latency p99:     {:?}", percentile(&latencies, 990));
+    println!("latency p99.9:   {:?}", percentile(&latencies, 999));
+    println!(
+        "latency max:     {:?}",
+        latencies.last().copied().unwrap_or_default()
+    );
+    println!("slow station:    {:?}", slow_latency.first());
+}
@@ -1,79 +1,185 @@
 use http_body_util::Full;
 use hyper::{
     body::{Bytes, Incoming},
+    header::{self, HeaderValue},
     server::conn::http1,
     service::service_fn,
-    Request, Response, StatusCode,
+    HeaderMap, Request, Response, StatusCode,
 };
 use hyper_util::rt::TokioIo;
-use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::oneshot,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
 
 use crate::{
+    certificate::ClientCertificate,
     client_loop::{ClientHandle, ClientInfo},
+    config::{Config, RegistrationPolicy},
+    connection::{ConnectionRequest, HandleConnection},
+    credentials::{basic_credentials, CredentialStore},
     error::CrushResult,
-    server_loop::ServerHandle,
+    events::Events,
+    interceptor::CallService,
+    keepalive::Keepalive,
+    server_loop::{ServerHandle, ToServer},
+    session::Session,
+    station_state::StationStates,
+    tls::{Tls, TlsConfig},
+    CrushHandle,
 };
 
-struct Accept {
-    bind: SocketAddr,
+/// How long to back off after `accept` failed, e.g. because the process ran out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+/// How long a station may take to complete the TLS handshake.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The WebSocket subprotocols crush speaks.
+const SUPPORTED_PROTOCOLS: [&str; 1] = ["ocpp1.6"];
+
+/// Everything needed to turn an accepted connection into a station session.
+#[derive(Clone)]
+struct Upgrade {
     server_handle: ServerHandle,
+    service: CallService,
+    keepalive: Keepalive,
+    registration_policy: RegistrationPolicy,
+    events: Events,
+    states: StationStates,
+    connection_handler: Arc<dyn HandleConnection>,
+    credentials: Option<Arc<dyn CredentialStore>>,
+    unsecured_stations: bool,
+    requires_client_certificate: bool,
+}
+
+struct Accept {
+    listener: TcpListener,
+    upgrade: Upgrade,
+    tls: Option<Arc<Tls>>,
 }
 
 impl Accept {
-    fn new(bind: SocketAddr, server_handle: ServerHandle) -> Self {
+    fn new(listener: TcpListener, upgrade: Upgrade, tls: Option<Arc<Tls>>) -> Self {
         Self {
-            bind,
-            server_handle,
+            listener,
+            upgrade,
+            tls,
         }
     }
-    async fn accept_loop(&self) -> CrushResult<()> {
-        let listener = TcpListener::bind(self.bind).await?;
+    #[allow(
+        clippy::infinite_loop,
+        reason = "Accepting connections only stops when crush is dropped"
+    )]
+    async fn accept_loop(&self) {
+        if let Some(tls) = &self.tls {
+            tokio::spawn(Arc::clone(tls).watch());
+        }
 
         loop {
-            let (tcp, ip) = listener.accept().await?;
-            let server_handle = self.server_handle.clone();
+            let (tcp, ip) = match self.listener.accept().await {
+                Ok(connection) => connection,
+                Err(error) => {
+                    tracing::error!("Failed to accept connection: {error}");
+                    sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            };
+            let upgrade = self.upgrade.clone();
+            let tls = self.tls.clone();
             tokio::spawn(async move {
-                let tokio_io = TokioIo::new(tcp);
-
-                let service =
-                    service_fn(move |request| handle_request(request, ip, server_handle.clone()));
-
-                let connection = http1::Builder::new()
-                    .serve_connection(tokio_io, service)
-                    .with_upgrades();
-
-                if let Err(error) = connection.await {
-                    tracing::error!("{error}");
+                let Some(tls) = tls else {
+                    serve_connection(tcp, ip, None, upgrade).await;
+                    return;
+                };
+                let Some(acceptor) = tls.acceptor() else {
+                    return;
+                };
+                match timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                    Ok(Ok(stream)) => {
+                        let certificate = stream
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .and_then(<[_]>::first)
+                            .and_then(|der| ClientCertificate::parse(der))
+                            .map(Arc::new);
+                        serve_connection(stream, ip, certificate, upgrade).await;
+                    }
+                    Ok(Err(error)) => tracing::info!("TLS handshake with {ip} failed: {error}"),
+                    Err(_elapsed) => tracing::info!("TLS handshake with {ip} timed out"),
                 }
             });
         }
     }
 }
 
-async fn run_accept(accept_actor: Accept) -> CrushResult<()> {
-    accept_actor.accept_loop().await?;
-    Ok(())
+/// Serves the HTTP requests of a connection until it is upgraded to a WebSocket or closed.
+async fn serve_connection<Io>(
+    io: Io,
+    ip: SocketAddr,
+    certificate: Option<Arc<ClientCertificate>>,
+    upgrade: Upgrade,
+) where
+    Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        handle_request(request, ip, certificate.clone(), upgrade.clone())
+    });
+
+    let connection = http1::Builder::new()
+        .serve_connection(TokioIo::new(io), service)
+        .with_upgrades();
+
+    if let Err(error) = connection.await {
+        tracing::error!("{error}");
+    }
+}
+
+async fn run_accept(accept_actor: Accept) {
+    accept_actor.accept_loop().await;
 }
 
 pub(crate) struct AcceptHandle;
 
 impl AcceptHandle {
-    pub(crate) fn start(bind: SocketAddr, server_handle: ServerHandle) {
-        let actor = Accept::new(bind, server_handle);
-        tokio::spawn(async move {
-            if let Err(error) = run_accept(actor).await {
-                tracing::error!("{error}");
-            }
-        });
+    /// Accepts connections on `listener`, with the certificate of `crush` already loaded if
+    /// TLS is configured.
+    pub(crate) fn start(
+        config: &Config,
+        listener: TcpListener,
+        crush: &CrushHandle,
+        service: CallService,
+        connection_handler: Arc<dyn HandleConnection>,
+    ) -> JoinHandle<()> {
+        let upgrade = Upgrade {
+            server_handle: crush.server_handle.clone(),
+            service,
+            keepalive: config.keepalive,
+            registration_policy: config.registration_policy,
+            events: crush.events.clone(),
+            states: crush.states.clone(),
+            connection_handler,
+            credentials: crush.credentials.clone(),
+            unsecured_stations: config.unsecured_stations,
+            requires_client_certificate: config
+                .tls
+                .as_ref()
+                .is_some_and(TlsConfig::requires_client_certificate),
+        };
+        let tls = crush.tls.clone();
+        let actor = Accept::new(listener, upgrade, tls);
+        tokio::spawn(run_accept(actor))
     }
 }
 
-#[allow(clippy::unused_async, reason = "Used for closure")]
 async fn handle_request(
     mut request: Request<Incoming>,
     ip: SocketAddr,
-    server_handle: ServerHandle,
+    certificate: Option<Arc<ClientCertificate>>,
+    upgrade: Upgrade,
 ) -> CrushResult<Response<Full<Bytes>>> {
     if !hyper_tungstenite::is_upgrade_request(&request) {
         let body = Full::<Bytes>::from("This endpoint requires a WebSocket upgrade request.");
@@ -91,32 +197,172 @@ async fn handle_request(
         ExtractNameResult::Error(response) => return Ok(response),
     };
 
-    let Ok((response, websocket)) = hyper_tungstenite::upgrade(&mut request, None) else {
-        let body = Full::<Bytes>::from("WebSocket upgrade failed. Please try again.");
-        let response = Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .header("Content-Type", "text/plain")
-            .body(body)?;
-        return Ok(response);
+    if upgrade.requires_client_certificate
+        && !certificate
+            .as_deref()
+            .is_some_and(|certificate| certificate.identifies(&name))
+    {
+        tracing::info!("Refused connection of {name} from {ip}: client certificate mismatch");
+        return plain_response(
+            StatusCode::FORBIDDEN,
+            "The client certificate does not identify this station.",
+        );
+    }
+
+    if let Some(credentials) = &upgrade.credentials {
+        let authenticated = authenticate(
+            credentials.as_ref(),
+            &name,
+            request.headers(),
+            upgrade.unsecured_stations,
+        )
+        .await;
+        if !authenticated {
+            tracing::info!("Refused connection of {name} from {ip}: authentication failed");
+            let body = Full::<Bytes>::from("Authentication failed.");
+            let response = Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(header::WWW_AUTHENTICATE, r#"Basic realm="OCPP""#)
+                .header("Content-Type", "text/plain")
+                .body(body)?;
+            return Ok(response);
+        }
+    }
+
+    let connection_request = ConnectionRequest::new(
+        name.clone(),
+        ip,
+        request.headers().clone(),
+        certificate.clone(),
+    );
+    let decision = upgrade.connection_handler.handle(connection_request).await;
+    let metadata = match decision.into_result() {
+        Ok(metadata) => metadata,
+        Err(status) => {
+            tracing::info!("Refused connection of {name} from {ip} with {status}");
+            return plain_response(status, "Connection refused.");
+        }
     };
 
-    tokio::spawn(async move {
-        let id = server_handle.next_id();
+    let protocol = negotiate_protocol(&request);
 
-        let client_info = ClientInfo {
-            ip,
-            id,
-            name,
-            server_handle,
-            websocket,
-        };
+    let Ok((mut upgrade_response, websocket)) = hyper_tungstenite::upgrade(&mut request, None)
+    else {
+        return plain_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "WebSocket upgrade failed. Please try again.",
+        );
+    };
 
-        ClientHandle::spawn(client_info);
-    });
+    if let Some(protocol) = protocol {
+        upgrade_response.headers_mut().insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(protocol),
+        );
+    }
+
+    let session = Session::new(
+        name,
+        ip,
+        protocol.map(str::to_owned),
+        certificate,
+        metadata,
+        upgrade.events,
+        upgrade.states,
+    );
+    let client_info = ClientInfo {
+        id: upgrade.server_handle.next_id(),
+        session: Arc::new(session),
+        server_handle: upgrade.server_handle.clone(),
+        service: upgrade.service,
+        keepalive: upgrade.keepalive,
+        registration_policy: upgrade.registration_policy,
+        websocket,
+    };
+    let (client_handle, client_join) = ClientHandle::spawn(client_info);
+
+    if !admit(&upgrade.server_handle, client_handle, client_join).await? {
+        return plain_response(
+            StatusCode::CONFLICT,
+            "A station with this id is already connected.",
+        );
+    }
+
+    Ok(upgrade_response)
+}
 
+/// Registers the session with the server loop, answering whether the duplicate connection
+/// policy admits it.
+///
+/// The task of a session that is not admitted is aborted before it talks to the station.
+async fn admit(
+    server_handle: &ServerHandle,
+    client_handle: ClientHandle,
+    client_join: JoinHandle<()>,
+) -> CrushResult<bool> {
+    let (sender, receiver) = oneshot::channel();
+    let admitted = async {
+        server_handle
+            .send(ToServer::NewClient(client_handle, sender))
+            .await?;
+        Ok(receiver.await?)
+    };
+    let admitted = admitted.await;
+    if !matches!(admitted, Ok(true)) {
+        client_join.abort();
+    }
+    admitted
+}
+
+/// A response refusing the upgrade with `status` and a plain text explanation.
+fn plain_response(status: StatusCode, body: &'static str) -> CrushResult<Response<Full<Bytes>>> {
+    let response = Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain")
+        .body(Full::<Bytes>::from(body))?;
     Ok(response)
 }
 
+/// Checks the HTTP Basic credentials of Security Profile 1, whose username is the station id.
+///
+/// If `unsecured_stations` is enabled, stations the store still allows on Security Profile 0
+/// may leave out the credentials.
+async fn authenticate(
+    credentials: &dyn CredentialStore,
+    name: &str,
+    headers: &HeaderMap,
+    unsecured_stations: bool,
+) -> bool {
+    if !headers.contains_key(header::AUTHORIZATION) {
+        return unsecured_stations && credentials.allows_unsecured(name).await;
+    }
+    match basic_credentials(headers) {
+        Some((username, password)) if username == name => credentials.verify(name, &password).await,
+        _ => false,
+    }
+}
+
+/// Picks the first protocol offered in the station's `Sec-WebSocket-Protocol` that crush speaks.
+fn negotiate_protocol(request: &Request<Incoming>) -> Option<&'static str> {
+    let offered = request
+        .headers()
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim);
+
+    for protocol in offered {
+        if let Some(supported) = SUPPORTED_PROTOCOLS
+            .iter()
+            .find(|supported| supported.eq_ignore_ascii_case(protocol))
+        {
+            return Some(supported);
+        }
+    }
+    None
+}
+
 enum ExtractNameResult {
     Name(String),
     Error(Response<Full<Bytes>>),
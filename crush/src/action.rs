@@ -0,0 +1,241 @@
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, future::Future, marker::PhantomData};
+
+use crate::{
+    actions::{
+        BootNotification, Heartbeat, LogStatusNotification, MeterValues, SecurityEventNotification,
+        SignCertificate, SignedFirmwareStatusNotification, StartTransaction, StatusNotification,
+        StopTransaction,
+    },
+    context::StationContext,
+    error::{OcppResponseError, OcppResult},
+    messages::{
+        boot_notification::{BootNotificationHandler, DefaultBootNotificationHandler},
+        heartbeat::{DefaultHeartbeatHandler, HeartbeatHandler},
+        log_status_notification::{
+            DefaultLogStatusNotificationHandler, LogStatusNotificationHandler,
+        },
+        meter_values::{DefaultMeterValuesHandler, MeterValuesHandler},
+        security_event_notification::{
+            DefaultSecurityEventNotificationHandler, SecurityEventNotificationHandler,
+        },
+        sign_certificate::{DefaultSignCertificateHandler, SignCertificateHandler},
+        signed_firmware_status_notification::{
+            DefaultSignedFirmwareStatusNotificationHandler, SignedFirmwareStatusNotificationHandler,
+        },
+        start_transaction::{DefaultStartTransactionHandler, StartTransactionHandler},
+        status_notification::{DefaultStatusNotificationHandler, StatusNotificationHandler},
+        stop_transaction::{DefaultStopTransactionHandler, StopTransactionHandler},
+    },
+};
+
+/// An OCPP action, tying the action name used on the wire to its request and response payloads.
+///
+/// Marker types for every OCPP 1.6 action are available in [`crate::actions`]. Custom or vendor
+/// specific actions can be added by implementing this trait on a new marker type.
+///
+/// # Examples
+///
+/// ```rust
+/// # use crush::Action;
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct VendorPingRequest {
+///     nonce: u32,
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct VendorPingResponse {
+///     nonce: u32,
+/// }
+///
+/// struct VendorPing;
+///
+/// impl Action for VendorPing {
+///     const NAME: &'static str = "VendorPing";
+///     type Request = VendorPingRequest;
+///     type Response = VendorPingResponse;
+/// }
+/// ```
+pub trait Action: Send + Sync + 'static {
+    /// The action name as it appears in the third element of a CALL frame.
+    const NAME: &'static str;
+    type Request: Serialize + DeserializeOwned + Send + 'static;
+    type Response: Serialize + DeserializeOwned + Send + 'static;
+}
+
+/// Handles incoming requests for the action `A`.
+///
+/// Implemented for every `Fn(StationContext, A::Request)` returning a future, so closures can be
+/// registered as well as dedicated handler types.
+#[async_trait]
+pub trait HandleAction<A: Action>: Send + Sync {
+    async fn handle(&self, context: StationContext, request: A::Request)
+        -> OcppResult<A::Response>;
+}
+
+#[async_trait]
+impl<A, F, Fut> HandleAction<A> for F
+where
+    A: Action,
+    F: Fn(StationContext, A::Request) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<A::Response>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: A::Request,
+    ) -> OcppResult<A::Response> {
+        self(context, request).await
+    }
+}
+
+/// Handles requests for actions that have no handler registered, such as vendor extensions.
+///
+/// The handler receives the action name and the raw JSON payload of the CALL and returns the raw
+/// JSON payload of the CALLRESULT. Implemented for every `Fn(StationContext, String, Value)`
+/// returning a future.
+#[async_trait]
+pub trait HandleFallback: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        action: String,
+        payload: Value,
+    ) -> OcppResult<Value>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleFallback for F
+where
+    F: Fn(StationContext, String, Value) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<Value>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        action: String,
+        payload: Value,
+    ) -> OcppResult<Value> {
+        self(context, action, payload).await
+    }
+}
+
+/// A handler with its request and response types erased to raw JSON payloads.
+#[async_trait]
+pub(crate) trait ErasedHandler: Send + Sync {
+    async fn call(&self, context: StationContext, payload: Value) -> OcppResult<Value>;
+}
+
+struct TypedHandler<A, H> {
+    handler: H,
+    action: PhantomData<fn() -> A>,
+}
+
+#[async_trait]
+impl<A, H> ErasedHandler for TypedHandler<A, H>
+where
+    A: Action,
+    H: HandleAction<A>,
+{
+    async fn call(&self, context: StationContext, payload: Value) -> OcppResult<Value> {
+        let request = serde_json::from_value::<A::Request>(payload).map_err(|error| {
+            OcppResponseError::InvalidRequestFormat {
+                details: Value::String(format!(
+                    "Failed to deserialize {} request: {error}",
+                    A::NAME
+                )),
+            }
+        })?;
+
+        let response = self.handler.handle(context, request).await?;
+
+        serde_json::to_value(response).map_err(|error| {
+            tracing::error!("Failed to serialize {} response: {error}", A::NAME);
+            OcppResponseError::InternalError
+        })
+    }
+}
+
+/// Maps action names to the handlers responsible for them.
+pub(crate) struct ActionRegistry {
+    handlers: HashMap<&'static str, Box<dyn ErasedHandler>>,
+    fallback: Option<Box<dyn HandleFallback>>,
+}
+
+impl ActionRegistry {
+    /// Creates a registry with the default handlers for the actions crush answers out of the box.
+    pub(crate) fn with_defaults() -> Self {
+        let mut registry = Self {
+            handlers: HashMap::default(),
+            fallback: None,
+        };
+        registry.register::<BootNotification, _>(BootNotificationHandler(
+            DefaultBootNotificationHandler,
+        ));
+        registry.register::<Heartbeat, _>(HeartbeatHandler(DefaultHeartbeatHandler));
+        registry.register::<StatusNotification, _>(StatusNotificationHandler(
+            DefaultStatusNotificationHandler,
+        ));
+        registry.register::<StartTransaction, _>(StartTransactionHandler(
+            DefaultStartTransactionHandler,
+        ));
+        registry.register::<MeterValues, _>(MeterValuesHandler(DefaultMeterValuesHandler));
+        registry
+            .register::<StopTransaction, _>(StopTransactionHandler(DefaultStopTransactionHandler));
+        registry.register::<SecurityEventNotification, _>(SecurityEventNotificationHandler(
+            DefaultSecurityEventNotificationHandler,
+        ));
+        registry
+            .register::<SignCertificate, _>(SignCertificateHandler(DefaultSignCertificateHandler));
+        registry.register::<SignedFirmwareStatusNotification, _>(
+            SignedFirmwareStatusNotificationHandler(DefaultSignedFirmwareStatusNotificationHandler),
+        );
+        registry.register::<LogStatusNotification, _>(LogStatusNotificationHandler(
+            DefaultLogStatusNotificationHandler,
+        ));
+        registry
+    }
+
+    /// Registers `handler` for `A`, replacing any handler registered before.
+    pub(crate) fn register<A, H>(&mut self, handler: H)
+    where
+        A: Action,
+        H: HandleAction<A> + 'static,
+    {
+        let handler = TypedHandler::<A, H> {
+            handler,
+            action: PhantomData,
+        };
+        self.handlers.insert(A::NAME, Box::new(handler));
+    }
+
+    /// Sets the handler for actions without a registered handler.
+    pub(crate) fn set_fallback<H>(&mut self, handler: H)
+    where
+        H: HandleFallback + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+    }
+
+    /// Routes `payload` to the handler registered for `action`, or to the fallback handler.
+    pub(crate) async fn dispatch(
+        &self,
+        context: StationContext,
+        action: String,
+        payload: Value,
+    ) -> OcppResult<Value> {
+        if let Some(handler) = self.handlers.get(action.as_str()) {
+            return handler.call(context, payload).await;
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.handle(context, action, payload).await,
+            None => Err(OcppResponseError::UnsupportedMessageType {
+                details: Value::String(format!("Unknown message type: '{action}'.")),
+            }),
+        }
+    }
+}
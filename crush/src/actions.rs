@@ -0,0 +1,341 @@
+//! Marker types for the OCPP 1.6 actions.
+//!
+//! Each marker implements [`Action`] and can be passed to
+//! [`CrushBuilder::with_action_handler`](crate::CrushBuilder::with_action_handler).
+
+use rust_ocpp::v1_6::messages::{
+    authorize::{AuthorizeRequest, AuthorizeResponse},
+    boot_notification::{BootNotificationRequest, BootNotificationResponse},
+    cancel_reservation::{CancelReservationRequest, CancelReservationResponse},
+    change_availability::{ChangeAvailabilityRequest, ChangeAvailabilityResponse},
+    change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
+    clear_cache::{ClearCacheRequest, ClearCacheResponse},
+    clear_charging_profile::{ClearChargingProfileRequest, ClearChargingProfileResponse},
+    data_transfer::{DataTransferRequest, DataTransferResponse},
+    diagnostics_status_notification::{
+        DiagnosticsStatusNotificationRequest, DiagnosticsStatusNotificationResponse,
+    },
+    firmware_status_notification::{
+        FirmwareStatusNotificationRequest, FirmwareStatusNotificationResponse,
+    },
+    get_composite_schedule::{GetCompositeScheduleRequest, GetCompositeScheduleResponse},
+    get_configuration::{GetConfigurationRequest, GetConfigurationResponse},
+    get_diagnostics::{GetDiagnosticsRequest, GetDiagnosticsResponse},
+    get_local_list_version::{GetLocalListVersionRequest, GetLocalListVersionResponse},
+    heart_beat::{HeartbeatRequest, HeartbeatResponse},
+    meter_values::{MeterValuesRequest, MeterValuesResponse},
+    remote_start_transaction::{RemoteStartTransactionRequest, RemoteStartTransactionResponse},
+    remote_stop_transaction::{RemoteStopTransactionRequest, RemoteStopTransactionResponse},
+    reserve_now::{ReserveNowRequest, ReserveNowResponse},
+    reset::{ResetRequest, ResetResponse},
+    send_local_list::{SendLocalListRequest, SendLocalListResponse},
+    set_charging_profile::{SetChargingProfileRequest, SetChargingProfileResponse},
+    start_transaction::{StartTransactionRequest, StartTransactionResponse},
+    status_notification::{StatusNotificationRequest, StatusNotificationResponse},
+    stop_transaction::{StopTransactionRequest, StopTransactionResponse},
+    trigger_message::{TriggerMessageRequest, TriggerMessageResponse},
+    unlock_connector::{UnlockConnectorRequest, UnlockConnectorResponse},
+    update_firmware::{UpdateFirmwareRequest, UpdateFirmwareResponse},
+};
+
+use crate::{
+    action::Action,
+    security::{
+        CertificateSignedRequest, CertificateSignedResponse, DeleteCertificateRequest,
+        DeleteCertificateResponse, ExtendedTriggerMessageRequest, ExtendedTriggerMessageResponse,
+        GetInstalledCertificateIdsRequest, GetInstalledCertificateIdsResponse, GetLogRequest,
+        GetLogResponse, InstallCertificateRequest, InstallCertificateResponse,
+        LogStatusNotificationRequest, LogStatusNotificationResponse,
+        SecurityEventNotificationRequest, SecurityEventNotificationResponse,
+        SignCertificateRequest, SignCertificateResponse, SignedFirmwareStatusNotificationRequest,
+        SignedFirmwareStatusNotificationResponse, SignedUpdateFirmwareRequest,
+        SignedUpdateFirmwareResponse,
+    },
+};
+
+macro_rules! ocpp_action {
+    ($(#[$meta:meta])* $action:ident, $request:ty, $response:ty) => {
+        $(#[$meta])*
+        pub struct $action;
+
+        impl Action for $action {
+            const NAME: &'static str = stringify!($action);
+            type Request = $request;
+            type Response = $response;
+        }
+    };
+}
+
+ocpp_action!(
+    /// `Authorize`, initiated by the charge point.
+    Authorize,
+    AuthorizeRequest,
+    AuthorizeResponse
+);
+
+ocpp_action!(
+    /// `BootNotification`, initiated by the charge point.
+    BootNotification,
+    BootNotificationRequest,
+    BootNotificationResponse
+);
+
+ocpp_action!(
+    /// `DataTransfer`, initiated by either side.
+    DataTransfer,
+    DataTransferRequest,
+    DataTransferResponse
+);
+
+ocpp_action!(
+    /// `DiagnosticsStatusNotification`, initiated by the charge point.
+    DiagnosticsStatusNotification,
+    DiagnosticsStatusNotificationRequest,
+    DiagnosticsStatusNotificationResponse
+);
+
+ocpp_action!(
+    /// `FirmwareStatusNotification`, initiated by the charge point.
+    FirmwareStatusNotification,
+    FirmwareStatusNotificationRequest,
+    FirmwareStatusNotificationResponse
+);
+
+ocpp_action!(
+    /// `Heartbeat`, initiated by the charge point.
+    Heartbeat,
+    HeartbeatRequest,
+    HeartbeatResponse
+);
+
+ocpp_action!(
+    /// `MeterValues`, initiated by the charge point.
+    MeterValues,
+    MeterValuesRequest,
+    MeterValuesResponse
+);
+
+ocpp_action!(
+    /// `StartTransaction`, initiated by the charge point.
+    StartTransaction,
+    StartTransactionRequest,
+    StartTransactionResponse
+);
+
+ocpp_action!(
+    /// `StatusNotification`, initiated by the charge point.
+    StatusNotification,
+    StatusNotificationRequest,
+    StatusNotificationResponse
+);
+
+ocpp_action!(
+    /// `StopTransaction`, initiated by the charge point.
+    StopTransaction,
+    StopTransactionRequest,
+    StopTransactionResponse
+);
+
+ocpp_action!(
+    /// `CancelReservation`, initiated by the central system.
+    CancelReservation,
+    CancelReservationRequest,
+    CancelReservationResponse
+);
+
+ocpp_action!(
+    /// `ChangeAvailability`, initiated by the central system.
+    ChangeAvailability,
+    ChangeAvailabilityRequest,
+    ChangeAvailabilityResponse
+);
+
+ocpp_action!(
+    /// `ChangeConfiguration`, initiated by the central system.
+    ChangeConfiguration,
+    ChangeConfigurationRequest,
+    ChangeConfigurationResponse
+);
+
+ocpp_action!(
+    /// `ClearCache`, initiated by the central system.
+    ClearCache,
+    ClearCacheRequest,
+    ClearCacheResponse
+);
+
+ocpp_action!(
+    /// `ClearChargingProfile`, initiated by the central system.
+    ClearChargingProfile,
+    ClearChargingProfileRequest,
+    ClearChargingProfileResponse
+);
+
+ocpp_action!(
+    /// `GetCompositeSchedule`, initiated by the central system.
+    GetCompositeSchedule,
+    GetCompositeScheduleRequest,
+    GetCompositeScheduleResponse
+);
+
+ocpp_action!(
+    /// `GetConfiguration`, initiated by the central system.
+    GetConfiguration,
+    GetConfigurationRequest,
+    GetConfigurationResponse
+);
+
+ocpp_action!(
+    /// `GetDiagnostics`, initiated by the central system.
+    GetDiagnostics,
+    GetDiagnosticsRequest,
+    GetDiagnosticsResponse
+);
+
+ocpp_action!(
+    /// `GetLocalListVersion`, initiated by the central system.
+    GetLocalListVersion,
+    GetLocalListVersionRequest,
+    GetLocalListVersionResponse
+);
+
+ocpp_action!(
+    /// `RemoteStartTransaction`, initiated by the central system.
+    RemoteStartTransaction,
+    RemoteStartTransactionRequest,
+    RemoteStartTransactionResponse
+);
+
+ocpp_action!(
+    /// `RemoteStopTransaction`, initiated by the central system.
+    RemoteStopTransaction,
+    RemoteStopTransactionRequest,
+    RemoteStopTransactionResponse
+);
+
+ocpp_action!(
+    /// `ReserveNow`, initiated by the central system.
+    ReserveNow,
+    ReserveNowRequest,
+    ReserveNowResponse
+);
+
+ocpp_action!(
+    /// `Reset`, initiated by the central system.
+    Reset,
+    ResetRequest,
+    ResetResponse
+);
+
+ocpp_action!(
+    /// `SendLocalList`, initiated by the central system.
+    SendLocalList,
+    SendLocalListRequest,
+    SendLocalListResponse
+);
+
+ocpp_action!(
+    /// `SetChargingProfile`, initiated by the central system.
+    SetChargingProfile,
+    SetChargingProfileRequest,
+    SetChargingProfileResponse
+);
+
+ocpp_action!(
+    /// `TriggerMessage`, initiated by the central system.
+    TriggerMessage,
+    TriggerMessageRequest,
+    TriggerMessageResponse
+);
+
+ocpp_action!(
+    /// `UnlockConnector`, initiated by the central system.
+    UnlockConnector,
+    UnlockConnectorRequest,
+    UnlockConnectorResponse
+);
+
+ocpp_action!(
+    /// `UpdateFirmware`, initiated by the central system.
+    UpdateFirmware,
+    UpdateFirmwareRequest,
+    UpdateFirmwareResponse
+);
+
+ocpp_action!(
+    /// `SecurityEventNotification` of the security extension, initiated by the charge point.
+    SecurityEventNotification,
+    SecurityEventNotificationRequest,
+    SecurityEventNotificationResponse
+);
+
+ocpp_action!(
+    /// `SignCertificate` of the security extension, initiated by the charge point.
+    SignCertificate,
+    SignCertificateRequest,
+    SignCertificateResponse
+);
+
+ocpp_action!(
+    /// `CertificateSigned` of the security extension, initiated by the central system.
+    CertificateSigned,
+    CertificateSignedRequest,
+    CertificateSignedResponse
+);
+
+ocpp_action!(
+    /// `DeleteCertificate` of the security extension, initiated by the central system.
+    DeleteCertificate,
+    DeleteCertificateRequest,
+    DeleteCertificateResponse
+);
+
+ocpp_action!(
+    /// `ExtendedTriggerMessage` of the security extension, initiated by the central system.
+    ExtendedTriggerMessage,
+    ExtendedTriggerMessageRequest,
+    ExtendedTriggerMessageResponse
+);
+
+ocpp_action!(
+    /// `GetInstalledCertificateIds` of the security extension, initiated by the central system.
+    GetInstalledCertificateIds,
+    GetInstalledCertificateIdsRequest,
+    GetInstalledCertificateIdsResponse
+);
+
+ocpp_action!(
+    /// `InstallCertificate` of the security extension, initiated by the central system.
+    InstallCertificate,
+    InstallCertificateRequest,
+    InstallCertificateResponse
+);
+
+ocpp_action!(
+    /// `SignedUpdateFirmware` of the security extension, initiated by the central system.
+    SignedUpdateFirmware,
+    SignedUpdateFirmwareRequest,
+    SignedUpdateFirmwareResponse
+);
+
+ocpp_action!(
+    /// `SignedFirmwareStatusNotification` of the security extension, initiated by the charge
+    /// point.
+    SignedFirmwareStatusNotification,
+    SignedFirmwareStatusNotificationRequest,
+    SignedFirmwareStatusNotificationResponse
+);
+
+ocpp_action!(
+    /// `GetLog` of the security extension, initiated by the central system.
+    GetLog,
+    GetLogRequest,
+    GetLogResponse
+);
+
+ocpp_action!(
+    /// `LogStatusNotification` of the security extension, initiated by the charge point.
+    LogStatusNotification,
+    LogStatusNotificationRequest,
+    LogStatusNotificationResponse
+);
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use x509_parser::{
+    certificate::X509Certificate, oid_registry::OID_X509_SERIALNUMBER, prelude::FromDer,
+    x509::AttributeTypeAndValue,
+};
+
+/// The certificate a station authenticated with over mutual TLS, OCPP Security Profile 3.
+///
+/// Available to handlers through [`crate::StationContext::client_certificate`] once the
+/// certificate chain has been verified against the configured CA bundle, see
+/// [`crate::TlsConfig::with_client_ca`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    der: Vec<u8>,
+    subject: String,
+    issuer: String,
+    common_name: Option<String>,
+    subject_serial_number: Option<String>,
+    serial_number: String,
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+}
+
+impl ClientCertificate {
+    /// Reads the details of a DER encoded end-entity certificate.
+    pub(crate) fn parse(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(der).ok()?;
+        Some(Self {
+            der: der.to_vec(),
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            common_name: first_attribute(certificate.subject().iter_common_name()),
+            subject_serial_number: first_attribute(
+                certificate.subject().iter_by_oid(&OID_X509_SERIALNUMBER),
+            ),
+            serial_number: certificate.raw_serial_as_string(),
+            not_before: DateTime::from_timestamp(certificate.validity().not_before.timestamp(), 0)?,
+            not_after: DateTime::from_timestamp(certificate.validity().not_after.timestamp(), 0)?,
+        })
+    }
+
+    /// Whether the subject names the station, by its common name or serial number attribute.
+    pub(crate) fn identifies(&self, station_id: &str) -> bool {
+        self.common_name.as_deref() == Some(station_id)
+            || self.subject_serial_number.as_deref() == Some(station_id)
+    }
+
+    /// The certificate as presented in the TLS handshake, DER encoded.
+    #[must_use]
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The distinguished name of the subject, such as `CN=CP001, O=ACME`.
+    #[must_use]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The distinguished name of the CA that issued the certificate.
+    #[must_use]
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// The common name (CN) of the subject.
+    #[must_use]
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// The serial number attribute of the subject, which identifies the station, not the
+    /// certificate.
+    #[must_use]
+    pub fn subject_serial_number(&self) -> Option<&str> {
+        self.subject_serial_number.as_deref()
+    }
+
+    /// The serial number the CA assigned to the certificate, as colon separated hex bytes.
+    #[must_use]
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    /// The start of the validity period.
+    #[must_use]
+    pub fn not_before(&self) -> DateTime<Utc> {
+        self.not_before
+    }
+
+    /// The end of the validity period.
+    #[must_use]
+    pub fn not_after(&self) -> DateTime<Utc> {
+        self.not_after
+    }
+}
+
+fn first_attribute<'name>(
+    mut attributes: impl Iterator<Item = &'name AttributeTypeAndValue<'name>>,
+) -> Option<String> {
+    attributes
+        .next()
+        .and_then(|attribute| attribute.as_str().ok())
+        .map(str::to_owned)
+}
@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use rcgen::{
+    Certificate, CertificateParams, CertificateSigningRequestParams, DistinguishedName, DnType,
+    DnValue, ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SerialNumber,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, OnceLock, Weak},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::timeout,
+};
+
+use crate::{
+    actions::{CertificateSigned, SignCertificate},
+    context::StationContext,
+    error::{CertificateAuthorityError, OcppResult},
+    events::{Direction, Event, EventKind},
+    messages::sign_certificate::HandleSignCertificateRequest,
+    security::{
+        CertificateSignedRequest, CertificateSignedStatus, GenericStatus, SignCertificateRequest,
+        SignCertificateResponse,
+    },
+    Action, CrushHandle,
+};
+
+const SERIAL_NUMBER_LEN: usize = 16;
+
+/// A local CA signing the certificates charge points request with `SignCertificate`.
+///
+/// Meant for test labs and small deployments that do not run a PKI of their own. A CSR is only
+/// signed if the common name of its subject is the id of the station that sent it and its
+/// organization matches, see [`CertificateAuthority::with_organization`]. Set it with
+/// [`crate::CrushBuilder::with_certificate_authority`] to have crush answer `SignCertificate`
+/// and deliver the chain with `CertificateSigned` on its own.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use crush::{CertificateAuthority, Config, CrushBuilder};
+/// # use std::time::Duration;
+/// let authority =
+///     CertificateAuthority::from_pem_files("/etc/crush/station-ca.crt", "/etc/crush/station-ca.key")
+///         .expect("failed to load the CA")
+///         .with_organization("ACME Charging")
+///         .with_validity(Duration::from_hours(90 * 24));
+///
+/// let config = Config::new("0.0.0.0:443".parse().unwrap());
+/// let builder = CrushBuilder::new(config).with_certificate_authority(authority);
+/// ```
+pub struct CertificateAuthority {
+    certificate_pem: String,
+    issuer: Certificate,
+    key_pair: KeyPair,
+    organization: Option<String>,
+    validity: Duration,
+    random: SystemRandom,
+}
+
+impl CertificateAuthority {
+    /// Loads the CA certificate and its private key from PEM files.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a file cannot be read or does not hold a CA certificate or a private key.
+    pub fn from_pem_files(
+        certificate: impl AsRef<Path>,
+        private_key: impl AsRef<Path>,
+    ) -> Result<Self, CertificateAuthorityError> {
+        Self::from_pem(&read(certificate.as_ref())?, &read(private_key.as_ref())?)
+    }
+
+    /// Creates the CA from a PEM encoded certificate and its PEM encoded PKCS#8 private key.
+    ///
+    /// The organization defaults to the one in the subject of the CA certificate and
+    /// certificates are valid for one year.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the certificate or key cannot be parsed or the key cannot sign.
+    pub fn from_pem(
+        certificate: &str,
+        private_key: &str,
+    ) -> Result<Self, CertificateAuthorityError> {
+        let key_pair = KeyPair::from_pem(private_key)
+            .map_err(|error| CertificateAuthorityError::InvalidKey(error.to_string()))?;
+        let params = CertificateParams::from_ca_cert_pem(certificate)
+            .map_err(|error| CertificateAuthorityError::InvalidCertificate(error.to_string()))?;
+        let organization = text(&params.distinguished_name, &DnType::OrganizationName);
+        // Only the subject, key identifier and key of the issuer end up in signed certificates,
+        // so re-signing the parsed parameters restores everything signing needs.
+        let issuer = params
+            .self_signed(&key_pair)
+            .map_err(|error| CertificateAuthorityError::InvalidKey(error.to_string()))?;
+        Ok(Self {
+            certificate_pem: certificate.to_owned(),
+            issuer,
+            key_pair,
+            organization,
+            validity: Duration::from_hours(365 * 24),
+            random: SystemRandom::new(),
+        })
+    }
+
+    /// Sets the organization the subject of every CSR must name.
+    ///
+    /// This is the CPO name the stations are configured with. Without an organization from
+    /// here or the CA certificate the organization of a CSR is not checked.
+    #[must_use]
+    pub fn with_organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    /// Sets how long signed certificates are valid, one year by default.
+    #[must_use]
+    pub fn with_validity(mut self, validity: Duration) -> Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Signs the PEM encoded PKCS#10 `csr` of `station_id`.
+    ///
+    /// Returns the PEM encoded chain of the new station certificate followed by the CA
+    /// certificate, as `CertificateSigned` expects it. Only the subject and public key are taken
+    /// from the CSR, the certificate is always a client certificate that cannot sign others.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the CSR cannot be parsed, its signature is invalid or its subject does not
+    /// match the station and organization.
+    pub fn sign(&self, station_id: &str, csr: &str) -> Result<String, CertificateAuthorityError> {
+        let request = CertificateSigningRequestParams::from_pem(csr)
+            .map_err(|error| CertificateAuthorityError::InvalidRequest(error.to_string()))?;
+        let subject = request.params.distinguished_name;
+
+        let common_name = text(&subject, &DnType::CommonName);
+        if common_name.as_deref() != Some(station_id) {
+            return Err(CertificateAuthorityError::StationMismatch {
+                station_id: station_id.to_owned(),
+                common_name,
+            });
+        }
+        let organization = text(&subject, &DnType::OrganizationName);
+        if let Some(expected) = &self.organization {
+            if organization.as_ref() != Some(expected) {
+                return Err(CertificateAuthorityError::OrganizationMismatch {
+                    expected: expected.clone(),
+                    organization,
+                });
+            }
+        }
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = subject;
+        let not_before = OffsetDateTime::from_unix_timestamp(Utc::now().timestamp())
+            .map_err(|error| CertificateAuthorityError::Signing(error.to_string()))?;
+        params.not_before = not_before;
+        params.not_after = time::Duration::try_from(self.validity)
+            .ok()
+            .and_then(|validity| not_before.checked_add(validity))
+            .ok_or_else(|| CertificateAuthorityError::Signing("validity is too long".to_owned()))?;
+        params.serial_number = Some(self.serial_number()?);
+        params.is_ca = IsCa::ExplicitNoCa;
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        params.use_authority_key_identifier_extension = true;
+
+        let certificate = CertificateSigningRequestParams {
+            params,
+            public_key: request.public_key,
+        }
+        .signed_by(&self.issuer, &self.key_pair)
+        .map_err(|error| CertificateAuthorityError::Signing(error.to_string()))?;
+        Ok(format!("{}{}", certificate.pem(), self.certificate_pem))
+    }
+
+    /// A random positive serial number, unique for all practical purposes.
+    fn serial_number(&self) -> Result<SerialNumber, CertificateAuthorityError> {
+        let mut serial_number = [0; SERIAL_NUMBER_LEN];
+        self.random
+            .fill(&mut serial_number)
+            .map_err(|_unspecified| {
+                CertificateAuthorityError::Signing("no randomness for the serial number".to_owned())
+            })?;
+        if let Some(first) = serial_number.first_mut() {
+            *first &= 0x7f;
+        }
+        Ok(SerialNumber::from_slice(&serial_number))
+    }
+}
+
+fn read(path: &Path) -> Result<String, CertificateAuthorityError> {
+    fs::read_to_string(path).map_err(|source| CertificateAuthorityError::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// The value of the attribute `kind` of `name`, if it holds text.
+fn text(name: &DistinguishedName, kind: &DnType) -> Option<String> {
+    match name.get(kind)? {
+        DnValue::Utf8String(value) => Some(value.clone()),
+        DnValue::PrintableString(value) => Some(value.as_str().to_owned()),
+        DnValue::Ia5String(value) => Some(value.as_str().to_owned()),
+        DnValue::TeletexString(value) => Some(value.as_str().to_owned()),
+        _ => None,
+    }
+}
+
+/// Answers `SignCertificate` with the [`CertificateAuthority`] and delivers the signed chain.
+pub(crate) struct CertificateAuthorityHandler {
+    pub(crate) authority: Arc<CertificateAuthority>,
+    /// Set once crush started. Weak, so the handler does not keep a stopped instance alive.
+    pub(crate) crush: Arc<OnceLock<Weak<CrushHandle>>>,
+}
+
+#[async_trait]
+impl HandleSignCertificateRequest for CertificateAuthorityHandler {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignCertificateRequest,
+    ) -> OcppResult<SignCertificateResponse> {
+        let station_id = context.station_id().to_owned();
+        let Some(crush) = self.crush.get().and_then(Weak::upgrade) else {
+            tracing::warn!("Rejecting SignCertificate of {station_id}, crush is shutting down");
+            return Ok(SignCertificateResponse {
+                status: GenericStatus::Rejected,
+            });
+        };
+        let chain = match self.authority.sign(&station_id, &request.csr) {
+            Ok(chain) => chain,
+            Err(error) => {
+                tracing::warn!("Rejecting SignCertificate of {station_id}: {error}");
+                return Ok(SignCertificateResponse {
+                    status: GenericStatus::Rejected,
+                });
+            }
+        };
+
+        // CertificateSigned must not overtake the response, so it waits until that is sent.
+        let events = crush.events();
+        let crush = CrushHandle::clone(&crush);
+        let call_timeout = crush.call_timeout;
+        tokio::spawn(async move {
+            if timeout(call_timeout, responded(events, &station_id))
+                .await
+                .ok()
+                != Some(true)
+            {
+                tracing::warn!(
+                    "Not sending CertificateSigned to {station_id}, SignCertificate was not accepted"
+                );
+                return;
+            }
+            deliver(&crush, &station_id, chain).await;
+        });
+
+        Ok(SignCertificateResponse {
+            status: GenericStatus::Accepted,
+        })
+    }
+}
+
+/// Waits for the answer to the `SignCertificate` of `station_id`, returning whether it was a
+/// CALLRESULT.
+async fn responded(mut events: Receiver<Event>, station_id: &str) -> bool {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return false,
+        };
+        if event.station_id() != station_id {
+            continue;
+        }
+        match event.kind() {
+            EventKind::ResponseSent { action, .. } if action == SignCertificate::NAME => {
+                return true;
+            }
+            EventKind::Error {
+                direction: Direction::Sent,
+                action,
+                ..
+            } if action == SignCertificate::NAME => return false,
+            _ => {}
+        }
+    }
+}
+
+async fn deliver(crush: &CrushHandle, station_id: &str, certificate_chain: String) {
+    let request = CertificateSignedRequest { certificate_chain };
+    match crush.call::<CertificateSigned>(station_id, request).await {
+        Ok(response) if response.status == CertificateSignedStatus::Accepted => {
+            tracing::info!("{station_id} installed its signed certificate");
+        }
+        Ok(_) => tracing::warn!("{station_id} rejected its signed certificate"),
+        Err(error) => {
+            tracing::warn!("Failed to deliver the signed certificate to {station_id}: {error}");
+        }
+    }
+}
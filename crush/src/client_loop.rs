@@ -1,157 +1,337 @@
 use futures::{
     stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt, TryStreamExt,
+    SinkExt, StreamExt,
 };
 use hyper::upgrade::Upgraded;
-use hyper_tungstenite::{tungstenite::Message, HyperWebsocket, WebSocketStream};
+use hyper_tungstenite::{
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    HyperWebsocket, WebSocketStream,
+};
 use hyper_util::rt::TokioIo;
-use std::net::SocketAddr;
-use tokio::try_join;
+use std::{future, sync::Arc, time::Duration};
 use tokio::{
+    select,
     sync::{
         mpsc::{channel, Receiver, Sender},
-        oneshot,
+        watch,
     },
     task::JoinHandle,
+    time::timeout,
 };
 
 use crate::{
+    config::RegistrationPolicy,
+    controller_loop::{ControllerHandle, ToController},
+    disconnect::DisconnectReason,
     error::CrushResult,
+    events::EventKind,
+    interceptor::CallService,
+    keepalive::{run_keepalive, Activity, Keepalive},
+    outbound::{OutboundCall, PendingCalls},
+    serde::OcppResponse,
     server_loop::{ServerHandle, ToServer},
+    session::Session,
+    supervisor::Supervised,
 };
 
+/// How long writing a single frame may take before the connection is considered dead.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long sending the close frame of a session crush ends itself may take.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub(crate) struct ClientInfo {
-    pub ip: SocketAddr,
     pub id: usize,
-    pub name: String,
+    pub session: Arc<Session>,
     pub server_handle: ServerHandle,
+    pub service: CallService,
+    pub keepalive: Keepalive,
+    pub registration_policy: RegistrationPolicy,
     pub websocket: HyperWebsocket,
 }
 
 pub(crate) enum ToClient {
     Message(String),
+    Ping,
+    Call(OutboundCall),
 }
 
 struct Client {
     id: usize,
+    session: Arc<Session>,
     server_handle: ServerHandle,
+    controller_handle: ControllerHandle,
+    keepalive: Keepalive,
+    registration_policy: RegistrationPolicy,
+    sender: Sender<ToClient>,
     receiver: Receiver<ToClient>,
+    close_receiver: watch::Receiver<Option<DisconnectReason>>,
+    /// Changes when the server loop restarted and has to be told about this session again.
+    server_restarts: watch::Receiver<usize>,
+    handle: ClientHandle,
     websocket: HyperWebsocket,
 }
 
-impl Client {
-    fn new(
-        id: usize,
-        server_handle: ServerHandle,
-        receiver: Receiver<ToClient>,
-        websocket: HyperWebsocket,
-    ) -> Self {
-        Self {
-            id,
-            server_handle,
-            receiver,
-            websocket,
-        }
-    }
-}
-
+/// The registration of a session with the server loop.
+///
+/// It does not own the task of the session, so a crashing server loop does not take the
+/// sessions down with it.
+#[derive(Clone)]
 pub(crate) struct ClientHandle {
     pub(crate) id: usize,
-    ip: SocketAddr,
-    name: String,
+    session: Arc<Session>,
     sender: Sender<ToClient>,
-    client_join: JoinHandle<()>,
+    close_sender: Arc<watch::Sender<Option<DisconnectReason>>>,
+    controller: Supervised,
 }
 
 impl ClientHandle {
-    pub(crate) fn spawn(client_info: ClientInfo) {
+    /// Spawns the session for an upgraded connection.
+    ///
+    /// The session only starts talking to the station once the upgrade response has been sent,
+    /// so the connection can still be refused after this returns, by aborting the returned task.
+    pub(crate) fn spawn(client_info: ClientInfo) -> (Self, JoinHandle<()>) {
         let (sender, receiver) = channel(64);
+        let (close_sender, close_receiver) = watch::channel(None);
 
-        let actor = Client::new(
-            client_info.id,
-            client_info.server_handle,
-            receiver,
-            client_info.websocket,
+        let controller_handle = ControllerHandle::new(
+            Arc::clone(&client_info.session),
+            client_info.service,
+            sender.clone(),
+            client_info.registration_policy,
         );
 
-        let (oneshot_sender, oneshot_receiver) = oneshot::channel();
-        let client_join = tokio::spawn(async move {
-            if let Err(error) = start_client(actor, oneshot_receiver).await {
-                tracing::error!("{error}");
-            };
-        });
-
-        let client_handle = ClientHandle {
+        let handle = Self {
             id: client_info.id,
-            ip: client_info.ip,
-            name: client_info.name,
+            session: Arc::clone(&client_info.session),
+            sender: sender.clone(),
+            close_sender: Arc::new(close_sender),
+            controller: controller_handle.supervised().clone(),
+        };
+        let actor = Client {
+            id: client_info.id,
+            session: client_info.session,
+            server_restarts: client_info.server_handle.restarts(),
+            server_handle: client_info.server_handle,
+            controller_handle,
+            keepalive: client_info.keepalive,
+            registration_policy: client_info.registration_policy,
             sender,
-            client_join,
+            receiver,
+            close_receiver,
+            handle: handle.clone(),
+            websocket: client_info.websocket,
         };
 
-        drop(oneshot_sender.send(client_handle));
+        let client_join = tokio::spawn(async move {
+            if let Err(error) = start_client(actor).await {
+                tracing::error!("{error}");
+            }
+        });
+
+        (handle, client_join)
     }
-    pub(crate) async fn send(&mut self, msg: ToClient) {
-        assert!(
-            self.sender.send(msg).await.is_ok(),
-            "Client loop has shut down"
-        );
+    pub(crate) fn name(&self) -> &str {
+        self.session.station_id()
     }
-}
-
-impl Drop for ClientHandle {
-    fn drop(&mut self) {
-        self.client_join.abort();
+    pub(crate) fn session(&self) -> &Session {
+        &self.session
+    }
+    /// The mailbox of the session, e.g. to send outbound calls through.
+    pub(crate) fn sender(&self) -> Sender<ToClient> {
+        self.sender.clone()
+    }
+    /// The controller handling the CALLs of the session.
+    pub(crate) fn controller(&self) -> &Supervised {
+        &self.controller
+    }
+    /// Ends the session, telling the station why with a close frame.
+    ///
+    /// Only the first reason counts.
+    pub(crate) fn close(&self, reason: DisconnectReason) {
+        self.close_sender.send_if_modified(|closed| {
+            if closed.is_some() {
+                return false;
+            }
+            *closed = Some(reason);
+            true
+        });
     }
 }
 
-async fn start_client(
-    mut client_actor: Client,
-    oneshot_receiver: oneshot::Receiver<ClientHandle>,
-) -> CrushResult<()> {
-    let client_handle = oneshot_receiver.await?;
+async fn start_client(client_actor: Client) -> CrushResult<()> {
+    let id = client_actor.id;
+    let name = client_actor.session.station_id().to_owned();
+    let server_handle = client_actor.server_handle.clone();
+
+    let session = Arc::clone(&client_actor.session);
+
+    let reason = run_client(client_actor).await;
+
+    tracing::info!("Station: {name} disconnected: {reason}");
+    session.emit(|| EventKind::Disconnected {
+        reason: reason.clone(),
+    });
+
+    server_handle.send(ToServer::ClientGone(id, reason)).await?;
+
+    Ok(())
+}
 
+/// Runs the session until either direction of the connection ends.
+///
+/// This is the only place a session ends, so every way a connection can terminate is reported
+/// to the server exactly once, by `start_client`.
+async fn run_client(mut client_actor: Client) -> DisconnectReason {
+    let websocket = match client_actor.websocket.await {
+        Ok(websocket) => websocket,
+        Err(error) => return DisconnectReason::ReadError(error.to_string()),
+    };
     tracing::info!(
         "Station: {} with IP: {} connected.",
-        client_handle.name,
-        client_handle.ip
+        client_actor.session.station_id(),
+        client_actor.session.address()
     );
+    client_actor.session.emit(|| EventKind::Connected {
+        address: client_actor.session.address(),
+        protocol: client_actor.session.protocol().map(str::to_owned),
+    });
 
-    client_actor
-        .server_handle
-        .send(ToServer::NewClient(client_handle))
-        .await;
+    let (mut write, read) = websocket.split();
+    let activity = Activity::new();
+    let pending_calls = PendingCalls::new(
+        Arc::clone(&client_actor.session),
+        client_actor.registration_policy,
+    );
+    let closed = async {
+        let closed = client_actor
+            .close_receiver
+            .wait_for(Option::is_some)
+            .await
+            .map(|reason| reason.clone());
+        match closed {
+            Ok(reason) => reason.unwrap_or(DisconnectReason::Shutdown),
+            // This task holds a handle itself, so the sender outlives it.
+            Err(_) => future::pending().await,
+        }
+    };
 
-    run_client(client_actor).await?;
+    let reason = select! {
+        reason = tcp_read(
+            read,
+            client_actor.controller_handle,
+            &activity,
+            &client_actor.session,
+            &pending_calls,
+        ) => reason,
+        reason = tcp_write(&mut write, &mut client_actor.receiver, &pending_calls) => reason,
+        reason = run_keepalive(
+            client_actor.keepalive,
+            &activity,
+            &client_actor.sender,
+            &client_actor.session,
+        ) => reason,
+        reason = closed => reason,
+        reason = rejoin(
+            &client_actor.server_handle,
+            &mut client_actor.server_restarts,
+            &client_actor.handle,
+        ) => reason,
+    };
 
-    Ok(())
-}
+    if reason.is_initiated_by_server() {
+        let close_frame = CloseFrame {
+            code: CloseCode::Away,
+            reason: reason.to_string().into(),
+        };
+        // The station is most likely gone already, so failing to say goodbye is expected.
+        drop(timeout(CLOSE_TIMEOUT, write.send(Message::Close(Some(close_frame)))).await);
+    }
 
-async fn run_client(client_actor: Client) -> CrushResult<()> {
-    let (write, read) = client_actor.websocket.await?.split();
+    reason
+}
 
-    let ((), ()) = try_join!(
-        tcp_read(client_actor.id, read, client_actor.server_handle),
-        tcp_write(write, client_actor.receiver),
-    )?;
-    Ok(())
+/// Registers the session again every time the server loop restarted.
+async fn rejoin(
+    server_handle: &ServerHandle,
+    restarts: &mut watch::Receiver<usize>,
+    client_handle: &ClientHandle,
+) -> DisconnectReason {
+    while restarts.changed().await.is_ok() {
+        if server_handle
+            .send(ToServer::Rejoin(client_handle.clone()))
+            .await
+            .is_err()
+        {
+            return DisconnectReason::Shutdown;
+        }
+    }
+    // Supervision ended, which it only does once crush shuts down.
+    future::pending().await
 }
 
 async fn tcp_read(
-    id: usize,
     mut read: SplitStream<WebSocketStream<TokioIo<Upgraded>>>,
-    mut server_handle: ServerHandle,
-) -> CrushResult<()> {
-    while let Ok(Some(message)) = read.try_next().await {
+    mut controller_handle: ControllerHandle,
+    activity: &Activity,
+    session: &Session,
+    pending_calls: &PendingCalls,
+) -> DisconnectReason {
+    let mut reason = DisconnectReason::ConnectionLost;
+
+    loop {
+        let message = match read.next().await {
+            Some(Ok(message)) => message,
+            Some(Err(error)) => {
+                // After a close frame the closing handshake is complete, whatever follows.
+                return match reason {
+                    DisconnectReason::Closed { .. } => reason,
+                    _ => DisconnectReason::ReadError(error.to_string()),
+                };
+            }
+            None => return reason,
+        };
+
+        activity.frame_received();
         match message {
-            Message::Text(text) => server_handle.send(ToServer::ClientMessage(id, text)).await,
+            Message::Text(text) => {
+                activity.message_received();
+                session.message_received();
+                match OcppResponse::parse(&text) {
+                    Some(Ok(response)) => {
+                        pending_calls.resolve(response);
+                        continue;
+                    }
+                    Some(Err(error)) => {
+                        tracing::error!(
+                            "Failed to deserialize OcppResponse: {error}. \n Input: \n {text}"
+                        );
+                        continue;
+                    }
+                    None => {}
+                }
+                if controller_handle
+                    .send(ToController::Message(text))
+                    .await
+                    .is_err()
+                {
+                    return DisconnectReason::Shutdown;
+                }
+            }
             Message::Close(close_frame) => {
-                tracing::info!(
-                    "Received close for id {} with close frame: {:?}",
-                    id,
-                    close_frame
-                );
-                server_handle.send(ToServer::ClientGone(id)).await;
+                tracing::debug!("Received close with close frame: {:?}", close_frame);
+                reason = match close_frame {
+                    Some(frame) => DisconnectReason::Closed {
+                        code: frame.code.into(),
+                        reason: frame.reason.into_owned(),
+                    },
+                    None => DisconnectReason::Closed {
+                        code: CloseCode::Status.into(),
+                        reason: String::new(),
+                    },
+                };
             }
             Message::Frame(frame) => {
                 tracing::info!("Frame: {frame}");
@@ -161,17 +341,27 @@ async fn tcp_read(
             }
         }
     }
-    Ok(())
 }
 
 async fn tcp_write(
-    mut write: SplitSink<WebSocketStream<TokioIo<Upgraded>>, Message>,
-    mut receiver: Receiver<ToClient>,
-) -> CrushResult<()> {
+    write: &mut SplitSink<WebSocketStream<TokioIo<Upgraded>>, Message>,
+    receiver: &mut Receiver<ToClient>,
+    pending_calls: &PendingCalls,
+) -> DisconnectReason {
     while let Some(msg) = receiver.recv().await {
-        match msg {
-            ToClient::Message(message) => write.send(message.into()).await?,
+        let message = match msg {
+            ToClient::Message(message) => message.into(),
+            ToClient::Ping => Message::Ping(Vec::new()),
+            ToClient::Call(call) => match pending_calls.register(call) {
+                Some(frame) => frame.into(),
+                None => continue,
+            },
+        };
+        match timeout(WRITE_TIMEOUT, write.send(message)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => return DisconnectReason::WriteError(error.to_string()),
+            Err(_elapsed) => return DisconnectReason::WriteTimeout,
         }
     }
-    Ok(())
+    DisconnectReason::Shutdown
 }
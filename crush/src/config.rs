@@ -0,0 +1,171 @@
+use std::{net::SocketAddr, time::Duration};
+
+use crate::{keepalive::Keepalive, tls::TlsConfig};
+
+/// What to do when a station connects while a session with the same station id is open.
+///
+/// A charger that lost its connection often reconnects before the old socket is detected dead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateConnectionPolicy {
+    /// Close the old session and keep the new one.
+    #[default]
+    Replace,
+    /// Refuse the new connection with `409 Conflict`.
+    Reject,
+    /// Keep both sessions, sending outbound calls to the newest one.
+    AllowBoth,
+}
+
+/// How crush treats CALLs a station sends before its `BootNotification` was accepted.
+///
+/// OCPP 1.6 forbids a charge point to send anything but `BootNotification` until the central
+/// system accepted it, the `RegistrationStatus` returned by the boot notification handler decides.
+/// A station that reconnects keeps the status of its last boot. Stations crush has not seen boot
+/// are let through, charge points do not boot again just because they reconnect.
+///
+/// The messages crush asks a station for with `TriggerMessage` or `ExtendedTriggerMessage` are
+/// accepted from it whatever its status, as OCPP allows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RegistrationPolicy {
+    /// Handle such CALLs anyway, but log a warning and publish [`crate::EventKind::OutOfOrder`].
+    #[default]
+    Flag,
+    /// Answer such CALLs with a `SecurityError` CALLERROR without running their handler.
+    ///
+    /// While a station is `Pending`, [`crate::CrushHandle::call`] is limited
+    /// to `ChangeConfiguration`, `GetConfiguration`, `TriggerMessage` and
+    /// `ExtendedTriggerMessage`. Nothing is sent to a `Rejected` station.
+    Reject,
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub(crate) address: SocketAddr,
+    pub(crate) keepalive: Keepalive,
+    pub(crate) duplicate_connection_policy: DuplicateConnectionPolicy,
+    pub(crate) call_timeout: Duration,
+    pub(crate) event_capacity: usize,
+    pub(crate) tls: Option<TlsConfig>,
+    pub(crate) reconnect_timeout: Duration,
+    pub(crate) registration_policy: RegistrationPolicy,
+    pub(crate) unsecured_stations: bool,
+}
+
+impl Config {
+    #[must_use]
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            keepalive: Keepalive::default(),
+            duplicate_connection_policy: DuplicateConnectionPolicy::default(),
+            call_timeout: Duration::from_secs(30),
+            event_capacity: 1024,
+            tls: None,
+            reconnect_timeout: Duration::from_mins(2),
+            registration_policy: RegistrationPolicy::default(),
+            unsecured_stations: false,
+        }
+    }
+
+    /// Terminates TLS on the listening socket, which OCPP Security Profile 2 requires.
+    ///
+    /// Only TLS 1.2 and 1.3 are accepted, with the AES-GCM cipher suites demanded by the OCPP
+    /// security whitepaper. Stations then connect with `wss://` URLs.
+    #[must_use]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Lets stations the credential store still allows on Security Profile 0 connect without an
+    /// `Authorization` header, see [`crate::CredentialStore::allows_unsecured`].
+    ///
+    /// Disabled by default: with a credential store set, every upgrade request without
+    /// credentials is refused with `401 Unauthorized`, whatever the store says. Enable it only
+    /// while migrating stations with [`crate::CrushHandle::upgrade_security_profile`].
+    #[must_use]
+    pub fn with_unsecured_stations(mut self, allowed: bool) -> Self {
+        self.unsecured_stations = allowed;
+        self
+    }
+
+    /// Sets how a second connection with the id of an already connected station is handled.
+    #[must_use]
+    pub fn with_duplicate_connection_policy(mut self, policy: DuplicateConnectionPolicy) -> Self {
+        self.duplicate_connection_policy = policy;
+        self
+    }
+
+    /// Sets how CALLs of stations whose `BootNotification` was not accepted are treated.
+    #[must_use]
+    pub fn with_registration_policy(mut self, policy: RegistrationPolicy) -> Self {
+        self.registration_policy = policy;
+        self
+    }
+
+    /// Sets how many events a subscriber of the event stream may fall behind, 1024 by default.
+    ///
+    /// A subscriber that falls further behind misses the oldest events instead of slowing crush
+    /// down.
+    #[must_use]
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Sets how long [`crate::CrushHandle::call`] waits for a response, 30 seconds by default.
+    #[must_use]
+    pub fn with_call_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = timeout;
+        self
+    }
+
+    /// Sets how long [`crate::CrushHandle::upgrade_security_profile`] waits for a station to
+    /// reconnect with its new credentials before restoring the old ones, 2 minutes by default.
+    #[must_use]
+    pub fn with_reconnect_timeout(mut self, timeout: Duration) -> Self {
+        self.reconnect_timeout = timeout;
+        self
+    }
+
+    /// Sets how often a WebSocket ping is sent to every station, 60 seconds by default.
+    ///
+    /// This mirrors the `WebSocketPingInterval` configuration key of OCPP 1.6: a
+    /// `Duration::ZERO` interval disables pings.
+    #[must_use]
+    pub fn with_websocket_ping_interval(mut self, interval: Duration) -> Self {
+        self.keepalive.ping_interval = (!interval.is_zero()).then_some(interval);
+        self
+    }
+
+    /// Sets after how many consecutive unanswered pings a session is closed, 3 by default.
+    #[must_use]
+    pub fn with_max_missed_pongs(mut self, max_missed_pongs: u32) -> Self {
+        self.keepalive.max_missed_pongs = max_missed_pongs.max(1);
+        self
+    }
+
+    /// Sets after how many heartbeat intervals without an OCPP message a station is reported
+    /// unresponsive, 3 by default.
+    ///
+    /// The interval is the one the boot notification handler returned to the station. Unlike
+    /// the idle timeout this keeps the connection open, it publishes
+    /// [`crate::EventKind::Unresponsive`] and [`crate::EventKind::Recovered`] once the station
+    /// sends a message again. Zero disables the check.
+    #[must_use]
+    pub fn with_unresponsive_after(mut self, intervals: u32) -> Self {
+        self.keepalive.unresponsive_after = (intervals > 0).then_some(intervals);
+        self
+    }
+
+    /// Closes sessions that have not sent any OCPP message, such as a Heartbeat, for `timeout`.
+    ///
+    /// Pongs keep a connection alive but do not count as messages. Disabled by default.
+    #[must_use]
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive.idle_timeout = Some(timeout);
+        self
+    }
+}
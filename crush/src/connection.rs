@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use hyper::http::{Extensions, HeaderMap, StatusCode};
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use crate::certificate::ClientCertificate;
+
+/// A station asking to open a WebSocket connection, before it is upgraded.
+#[derive(Debug, Clone)]
+pub struct ConnectionRequest {
+    station_id: String,
+    address: SocketAddr,
+    headers: HeaderMap,
+    client_certificate: Option<Arc<ClientCertificate>>,
+}
+
+impl ConnectionRequest {
+    pub(crate) fn new(
+        station_id: String,
+        address: SocketAddr,
+        headers: HeaderMap,
+        client_certificate: Option<Arc<ClientCertificate>>,
+    ) -> Self {
+        Self {
+            station_id,
+            address,
+            headers,
+            client_certificate,
+        }
+    }
+
+    /// The charge point identity taken from the `/ocpp/{station_id}` connection path.
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    /// The remote address the station connects from.
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The headers of the HTTP upgrade request, e.g. to check an `Authorization` header.
+    #[must_use]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The verified client certificate, if the station connected over mutual TLS.
+    #[must_use]
+    pub fn client_certificate(&self) -> Option<&ClientCertificate> {
+        self.client_certificate.as_deref()
+    }
+}
+
+/// Whether a station may open a session, returned by a [`HandleConnection`].
+#[derive(Debug)]
+pub struct ConnectionDecision {
+    rejection: Option<StatusCode>,
+    metadata: Extensions,
+}
+
+impl ConnectionDecision {
+    /// Upgrades the connection.
+    #[must_use]
+    pub fn accept() -> Self {
+        Self {
+            rejection: None,
+            metadata: Extensions::new(),
+        }
+    }
+
+    /// Refuses the upgrade with `status`, usually 401, 403 or 404.
+    #[must_use]
+    pub fn reject(status: StatusCode) -> Self {
+        Self {
+            rejection: Some(status),
+            metadata: Extensions::new(),
+        }
+    }
+
+    /// Attaches `value` to the session, replacing a value of the same type.
+    ///
+    /// The metadata is available to handlers through [`crate::StationContext::metadata`] and
+    /// to the registry through [`crate::StationInfo::metadata`]. Has no effect on a rejected
+    /// connection.
+    #[must_use]
+    pub fn with_metadata<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.metadata.insert(value);
+        self
+    }
+
+    /// The metadata for an accepted connection, or the status to refuse it with.
+    pub(crate) fn into_result(self) -> Result<Extensions, StatusCode> {
+        match self.rejection {
+            Some(status) => Err(status),
+            None => Ok(self.metadata),
+        }
+    }
+}
+
+/// Decides whether a station may connect, before its connection is upgraded to a WebSocket.
+///
+/// Implemented for every `Fn(ConnectionRequest)` returning a future.
+#[async_trait]
+pub trait HandleConnection: Send + Sync {
+    async fn handle(&self, request: ConnectionRequest) -> ConnectionDecision;
+}
+
+#[async_trait]
+impl<F, Fut> HandleConnection for F
+where
+    F: Fn(ConnectionRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = ConnectionDecision> + Send,
+{
+    async fn handle(&self, request: ConnectionRequest) -> ConnectionDecision {
+        self(request).await
+    }
+}
+
+/// Accepts every station.
+pub(crate) struct DefaultConnectionHandler;
+
+#[async_trait]
+impl HandleConnection for DefaultConnectionHandler {
+    async fn handle(&self, _request: ConnectionRequest) -> ConnectionDecision {
+        ConnectionDecision::accept()
+    }
+}
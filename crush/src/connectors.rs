@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::{
+    messages::status_notification::StatusNotificationRequest,
+    types::{ChargePointErrorCode, ChargePointStatus},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The status a station last reported for one of its connectors with `StatusNotification`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectorStatus {
+    connector_id: u32,
+    status: ChargePointStatus,
+    error_code: ChargePointErrorCode,
+    info: Option<String>,
+    vendor_id: Option<String>,
+    vendor_error_code: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    received_at: DateTime<Utc>,
+}
+
+impl ConnectorStatus {
+    pub(crate) fn new(request: &StatusNotificationRequest) -> Self {
+        Self {
+            connector_id: request.connector_id,
+            status: request.status.clone(),
+            error_code: request.error_code.clone(),
+            info: request.info.clone(),
+            vendor_id: request.vendor_id.clone(),
+            vendor_error_code: request.vendor_error_code.clone(),
+            timestamp: request.timestamp,
+            received_at: Utc::now(),
+        }
+    }
+
+    /// The connector, 0 for the charge point as a whole.
+    #[must_use]
+    pub fn connector_id(&self) -> u32 {
+        self.connector_id
+    }
+
+    #[must_use]
+    pub fn status(&self) -> &ChargePointStatus {
+        &self.status
+    }
+
+    #[must_use]
+    pub fn error_code(&self) -> &ChargePointErrorCode {
+        &self.error_code
+    }
+
+    /// Free text the station added about the error.
+    #[must_use]
+    pub fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
+    /// The vendor that defines [`ConnectorStatus::vendor_error_code`].
+    #[must_use]
+    pub fn vendor_id(&self) -> Option<&str> {
+        self.vendor_id.as_deref()
+    }
+
+    #[must_use]
+    pub fn vendor_error_code(&self) -> Option<&str> {
+        self.vendor_error_code.as_deref()
+    }
+
+    /// When the status was reported by the station's clock, if the station sent a timestamp.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    /// When crush received the status, by its own clock.
+    #[must_use]
+    pub fn received_at(&self) -> DateTime<Utc> {
+        self.received_at
+    }
+
+    /// Whether both report the same condition, no matter when.
+    fn same_condition(&self, other: &Self) -> bool {
+        self.status == other.status
+            && self.error_code == other.error_code
+            && self.info == other.info
+            && self.vendor_id == other.vendor_id
+            && self.vendor_error_code == other.vendor_error_code
+    }
+}
+
+/// The connector status table of a station, see [`crate::CrushHandle::connectors`].
+///
+/// Connector 0 stands for the charge point as a whole and is kept apart from the connectors.
+/// Each entry is the newest notification by the station's clock: notifications that arrive out
+/// of order with an older timestamp are ignored. Notifications without a timestamp are taken in
+/// the order they arrive. A station's clock may be reset when it boots, so the statuses it
+/// reported before its last `BootNotification` are replaced by any that follow.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Connectors {
+    charge_point: Option<ConnectorStatus>,
+    statuses: BTreeMap<u32, ConnectorStatus>,
+    /// When crush received the station's last `BootNotification`.
+    booted_at: Option<DateTime<Utc>>,
+}
+
+/// What applying a `StatusNotification` to [`Connectors`] did.
+pub(crate) enum Update {
+    /// The table holds a newer status.
+    Stale { current: DateTime<Utc> },
+    /// The condition is the same, only the timestamp moved on.
+    Refreshed,
+    /// The condition changed from `previous`.
+    Changed { previous: Option<ConnectorStatus> },
+}
+
+impl Connectors {
+    /// The status of the charge point as a whole, reported for connector 0.
+    #[must_use]
+    pub fn charge_point(&self) -> Option<&ConnectorStatus> {
+        self.charge_point.as_ref()
+    }
+
+    /// The status of the connector `connector_id`, 0 being the charge point.
+    #[must_use]
+    pub fn connector(&self, connector_id: u32) -> Option<&ConnectorStatus> {
+        match connector_id {
+            0 => self.charge_point.as_ref(),
+            _ => self.statuses.get(&connector_id),
+        }
+    }
+
+    /// The statuses of all connectors but 0, ordered by connector id.
+    pub fn iter(&self) -> impl Iterator<Item = &ConnectorStatus> {
+        self.statuses.values()
+    }
+
+    /// Whether the station never reported a status.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.charge_point.is_none() && self.statuses.is_empty()
+    }
+
+    /// Applies the status of a `StatusNotification` unless the table holds a newer one.
+    pub(crate) fn update(&mut self, status: ConnectorStatus) -> Update {
+        let current = self.connector(status.connector_id);
+        if let Some(current) = current.and_then(|current| self.newer(current, &status)) {
+            return Update::Stale { current };
+        }
+        let update = match current {
+            Some(current) if current.same_condition(&status) => Update::Refreshed,
+            previous => Update::Changed {
+                previous: previous.cloned(),
+            },
+        };
+        match status.connector_id {
+            0 => self.charge_point = Some(status),
+            connector_id => {
+                self.statuses.insert(connector_id, status);
+            }
+        }
+        update
+    }
+
+    /// Stops comparing the statuses reported so far with the ones that follow, since the
+    /// station's clock may have been reset.
+    pub(crate) fn rebooted(&mut self) {
+        self.booted_at = Some(Utc::now());
+    }
+
+    /// The timestamp of `current` if it is newer than `status` by the station's clock.
+    fn newer(&self, current: &ConnectorStatus, status: &ConnectorStatus) -> Option<DateTime<Utc>> {
+        if self
+            .booted_at
+            .is_some_and(|booted_at| current.received_at < booted_at)
+        {
+            return None;
+        }
+        let current = current.timestamp?;
+        (status.timestamp? < current).then_some(current)
+    }
+}
@@ -0,0 +1,165 @@
+use hyper::http::Extensions;
+use rust_ocpp::v1_6::types::IdTagInfo;
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::{
+    certificate::ClientCertificate,
+    connectors::Connectors,
+    station_state::{FirmwareUpdate, LogUpload, StationStates},
+    transactions::{Transaction, Transactions},
+};
+
+/// Information about the charging station a request originates from.
+///
+/// A `StationContext` is handed to every handler together with the request payload, so
+/// handlers can tell stations apart without keeping their own connection bookkeeping.
+#[derive(Debug, Clone)]
+pub struct StationContext {
+    station_id: String,
+    address: SocketAddr,
+    client_certificate: Option<Arc<ClientCertificate>>,
+    metadata: Arc<Extensions>,
+    states: StationStates,
+}
+
+impl StationContext {
+    pub(crate) fn new(
+        station_id: String,
+        address: SocketAddr,
+        client_certificate: Option<Arc<ClientCertificate>>,
+        metadata: Arc<Extensions>,
+        states: StationStates,
+    ) -> Self {
+        Self {
+            station_id,
+            address,
+            client_certificate,
+            metadata,
+            states,
+        }
+    }
+
+    /// The charge point identity taken from the `/ocpp/{station_id}` connection path.
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    /// The remote address the station connected from.
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The certificate the station authenticated with, if it connected over mutual TLS.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{
+    /// #     actions::DataTransfer,
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::data_transfer::DataTransferResponse, types::DataTransferStatus,
+    /// #     },
+    /// #     Config, CrushBuilder,
+    /// # };
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_action::<DataTransfer, _>(|context, _request| async move {
+    ///     if let Some(certificate) = context.client_certificate() {
+    ///         tracing::info!("Certificate {} issued by {}", certificate.serial_number(), certificate.issuer());
+    ///     }
+    ///     Ok(DataTransferResponse {
+    ///         status: DataTransferStatus::Accepted,
+    ///         data: None,
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn client_certificate(&self) -> Option<&ClientCertificate> {
+        self.client_certificate.as_deref()
+    }
+
+    /// The metadata of type `T` attached when the connection was accepted, see
+    /// [`crate::ConnectionDecision::with_metadata`].
+    #[must_use]
+    pub fn metadata<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.metadata.get()
+    }
+
+    /// The current connector status table of the station.
+    ///
+    /// Like the firmware progress, a `StatusNotification` handler sees the table before the
+    /// notification it is handling is applied.
+    #[must_use]
+    pub fn connectors(&self) -> Connectors {
+        self.states.connectors(&self.station_id)
+    }
+
+    /// The transaction with the id `transaction_id`, of any station.
+    ///
+    /// A `StopTransaction` handler sees the transaction it stops as still active.
+    #[must_use]
+    pub fn transaction(&self, transaction_id: i32) -> Option<Transaction> {
+        self.states.transactions().get(transaction_id)
+    }
+
+    /// How crush answers for `id_tag`, see [`crate::CrushHandle::set_id_tag`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::authorize::AuthorizeResponse,
+    /// #         types::{AuthorizationStatus, IdTagInfo},
+    /// #     },
+    /// #     actions::Authorize,
+    /// #     Config, CrushBuilder,
+    /// # };
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_action::<Authorize, _>(|context, request| async move {
+    ///     let id_tag_info = context.id_tag(&request.id_tag).unwrap_or(IdTagInfo {
+    ///         expiry_date: None,
+    ///         parent_id_tag: None,
+    ///         status: AuthorizationStatus::Invalid,
+    ///     });
+    ///     Ok(AuthorizeResponse { id_tag_info })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn id_tag(&self, id_tag: &str) -> Option<IdTagInfo> {
+        self.states.id_tag(id_tag)
+    }
+
+    pub(crate) fn transactions(&self) -> &Transactions {
+        self.states.transactions()
+    }
+
+    /// The current progress of the station's last signed firmware update.
+    ///
+    /// A `SignedFirmwareStatusNotification` handler sees the status reported before the one
+    /// it is handling, crush records the new status once the handler answered.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{security::SignedFirmwareStatusNotificationResponse, Config, CrushBuilder};
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_signed_firmware_status_notification(|context, request| async move {
+    ///     if let Some(previous) = context.firmware_update() {
+    ///         tracing::info!("{}: {:?} -> {:?}", context.station_id(), previous.status(), request.status);
+    ///     }
+    ///     Ok(SignedFirmwareStatusNotificationResponse {})
+    /// });
+    /// ```
+    #[must_use]
+    pub fn firmware_update(&self) -> Option<FirmwareUpdate> {
+        self.states.firmware_update(&self.station_id)
+    }
+
+    /// The current progress of the station's last log upload.
+    #[must_use]
+    pub fn log_upload(&self) -> Option<LogUpload> {
+        self.states.log_upload(&self.station_id)
+    }
+}
@@ -1,45 +1,83 @@
+use futures::FutureExt;
+use rust_ocpp::v1_6::messages::{
+    boot_notification::{BootNotificationRequest, BootNotificationResponse},
+    meter_values::MeterValuesRequest,
+    start_transaction::{StartTransactionRequest, StartTransactionResponse},
+    status_notification::StatusNotificationRequest,
+    stop_transaction::StopTransactionRequest,
+};
+use serde_json::{json, Value};
+use std::{panic::AssertUnwindSafe, sync::Arc};
+use tower::ServiceExt;
+
 use crate::{
-    error::{CrushResult, IntoOcppRequestMessage},
-    messages::{
-        boot_notification::DefaultBootNotificationHandler, heartbeat::DefaultHeartbeatHandler,
-        status_notification::DefaultStatusNotificationHandler,
+    action::Action,
+    actions::{
+        BootNotification, LogStatusNotification, MeterValues, SignedFirmwareStatusNotification,
+        StartTransaction, StatusNotification, StopTransaction,
     },
-    serde::{OcppRequest, OcppRequestMessage, OcppResponseMessage},
-    HandleBootNotificationRequest, HandleHeartbeatRequest, HandleStatusNotificationRequest,
-};
-use tokio::sync::{
-    mpsc::{channel, Receiver, Sender},
-    oneshot,
+    client_loop::ToClient,
+    config::RegistrationPolicy,
+    context::StationContext,
+    error::{CrushError, CrushResult, IntoOcppRequestMessage, StorageError},
+    events::{Direction, EventKind},
+    interceptor::{CallService, OcppCall},
+    registration,
+    security::{LogStatusNotificationRequest, SignedFirmwareStatusNotificationRequest},
+    serde::{OcppRequest, OcppResponseMessage},
+    session::{Session, TransactionChange},
+    supervisor::{panic_message, supervise, Mailbox, Supervised},
+    OcppResponseError,
 };
+use tokio::sync::mpsc::{channel, Sender};
+
+/// The requests whose content is recorded once they were answered with a CALLRESULT.
+const RECORDED_ACTIONS: [&str; 7] = [
+    BootNotification::NAME,
+    StatusNotification::NAME,
+    StartTransaction::NAME,
+    MeterValues::NAME,
+    StopTransaction::NAME,
+    SignedFirmwareStatusNotification::NAME,
+    LogStatusNotification::NAME,
+];
 
 pub(crate) enum ToController {
-    Message(String, oneshot::Sender<String>),
+    Message(String),
 }
 
+/// Processes the CALLs of a single station, one at a time and in the order they were received.
+///
+/// Every connected station gets its own controller, so a slow handler only delays the station
+/// that sent the request while all other stations keep being served.
+#[derive(Clone)]
 struct Controller {
-    receiver: Receiver<ToController>,
-    heartbeat_handler: Option<Box<dyn HandleHeartbeatRequest + Send + Sync>>,
-    boot_notification_handler: Option<Box<dyn HandleBootNotificationRequest + Send + Sync>>,
-    status_notification_handler: Option<Box<dyn HandleStatusNotificationRequest + Send + Sync>>,
+    session: Arc<Session>,
+    context: StationContext,
+    service: CallService,
+    client_sender: Sender<ToClient>,
+    registration_policy: RegistrationPolicy,
 }
 
 impl Controller {
     fn new(
-        receiver: Receiver<ToController>,
-        heartbeat_handler: Option<Box<dyn HandleHeartbeatRequest + Send + Sync>>,
-        boot_notification_handler: Option<Box<dyn HandleBootNotificationRequest + Send + Sync>>,
-        status_notification_handler: Option<Box<dyn HandleStatusNotificationRequest + Send + Sync>>,
+        session: Arc<Session>,
+        service: CallService,
+        client_sender: Sender<ToClient>,
+        registration_policy: RegistrationPolicy,
     ) -> Self {
+        let context = session.context();
         Self {
-            receiver,
-            heartbeat_handler,
-            boot_notification_handler,
-            status_notification_handler,
+            session,
+            context,
+            service,
+            client_sender,
+            registration_policy,
         }
     }
     async fn handle_message(&self, msg: ToController) -> CrushResult<()> {
         match msg {
-            ToController::Message(message, sender) => {
+            ToController::Message(message) => {
                 let ocpp_request = match serde_json::from_str::<OcppRequest>(&message) {
                     Ok(ocpp_request) => ocpp_request,
                     Err(error) => {
@@ -50,102 +88,266 @@ impl Controller {
                     }
                 };
 
-                let uuid = ocpp_request.uuid;
-                let payload = ocpp_request.payload;
+                let OcppRequest {
+                    uuid,
+                    action,
+                    payload,
+                } = ocpp_request;
+
+                self.session.emit(|| EventKind::MessageReceived {
+                    unique_id: uuid.clone(),
+                    action: action.clone(),
+                    payload: payload.clone(),
+                });
 
-                let ocpp_response_message = self.process(payload).await;
+                let recorded = RECORDED_ACTIONS
+                    .contains(&action.as_str())
+                    .then(|| payload.clone());
+                let call = OcppCall::new(
+                    self.context.clone(),
+                    message,
+                    uuid.clone(),
+                    action.clone(),
+                    payload,
+                );
 
+                let mut ocpp_response_message = match self.admit(&uuid, &action) {
+                    Ok(()) => self.process(call).await,
+                    Err(error) => error.into_ocpp_response(),
+                };
+                let mut change = None;
+                if let (Some(request), OcppResponseMessage::CallResult(result)) =
+                    (&recorded, &mut ocpp_response_message)
+                {
+                    match self.save_transaction(&action, request, result).await {
+                        Ok(saved) => change = saved,
+                        Err(error) => {
+                            tracing::error!(
+                                "Failed to save the {action} of {}, answering it with an error: {error}",
+                                self.context.station_id()
+                            );
+                            ocpp_response_message =
+                                OcppResponseError::InternalError.into_ocpp_response();
+                        }
+                    }
+                }
                 let response = ocpp_response_message.serialize_with_params(3, &uuid)?;
-                drop(sender.send(response));
+                if self
+                    .client_sender
+                    .send(ToClient::Message(response))
+                    .await
+                    .is_err()
+                {
+                    tracing::debug!(
+                        "Dropping response for {}, client loop has shut down",
+                        self.context.station_id()
+                    );
+                }
+                // Emitted once the response is queued, so CALLs that subscribers send in
+                // reaction to it reach the station after it.
+                self.emit_response(&uuid, &action, &ocpp_response_message);
+                if let Some(change) = change {
+                    self.session.transaction_changed(change);
+                }
+                if let (Some(request), OcppResponseMessage::CallResult(result)) =
+                    (recorded, &ocpp_response_message)
+                {
+                    self.record(&action, request, result);
+                }
             }
         }
         Ok(())
     }
-    async fn process(&self, msg: OcppRequestMessage) -> OcppResponseMessage {
-        match msg {
-            OcppRequestMessage::StatusNotification(request) => {
-                if let Some(handler) = &self.status_notification_handler {
-                    match handler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::StatusNotification(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                } else {
-                    match DefaultStatusNotificationHandler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::StatusNotification(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                }
+    fn emit_response(&self, unique_id: &str, action: &str, response: &OcppResponseMessage) {
+        self.session.emit(|| match response {
+            OcppResponseMessage::CallResult(payload) => EventKind::ResponseSent {
+                unique_id: unique_id.to_owned(),
+                action: action.to_owned(),
+                payload: payload.clone(),
+            },
+            OcppResponseMessage::CallError {
+                error_code,
+                error_description,
+                error_details,
+            } => EventKind::Error {
+                direction: Direction::Sent,
+                unique_id: unique_id.to_owned(),
+                action: action.to_owned(),
+                error_code: error_code.clone(),
+                error_description: error_description.clone(),
+                error_details: error_details.clone(),
+            },
+        });
+    }
+    /// Checks whether the station may send `action` before its `BootNotification` was accepted.
+    fn admit(&self, unique_id: &str, action: &str) -> Result<(), OcppResponseError> {
+        let status = self.session.registration_status();
+        if registration::accepts_call(status.as_ref(), action) || self.session.was_triggered(action)
+        {
+            return Ok(());
+        }
+        tracing::warn!(
+            "{} sent {action} without being accepted, its registration status is {status:?}",
+            self.context.station_id()
+        );
+        self.session.emit(|| EventKind::OutOfOrder {
+            unique_id: unique_id.to_owned(),
+            action: action.to_owned(),
+            status: status.clone(),
+        });
+        match self.registration_policy {
+            RegistrationPolicy::Flag => Ok(()),
+            RegistrationPolicy::Reject => Err(OcppResponseError::Unregistered {
+                details: json!({ "registrationStatus": status }),
+            }),
+        }
+    }
+    /// Saves the transaction a `StartTransaction` or `StopTransaction` starts or stops before
+    /// the station is answered, so it is never given a transaction id crush could lose.
+    ///
+    /// The id of a started transaction is allocated here and replaces the one in `response`,
+    /// whichever handler answered the `StartTransaction`. Returns the change to publish once
+    /// the answer is sent.
+    async fn save_transaction(
+        &self,
+        action: &str,
+        request: &Value,
+        response: &mut Value,
+    ) -> Result<Option<TransactionChange>, StorageError> {
+        let recorded = match action {
+            StartTransaction::NAME => serde_json::from_value::<StartTransactionRequest>(
+                request.clone(),
+            )
+            .and_then(|request| {
+                let mut started =
+                    serde_json::from_value::<StartTransactionResponse>(response.clone())?;
+                started.transaction_id = self.context.transactions().allocate_id();
+                *response = serde_json::to_value(&started)?;
+                Ok(Some(
+                    self.session
+                        .start_transaction(&request, started.transaction_id),
+                ))
+            }),
+            StopTransaction::NAME => {
+                serde_json::from_value::<StopTransactionRequest>(request.clone())
+                    .map(|request| self.session.stop_transaction(&request))
             }
-            OcppRequestMessage::BootNotification(request) => {
-                if let Some(handler) = &self.boot_notification_handler {
-                    match handler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::BootNotification(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                } else {
-                    match DefaultBootNotificationHandler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::BootNotification(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                }
+            _ => return Ok(None),
+        };
+        let (change, written) = match recorded {
+            Ok(Some(recorded)) => recorded,
+            Ok(None) => return Ok(None),
+            Err(error) => {
+                tracing::debug!(
+                    "Not recording {action} of {}: {error}",
+                    self.context.station_id()
+                );
+                return Ok(None);
             }
-            OcppRequestMessage::Heartbeat(request) => {
-                if let Some(handler) = &self.heartbeat_handler {
-                    match handler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::Heartbeat(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                } else {
-                    match DefaultHeartbeatHandler.handle(request).await {
-                        Ok(response) => OcppResponseMessage::Heartbeat(response),
-                        Err(error) => error.into_ocpp_response(),
-                    }
-                }
+        };
+        written.wait().await?;
+        Ok(Some(change))
+    }
+    /// Records what an answered request tells about the station in its session.
+    fn record(&self, action: &str, request: Value, response: &Value) {
+        let recorded = match action {
+            BootNotification::NAME => serde_json::from_value::<BootNotificationRequest>(request)
+                .and_then(|request| {
+                    let response =
+                        serde_json::from_value::<BootNotificationResponse>(response.clone())?;
+                    self.session.booted(request, response);
+                    Ok(())
+                }),
+            StatusNotification::NAME => {
+                serde_json::from_value::<StatusNotificationRequest>(request)
+                    .map(|request| self.session.connector_status(&request))
+            }
+            MeterValues::NAME => serde_json::from_value::<MeterValuesRequest>(request)
+                .map(|request| self.session.meter_values(&request)),
+            SignedFirmwareStatusNotification::NAME => {
+                serde_json::from_value::<SignedFirmwareStatusNotificationRequest>(request)
+                    .map(|request| self.session.firmware_status(&request))
+            }
+            LogStatusNotification::NAME => {
+                serde_json::from_value::<LogStatusNotificationRequest>(request)
+                    .map(|request| self.session.log_status(&request))
+            }
+            _ => Ok(()),
+        };
+        if let Err(error) = recorded {
+            tracing::debug!(
+                "Not recording {action} of {}: {error}",
+                self.context.station_id()
+            );
+        }
+    }
+    /// Runs `call` through the interceptors and its handler.
+    ///
+    /// A panicking handler is answered with an `InternalError` CALLERROR and the controller
+    /// carries on with the next message of the station.
+    async fn process(&self, call: OcppCall) -> OcppResponseMessage {
+        let action = call.action().to_owned();
+        match AssertUnwindSafe(self.service.clone().oneshot(call))
+            .catch_unwind()
+            .await
+        {
+            Ok(Ok(payload)) => OcppResponseMessage::CallResult(payload),
+            Ok(Err(error)) => error.into_ocpp_response(),
+            Err(panic) => {
+                tracing::error!(
+                    "Handler for {action} from {} panicked: {}",
+                    self.context.station_id(),
+                    panic_message(panic.as_ref())
+                );
+                OcppResponseError::InternalError.into_ocpp_response()
             }
         }
     }
 }
 
-async fn run_controller(mut controller_actor: Controller) -> CrushResult<()> {
-    while let Some(msg) = controller_actor.receiver.recv().await {
+/// Runs the controller until the session ends.
+///
+/// A panic outside of the handlers takes the loop down, and the supervisor starts it again on
+/// the same mailbox, so the station keeps being served. The next CALL waits while the storage
+/// is behind, which in turn makes the station wait.
+async fn run_controller(mut receiver: Mailbox<ToController>, controller_actor: Controller) {
+    while let Some(msg) = receiver.recv().await {
+        controller_actor.session.storage_ready().await;
         if let Err(error) = controller_actor.handle_message(msg).await {
             tracing::error!("{error}");
-        };
+        }
     }
-    Ok(())
 }
 
-#[derive(Clone)]
 pub(crate) struct ControllerHandle {
     sender: Sender<ToController>,
+    supervised: Supervised,
 }
 
 impl ControllerHandle {
     pub(crate) fn new(
-        heartbeat_handler: Option<Box<dyn HandleHeartbeatRequest + Send + Sync>>,
-        boot_notification_handler: Option<Box<dyn HandleBootNotificationRequest + Send + Sync>>,
-        status_notification_handler: Option<Box<dyn HandleStatusNotificationRequest + Send + Sync>>,
+        session: Arc<Session>,
+        service: CallService,
+        client_sender: Sender<ToClient>,
+        registration_policy: RegistrationPolicy,
     ) -> Self {
         let (sender, receiver) = channel(64);
 
-        tokio::spawn(async move {
-            let actor = Controller::new(
-                receiver,
-                heartbeat_handler,
-                boot_notification_handler,
-                status_notification_handler,
-            );
-            if let Err(error) = run_controller(actor).await {
-                tracing::error!("{error}");
-            };
+        let name = format!("Controller of {}", session.station_id());
+        let actor = Controller::new(session, service, client_sender, registration_policy);
+        let (supervised, _controller_join) = supervise(name, receiver, move |mailbox| {
+            run_controller(mailbox, actor.clone())
         });
 
-        Self { sender }
+        Self { sender, supervised }
     }
-    pub(crate) async fn send(&mut self, msg: ToController) {
-        assert!(
-            self.sender.send(msg).await.is_ok(),
-            "Controller loop has shut down"
-        );
+    pub(crate) fn supervised(&self) -> &Supervised {
+        &self.supervised
+    }
+    pub(crate) async fn send(&mut self, msg: ToController) -> CrushResult<()> {
+        if self.sender.send(msg).await.is_err() {
+            return Err(CrushError::ControllerLoopClosed);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,316 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::http::{header, HeaderMap};
+use ring::{
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::{Arc, PoisonError, RwLock, RwLockWriteGuard},
+};
+use tokio::{sync::Semaphore, task::spawn_blocking};
+
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+/// How many passwords are hashed at once, so stations flooding crush with upgrade requests
+/// cannot occupy every blocking thread.
+const CONCURRENT_HASHES: usize = 4;
+/// Passwords of charge points are random keys rather than memorable words, so a moderate work
+/// factor keeps them safe at rest without making every reconnect expensive.
+const PBKDF2_ITERATIONS: NonZeroU32 = match NonZeroU32::new(100_000) {
+    Some(iterations) => iterations,
+    None => unreachable!(),
+};
+
+/// Verifies the passwords charge points present with HTTP Basic authentication.
+///
+/// Setting a credential store with [`crate::CrushBuilder::with_credential_store`] enables OCPP
+/// Security Profile 1: every station must authenticate with its station id as the username,
+/// otherwise its connection is refused with `401 Unauthorized` before the upgrade.
+///
+/// Stores that can also be written support [`crate::CrushHandle::upgrade_security_profile`],
+/// which rotates the `AuthorizationKey` of a station and migrates stations that still connect
+/// without credentials.
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Whether `password` is the current password of `station_id`.
+    ///
+    /// The password is passed as the raw bytes of the decoded `Authorization` header, since
+    /// OCPP allows binary authorization keys.
+    async fn verify(&self, station_id: &str, password: &[u8]) -> bool;
+
+    /// Whether `station_id` may connect without an `Authorization` header, because it has not
+    /// been upgraded from Security Profile 0 yet. No station may by default.
+    ///
+    /// Only consulted if [`crate::Config::with_unsecured_stations`] is enabled.
+    async fn allows_unsecured(&self, _station_id: &str) -> bool {
+        false
+    }
+
+    /// Stages `password` as a new credential of `station_id`, which is accepted alongside the
+    /// current one until [`CredentialStore::commit_password`] or
+    /// [`CredentialStore::revert_password`].
+    ///
+    /// Returns whether the password was stored, read-only stores keep the default that stores
+    /// nothing.
+    async fn rotate_password(&self, _station_id: &str, _password: &[u8]) -> bool {
+        false
+    }
+
+    /// Makes the password staged with [`CredentialStore::rotate_password`] the only credential
+    /// of `station_id`, returning whether there was a staged password.
+    async fn commit_password(&self, _station_id: &str) -> bool {
+        false
+    }
+
+    /// Drops the password staged with [`CredentialStore::rotate_password`], so `station_id`
+    /// keeps only its current credential. Returns whether there was a staged password.
+    async fn revert_password(&self, _station_id: &str) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl<T: CredentialStore + ?Sized> CredentialStore for Arc<T> {
+    async fn verify(&self, station_id: &str, password: &[u8]) -> bool {
+        (**self).verify(station_id, password).await
+    }
+
+    async fn allows_unsecured(&self, station_id: &str) -> bool {
+        (**self).allows_unsecured(station_id).await
+    }
+
+    async fn rotate_password(&self, station_id: &str, password: &[u8]) -> bool {
+        (**self).rotate_password(station_id, password).await
+    }
+
+    async fn commit_password(&self, station_id: &str) -> bool {
+        (**self).commit_password(station_id).await
+    }
+
+    async fn revert_password(&self, station_id: &str) -> bool {
+        (**self).revert_password(station_id).await
+    }
+}
+
+#[derive(Clone)]
+struct HashedPassword {
+    salt: [u8; SALT_LEN],
+    hash: [u8; HASH_LEN],
+}
+
+/// What passwords of stations without one are verified against, so that refusing an unknown
+/// station takes as long as refusing a wrong password and does not reveal which stations exist.
+const UNKNOWN_STATION: HashedPassword = HashedPassword {
+    salt: [0; SALT_LEN],
+    hash: [0; HASH_LEN],
+};
+
+/// What a station has to present to connect.
+enum Credential {
+    /// Nothing, the station is still on Security Profile 0.
+    Unsecured,
+    Password(HashedPassword),
+}
+
+#[derive(Default)]
+struct Credentials {
+    current: HashMap<String, Credential>,
+    /// The passwords of rotations in progress, accepted alongside the current credentials.
+    staged: HashMap<String, HashedPassword>,
+}
+
+/// A [`CredentialStore`] keeping salted PBKDF2 hashes of the passwords in memory.
+///
+/// # Examples
+///
+/// ```rust
+/// # use crush::{Config, CrushBuilder, InMemoryCredentialStore};
+/// # use std::sync::Arc;
+/// let credentials = Arc::new(InMemoryCredentialStore::new());
+/// credentials.set_password("CP001", b"0123456789abcdef");
+///
+/// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+/// let builder = CrushBuilder::new(config).with_credential_store(Arc::clone(&credentials));
+/// ```
+pub struct InMemoryCredentialStore {
+    random: SystemRandom,
+    credentials: RwLock<Credentials>,
+    hashing: Semaphore,
+}
+
+impl InMemoryCredentialStore {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            random: SystemRandom::new(),
+            credentials: RwLock::default(),
+            hashing: Semaphore::new(CONCURRENT_HASHES),
+        }
+    }
+
+    /// Sets the password of `station_id`, replacing the previous one.
+    ///
+    /// The password is hashed on the calling thread, which takes tens of milliseconds. It is
+    /// meant for provisioning the store before crush runs, from within a runtime call it through
+    /// [`tokio::task::spawn_blocking`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system cannot provide randomness for the salt.
+    pub fn set_password(&self, station_id: impl Into<String>, password: &[u8]) {
+        let password = hash(&self.random, password);
+        self.write()
+            .current
+            .insert(station_id.into(), Credential::Password(password));
+    }
+
+    /// Lets `station_id` connect without credentials until it gets a password.
+    ///
+    /// This is meant for stations still on Security Profile 0, which are then moved to Basic
+    /// authentication with [`crate::CrushHandle::upgrade_security_profile`]. They are refused
+    /// unless [`crate::Config::with_unsecured_stations`] is enabled.
+    pub fn allow_unsecured(&self, station_id: impl Into<String>) {
+        self.write()
+            .current
+            .insert(station_id.into(), Credential::Unsecured);
+    }
+
+    /// Removes the password of `station_id`, so it can no longer connect.
+    ///
+    /// Returns whether the station had a password.
+    pub fn remove_password(&self, station_id: &str) -> bool {
+        matches!(
+            self.write().current.remove(station_id),
+            Some(Credential::Password(_))
+        )
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Credentials> {
+        self.credentials
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Salts and hashes `password`, which takes long enough to block a runtime thread.
+fn hash(random: &SystemRandom, password: &[u8]) -> HashedPassword {
+    let mut salt = [0; SALT_LEN];
+    random
+        .fill(&mut salt)
+        .expect("the system random number generator failed");
+
+    let mut hash = [0; HASH_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        PBKDF2_ITERATIONS,
+        &salt,
+        password,
+        &mut hash,
+    );
+    HashedPassword { salt, hash }
+}
+
+impl Default for InMemoryCredentialStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CredentialStore for InMemoryCredentialStore {
+    async fn verify(&self, station_id: &str, password: &[u8]) -> bool {
+        let stored = {
+            let credentials = self
+                .credentials
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            let current = match credentials.current.get(station_id) {
+                Some(Credential::Password(stored)) => Some(stored.clone()),
+                _ => None,
+            };
+            current
+                .into_iter()
+                .chain(credentials.staged.get(station_id).cloned())
+                .collect::<Vec<_>>()
+        };
+        let known = !stored.is_empty();
+        let stored = if known { stored } else { vec![UNKNOWN_STATION] };
+        // Anyone can make crush hash, so not on a runtime thread and not without limit.
+        let Ok(_permit) = self.hashing.acquire().await else {
+            return false;
+        };
+        let password = password.to_vec();
+        let verified = spawn_blocking(move || {
+            stored.iter().any(|stored| {
+                pbkdf2::verify(
+                    pbkdf2::PBKDF2_HMAC_SHA256,
+                    PBKDF2_ITERATIONS,
+                    &stored.salt,
+                    &password,
+                    &stored.hash,
+                )
+                .is_ok()
+            })
+        })
+        .await
+        .unwrap_or(false);
+        known && verified
+    }
+
+    async fn allows_unsecured(&self, station_id: &str) -> bool {
+        matches!(
+            self.credentials
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .current
+                .get(station_id),
+            Some(Credential::Unsecured)
+        )
+    }
+
+    async fn rotate_password(&self, station_id: &str, password: &[u8]) -> bool {
+        let random = self.random.clone();
+        let password = password.to_vec();
+        let Ok(_permit) = self.hashing.acquire().await else {
+            return false;
+        };
+        let Ok(password) = spawn_blocking(move || hash(&random, &password)).await else {
+            return false;
+        };
+        self.write().staged.insert(station_id.to_owned(), password);
+        true
+    }
+
+    async fn commit_password(&self, station_id: &str) -> bool {
+        let mut credentials = self.write();
+        let Some(password) = credentials.staged.remove(station_id) else {
+            return false;
+        };
+        credentials
+            .current
+            .insert(station_id.to_owned(), Credential::Password(password));
+        true
+    }
+
+    async fn revert_password(&self, station_id: &str) -> bool {
+        self.write().staged.remove(station_id).is_some()
+    }
+}
+
+/// The username and password of an `Authorization: Basic` header.
+pub(crate) fn basic_credentials(headers: &HeaderMap) -> Option<(String, Vec<u8>)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+
+    let decoded = STANDARD.decode(encoded.trim()).ok()?;
+    let separator = decoded.iter().position(|byte| *byte == b':')?;
+    let username = String::from_utf8(decoded.get(..separator)?.to_vec()).ok()?;
+    let password = decoded.get(separator + 1..)?.to_vec();
+    Some((username, password))
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// Why the session of a station ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DisconnectReason {
+    /// The station closed the WebSocket with a closing handshake.
+    Closed { code: u16, reason: String },
+    /// The connection ended without a closing handshake.
+    ConnectionLost,
+    /// Reading from the connection failed.
+    ReadError(String),
+    /// Writing to the connection failed.
+    WriteError(String),
+    /// Writing to the connection did not complete in time.
+    WriteTimeout,
+    /// The station did not answer the configured number of WebSocket pings in a row.
+    MissedPongs,
+    /// The station did not send any OCPP message within the idle timeout.
+    IdleTimeout,
+    /// The station connected again and the new connection took over.
+    Replaced,
+    /// Crush shut the session down.
+    Shutdown,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed { code, reason } if reason.is_empty() => {
+                write!(formatter, "closed by station with code {code}")
+            }
+            Self::Closed { code, reason } => {
+                write!(formatter, "closed by station with code {code}: {reason}")
+            }
+            Self::ConnectionLost => write!(formatter, "connection lost"),
+            Self::ReadError(error) => write!(formatter, "read error: {error}"),
+            Self::WriteError(error) => write!(formatter, "write error: {error}"),
+            Self::WriteTimeout => write!(formatter, "write timed out"),
+            Self::MissedPongs => write!(formatter, "station stopped answering pings"),
+            Self::IdleTimeout => write!(formatter, "no message within the idle timeout"),
+            Self::Replaced => write!(formatter, "replaced by a new connection"),
+            Self::Shutdown => write!(formatter, "shut down by server"),
+        }
+    }
+}
+
+impl DisconnectReason {
+    /// Whether crush itself ended the session and should tell the station with a close frame.
+    pub(crate) fn is_initiated_by_server(&self) -> bool {
+        matches!(self, Self::MissedPongs | Self::IdleTimeout | Self::Replaced)
+    }
+}
@@ -1,6 +1,13 @@
 use hyper_tungstenite::tungstenite::{self, http};
+use rust_ocpp::v1_6::types::{ConfigurationStatus, RegistrationStatus};
+use rustls::pki_types::pem;
 use serde_json::Value;
-use std::io;
+use std::{
+    error::Error as StdError,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 use tokio::sync::oneshot;
 
 use crate::serde::OcppResponseMessage;
@@ -17,10 +24,24 @@ pub(crate) enum CrushError {
     OneshotReceive(#[from] oneshot::error::RecvError),
 
     #[error(transparent)]
-    Tungstenite(#[from] tungstenite::Error),
+    Tungstenite(Box<tungstenite::Error>),
 
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+
+    #[error("Server loop has shut down")]
+    ServerLoopClosed,
+
+    #[error("Controller loop has shut down")]
+    ControllerLoopClosed,
+}
+impl From<tungstenite::Error> for CrushError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::Tungstenite(Box::new(error))
+    }
 }
 /*
 impl fmt::Display for CrushError {
@@ -35,6 +56,7 @@ impl fmt::Display for CrushError {
 pub(crate) type CrushResult<T, E = CrushError> = Result<T, E>;
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum OcppResponseError {
     #[error("Generic error")]
     Generic,
@@ -47,10 +69,197 @@ pub enum OcppResponseError {
 
     #[error("Internal server error")]
     InternalError,
+
+    /// The station sent a CALL before its `BootNotification` was accepted.
+    #[error("Station is not registered")]
+    Unregistered { details: Value },
 }
 
 pub type OcppResult<T> = Result<T, OcppResponseError>;
 
+/// Why a CALL sent to a station through [`crate::CrushHandle::call`] did not succeed.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CallError {
+    #[error("Station {0} is not connected")]
+    NotConnected(String),
+
+    #[error("Station did not respond in time")]
+    Timeout,
+
+    #[error("Session ended before the station responded")]
+    Disconnected,
+
+    /// The station answered with a CALLERROR.
+    #[error("Station responded with {code}: {description}")]
+    Failed {
+        code: String,
+        description: String,
+        details: Value,
+    },
+
+    /// The station is not accepted, see [`crate::RegistrationPolicy::Reject`].
+    #[error("Station is not accepted for this call, its registration status is {status:?}")]
+    Unregistered { status: Option<RegistrationStatus> },
+
+    #[error("Failed to serialize the request: {0}")]
+    Serialize(String),
+
+    #[error("Failed to deserialize the response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Why [`crate::CrushBuilder::try_build`] could not build a crush instance.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BuildError {
+    #[error("Failed to load the storage: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("Failed to load the TLS certificate: {0}")]
+    Tls(#[from] TlsError),
+
+    #[error("Failed to listen on {address}: {source}")]
+    Bind {
+        address: SocketAddr,
+        source: io::Error,
+    },
+}
+
+/// Why the TLS certificate could not be loaded, see [`crate::TlsConfig`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TlsError {
+    #[error("TLS is not configured")]
+    NotConfigured,
+
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Invalid PEM in {}: {reason}", path.display())]
+    Pem { path: PathBuf, reason: String },
+
+    #[error("No certificate found in {}", .0.display())]
+    NoCertificate(PathBuf),
+
+    #[error("Invalid client CA bundle in {}: {reason}", path.display())]
+    ClientCa { path: PathBuf, reason: String },
+
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+}
+
+impl TlsError {
+    pub(crate) fn pem(path: &Path, error: &pem::Error) -> Self {
+        Self::Pem {
+            path: path.to_owned(),
+            reason: error.to_string(),
+        }
+    }
+}
+
+/// Why a [`crate::CertificateAuthority`] could not be loaded or refused to sign a CSR.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum CertificateAuthorityError {
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Invalid CA certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("Invalid CA private key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid certificate signing request: {0}")]
+    InvalidRequest(String),
+
+    #[error("CSR common name {common_name:?} is not the station id {station_id}")]
+    StationMismatch {
+        station_id: String,
+        common_name: Option<String>,
+    },
+
+    #[error("CSR organization {organization:?} is not {expected}")]
+    OrganizationMismatch {
+        expected: String,
+        organization: Option<String>,
+    },
+
+    #[error("Failed to sign the certificate: {0}")]
+    Signing(String),
+}
+
+/// Why a [`crate::Storage`] could not load or save records.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum StorageError {
+    #[error("Failed to access {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Invalid record on line {line} of {}: {reason}", path.display())]
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+
+    #[error("Failed to serialize a record: {0}")]
+    Serialize(String),
+
+    #[error("Invalid {kind} record: {reason}")]
+    InvalidRecord { kind: &'static str, reason: String },
+
+    /// The storage was written by a newer crush, with a schema this one does not know.
+    #[error("Storage schema version {version} is newer than the supported {supported}")]
+    UnsupportedSchema { version: usize, supported: usize },
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite storage failed: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// An error of a storage implemented outside crush.
+    #[error(transparent)]
+    Other(Box<dyn StdError + Send + Sync>),
+}
+
+impl StorageError {
+    pub(crate) fn io(path: &Path, source: io::Error) -> Self {
+        Self::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+}
+
+/// Why [`crate::CrushHandle::upgrade_security_profile`] did not move a station.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SecurityProfileError {
+    #[error("Basic authentication is not configured")]
+    NoCredentialStore,
+
+    #[error("The credential store cannot store passwords")]
+    ReadOnlyStore,
+
+    /// Security Profile 2 was requested, but crush does not accept TLS connections.
+    #[error("TLS is not configured")]
+    TlsNotConfigured,
+
+    #[error("Failed to change {key}: {error}")]
+    Call { key: &'static str, error: CallError },
+
+    #[error("Station did not accept the new {key}: {status:?}")]
+    NotAccepted {
+        key: &'static str,
+        status: ConfigurationStatus,
+    },
+
+    /// The station did not reconnect with its new credentials, the previous ones were restored.
+    #[error("Station did not reconnect with its new credentials in time")]
+    ReconnectTimeout,
+}
+
 pub(crate) trait IntoOcppRequestMessage {
     fn into_ocpp_response(self) -> OcppResponseMessage;
 }
@@ -73,6 +282,11 @@ impl IntoOcppRequestMessage for OcppResponseError {
                 error_description: "An internal error occurred and the receiver was not able to process the requested Action successfully".to_owned(),
                 error_details: Value::default(),
             },
+            Self::Unregistered { details } => OcppResponseMessage::CallError {
+                error_code: "SecurityError".to_owned(),
+                error_description: "The charge point has to be accepted with a BootNotification first".to_owned(),
+                error_details: details,
+            },
             Self::Generic => OcppResponseMessage::CallError {
                 error_code: "GenericError".to_owned(),
                 error_description: "Something unexpected happened.".to_owned(),
@@ -0,0 +1,173 @@
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::{
+    messages::boot_notification::BootNotificationRequest, types::RegistrationStatus,
+};
+use serde_json::Value;
+use std::net::SocketAddr;
+use tokio::sync::broadcast;
+
+use crate::{
+    connectors::ConnectorStatus,
+    disconnect::DisconnectReason,
+    station_state::{FirmwareUpdate, LogUpload},
+    transactions::Transaction,
+};
+
+/// Something that happened on a station session, published on the event stream.
+///
+/// Subscribe with [`crate::Crush::events`] or [`crate::CrushHandle::events`].
+#[derive(Debug, Clone)]
+pub struct Event {
+    station_id: String,
+    timestamp: DateTime<Utc>,
+    kind: EventKind,
+}
+
+impl Event {
+    /// The station the event concerns.
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    /// When crush observed the event.
+    #[must_use]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// What happened.
+    #[must_use]
+    pub fn kind(&self) -> &EventKind {
+        &self.kind
+    }
+}
+
+/// Whether a CALLERROR was sent to the station or received from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Crush sent the frame to the station.
+    Sent,
+    /// The station sent the frame to crush.
+    Received,
+}
+
+/// What happened in an [`Event`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum EventKind {
+    /// The WebSocket connection was accepted.
+    Connected {
+        address: SocketAddr,
+        protocol: Option<String>,
+    },
+    /// The session ended.
+    Disconnected { reason: DisconnectReason },
+    /// A `BootNotification` was answered with a CALLRESULT registering the station as `status`.
+    Booted {
+        request: BootNotificationRequest,
+        status: RegistrationStatus,
+    },
+    /// A `StatusNotification` changed the status of a connector, see [`crate::Connectors`].
+    ///
+    /// Notifications that repeat the current status or are older than it publish nothing.
+    ConnectorStatusChanged {
+        previous: Option<ConnectorStatus>,
+        status: ConnectorStatus,
+    },
+    /// A `StartTransaction` was answered with the id of a new transaction.
+    TransactionStarted { transaction: Transaction },
+    /// A `StopTransaction` closed a transaction.
+    TransactionStopped { transaction: Transaction },
+    /// The station booted or started another transaction on the connector while the
+    /// transaction was active, so it must have lost it.
+    ///
+    /// A `StopTransaction` the station sends for it later still closes it.
+    TransactionOrphaned { transaction: Transaction },
+    /// A `SignedFirmwareStatusNotification` was answered with a CALLRESULT.
+    FirmwareProgress { update: FirmwareUpdate },
+    /// A `LogStatusNotification` was answered with a CALLRESULT.
+    LogProgress { upload: LogUpload },
+    /// The station sent no OCPP message for the configured number of heartbeat intervals, see
+    /// [`crate::Config::with_unresponsive_after`]. The connection stays open.
+    Unresponsive {
+        last_message_at: Option<DateTime<Utc>>,
+    },
+    /// An unresponsive station sent a message again.
+    Recovered { unresponsive_since: DateTime<Utc> },
+    /// The station sent a CALL.
+    MessageReceived {
+        unique_id: String,
+        action: String,
+        payload: Value,
+    },
+    /// The station sent a CALL other than `BootNotification` without being accepted, see
+    /// [`crate::RegistrationPolicy`].
+    OutOfOrder {
+        unique_id: String,
+        action: String,
+        status: Option<RegistrationStatus>,
+    },
+    /// Crush answered a CALL of the station with a CALLRESULT.
+    ResponseSent {
+        unique_id: String,
+        action: String,
+        payload: Value,
+    },
+    /// Crush sent a CALL to the station.
+    CallSent {
+        unique_id: String,
+        action: String,
+        payload: Value,
+    },
+    /// The station answered a CALL of crush with a CALLRESULT.
+    CallResult {
+        unique_id: String,
+        action: String,
+        payload: Value,
+    },
+    /// A CALL was answered with a CALLERROR, by crush or by the station.
+    Error {
+        direction: Direction,
+        unique_id: String,
+        action: String,
+        error_code: String,
+        error_description: String,
+        error_details: Value,
+    },
+}
+
+/// The sending side of the event stream.
+///
+/// Slow subscribers never hold crush up: once a subscriber falls more than the configured
+/// capacity behind, its oldest events are dropped and it receives
+/// [`broadcast::error::RecvError::Lagged`] with the number of missed events.
+#[derive(Clone)]
+pub(crate) struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Publishes the event built by `kind`, which is only called if anyone is subscribed.
+    pub(crate) fn emit(&self, station_id: &str, kind: impl FnOnce() -> EventKind) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let event = Event {
+            station_id: station_id.to_owned(),
+            timestamp: Utc::now(),
+            kind: kind(),
+        };
+        // Subscribers may have gone away since the check above.
+        drop(self.sender.send(event));
+    }
+}
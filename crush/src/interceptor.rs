@@ -0,0 +1,110 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tower::{service_fn, util::BoxCloneSyncService, Layer, Service};
+
+use crate::{action::ActionRegistry, context::StationContext, error::OcppResponseError};
+
+/// A CALL received from a charging station, as it travels through the interceptor chain.
+///
+/// Interceptors are [`tower::Layer`]s wrapping the [`CallService`] that dispatches the call to
+/// its handler. They see the call before dispatch and the `Ok` payload or the
+/// [`OcppResponseError`] after it, and can short-circuit the chain by returning an error
+/// without calling the inner service.
+#[derive(Debug, Clone)]
+pub struct OcppCall {
+    context: StationContext,
+    frame: String,
+    unique_id: String,
+    action: String,
+    payload: Value,
+}
+
+impl OcppCall {
+    pub(crate) fn new(
+        context: StationContext,
+        frame: String,
+        unique_id: String,
+        action: String,
+        payload: Value,
+    ) -> Self {
+        Self {
+            context,
+            frame,
+            unique_id,
+            action,
+            payload,
+        }
+    }
+
+    /// The station the call was received from.
+    #[must_use]
+    pub fn context(&self) -> &StationContext {
+        &self.context
+    }
+
+    /// The WebSocket text frame exactly as it was received.
+    #[must_use]
+    pub fn frame(&self) -> &str {
+        &self.frame
+    }
+
+    /// The unique id of the CALL, which the CALLRESULT or CALLERROR echoes.
+    #[must_use]
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    /// The action name of the CALL.
+    #[must_use]
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// The request payload that is handed to the handler.
+    #[must_use]
+    pub fn payload(&self) -> &Value {
+        &self.payload
+    }
+
+    /// Mutable access to the request payload, e.g. to normalize or redact fields before dispatch.
+    pub fn payload_mut(&mut self) -> &mut Value {
+        &mut self.payload
+    }
+}
+
+/// The service every interceptor wraps, resolving an [`OcppCall`] to the CALLRESULT payload.
+pub type CallService = BoxCloneSyncService<OcppCall, Value, OcppResponseError>;
+
+pub(crate) type BoxedLayer = Box<dyn FnOnce(CallService) -> CallService + Send>;
+
+pub(crate) fn boxed_layer<L>(layer: L) -> BoxedLayer
+where
+    L: Layer<CallService> + Send + 'static,
+    L::Service: Service<OcppCall, Response = Value, Error = OcppResponseError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    <L::Service as Service<OcppCall>>::Future: Send + 'static,
+{
+    Box::new(move |service| CallService::new(layer.layer(service)))
+}
+
+/// Builds the dispatch service for `registry` wrapped in `layers`, the first layer outermost.
+pub(crate) fn build_service(registry: ActionRegistry, layers: Vec<BoxedLayer>) -> CallService {
+    let registry = Arc::new(registry);
+
+    let dispatch = service_fn(move |call: OcppCall| {
+        let registry = Arc::clone(&registry);
+        async move {
+            registry
+                .dispatch(call.context, call.action, call.payload)
+                .await
+        }
+    });
+
+    layers
+        .into_iter()
+        .rev()
+        .fold(CallService::new(dispatch), |service, layer| layer(service))
+}
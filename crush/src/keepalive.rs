@@ -0,0 +1,181 @@
+use std::{
+    future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::{select, sync::mpsc::Sender, time::sleep};
+
+use crate::{client_loop::ToClient, disconnect::DisconnectReason, session::Session};
+
+/// Liveness settings applied to every station session.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Keepalive {
+    pub(crate) ping_interval: Option<Duration>,
+    pub(crate) max_missed_pongs: u32,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) unresponsive_after: Option<u32>,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            ping_interval: Some(Duration::from_mins(1)),
+            max_missed_pongs: 3,
+            idle_timeout: None,
+            unresponsive_after: Some(3),
+        }
+    }
+}
+
+/// When a session last received anything from its station.
+pub(crate) struct Activity {
+    started: Instant,
+    last_frame: AtomicU64,
+    last_message: AtomicU64,
+}
+
+impl Activity {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_frame: AtomicU64::default(),
+            last_message: AtomicU64::default(),
+        }
+    }
+
+    /// Records a frame of any kind, including pongs.
+    pub(crate) fn frame_received(&self) {
+        self.last_frame.store(self.now(), Ordering::Relaxed);
+    }
+
+    /// Records an OCPP message.
+    pub(crate) fn message_received(&self) {
+        let now = self.now();
+        self.last_frame.store(now, Ordering::Relaxed);
+        self.last_message.store(now, Ordering::Relaxed);
+    }
+
+    /// Whether any frame was received at or after `timestamp`, as returned by `now`.
+    fn frame_received_since(&self, timestamp: u64) -> bool {
+        self.last_frame.load(Ordering::Relaxed) >= timestamp
+    }
+
+    fn since_last_message(&self) -> Duration {
+        self.since(&self.last_message)
+    }
+
+    fn since(&self, timestamp: &AtomicU64) -> Duration {
+        let millis = self.now().saturating_sub(timestamp.load(Ordering::Relaxed));
+        Duration::from_millis(millis)
+    }
+
+    /// Milliseconds since the session started.
+    fn now(&self) -> u64 {
+        u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX)
+    }
+}
+
+/// Watches a session and returns why it has gone stale, sending pings along the way.
+pub(crate) async fn run_keepalive(
+    keepalive: Keepalive,
+    activity: &Activity,
+    sender: &Sender<ToClient>,
+    session: &Session,
+) -> DisconnectReason {
+    select! {
+        reason = ping(keepalive, activity, sender) => reason,
+        reason = idle(keepalive, activity) => reason,
+        reason = presence(keepalive, activity, session) => reason,
+    }
+}
+
+async fn ping(
+    keepalive: Keepalive,
+    activity: &Activity,
+    sender: &Sender<ToClient>,
+) -> DisconnectReason {
+    let Some(interval) = keepalive.ping_interval else {
+        return future::pending().await;
+    };
+
+    let mut last_ping = None;
+    let mut missed_pongs = 0;
+    loop {
+        sleep(interval).await;
+
+        // Any frame received after the last ping proves the station is still there.
+        if let Some(sent) = last_ping {
+            if activity.frame_received_since(sent) {
+                missed_pongs = 0;
+            } else {
+                missed_pongs += 1;
+            }
+        }
+
+        if missed_pongs >= keepalive.max_missed_pongs {
+            return DisconnectReason::MissedPongs;
+        }
+
+        last_ping = Some(activity.now());
+        if sender.send(ToClient::Ping).await.is_err() {
+            return DisconnectReason::Shutdown;
+        }
+    }
+}
+
+async fn idle(keepalive: Keepalive, activity: &Activity) -> DisconnectReason {
+    let Some(timeout) = keepalive.idle_timeout else {
+        return future::pending().await;
+    };
+
+    loop {
+        let idle = activity.since_last_message();
+        if idle >= timeout {
+            return DisconnectReason::IdleTimeout;
+        }
+        sleep(timeout.saturating_sub(idle)).await;
+    }
+}
+
+/// Marks the station unresponsive when it misses too many heartbeats, never ending the session.
+async fn presence(
+    keepalive: Keepalive,
+    activity: &Activity,
+    session: &Session,
+) -> DisconnectReason {
+    let Some(intervals) = keepalive.unresponsive_after else {
+        return future::pending().await;
+    };
+
+    let mut heartbeat_intervals = session.heartbeat_intervals();
+    loop {
+        let timeout = heartbeat_intervals
+            .borrow_and_update()
+            .map(|interval| interval.saturating_mul(intervals));
+        let wait = timeout.map(|timeout| {
+            let silent = activity.since_last_message();
+            if silent >= timeout {
+                session.unresponsive();
+                timeout
+            } else {
+                timeout.saturating_sub(silent)
+            }
+        });
+        select! {
+            () = sleep_for(wait) => {}
+            changed = heartbeat_intervals.changed() => {
+                if changed.is_err() {
+                    return future::pending().await;
+                }
+            }
+        }
+    }
+}
+
+/// Sleeps for `duration`, or forever without one.
+async fn sleep_for(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => sleep(duration).await,
+        None => future::pending().await,
+    }
+}
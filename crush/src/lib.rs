@@ -1,78 +1,636 @@
-use rust_ocpp::v1_6::messages::start_transaction::{
-    StartTransactionRequest, StartTransactionResponse,
-};
+#![cfg_attr(
+    test,
+    allow(
+        unused_crate_dependencies,
+        reason = "the dev-dependencies are for the integration tests and benchmarks"
+    )
+)]
 
-use std::net::SocketAddr;
-use tokio::task::{JoinError, JoinHandle};
+use rust_ocpp::v1_6::{
+    messages::{
+        boot_notification::{BootNotificationRequest, BootNotificationResponse},
+        heart_beat::{HeartbeatRequest, HeartbeatResponse},
+        meter_values::{MeterValuesRequest, MeterValuesResponse},
+        start_transaction::{StartTransactionRequest, StartTransactionResponse},
+        status_notification::{StatusNotificationRequest, StatusNotificationResponse},
+        stop_transaction::{StopTransactionRequest, StopTransactionResponse},
+    },
+    types::IdTagInfo,
+};
+use serde_json::Value;
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock, Weak},
+    time::Duration,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, oneshot},
+    task::{JoinError, JoinHandle},
+    time::timeout,
+};
+use tower::{Layer, Service};
 
+pub use action::{Action, HandleAction, HandleFallback};
+pub use certificate::ClientCertificate;
+pub use certificate_authority::CertificateAuthority;
 pub use chrono;
+pub use config::{Config, DuplicateConnectionPolicy, RegistrationPolicy};
+pub use connection::{ConnectionDecision, ConnectionRequest, HandleConnection};
+pub use connectors::{ConnectorStatus, Connectors};
+pub use context::StationContext;
+pub use credentials::{CredentialStore, InMemoryCredentialStore};
+pub use disconnect::DisconnectReason;
+pub use error::BuildError;
+pub use error::CallError;
+pub use error::CertificateAuthorityError;
 pub use error::OcppResponseError;
 pub use error::OcppResult;
+pub use error::SecurityProfileError;
+pub use error::StorageError;
+pub use error::TlsError;
+pub use events::{Direction, Event, EventKind};
+pub use hyper::http;
+pub use interceptor::{CallService, OcppCall};
 pub use messages::{
     boot_notification::HandleBootNotificationRequest, heartbeat::HandleHeartbeatRequest,
+    log_status_notification::HandleLogStatusNotificationRequest,
+    meter_values::HandleMeterValuesRequest,
+    security_event_notification::HandleSecurityEventNotificationRequest,
+    sign_certificate::HandleSignCertificateRequest,
+    signed_firmware_status_notification::HandleSignedFirmwareStatusNotificationRequest,
+    start_transaction::HandleStartTransactionRequest,
     status_notification::HandleStatusNotificationRequest,
+    stop_transaction::HandleStopTransactionRequest,
 };
+pub use outbound::PendingCall;
 pub use rust_ocpp;
+pub use security_profile::SecurityProfile;
+pub use session::StationInfo;
+pub use station_state::{FirmwareUpdate, LogUpload, StoredStation};
+pub use storage::{FileStorage, InMemoryStorage, Snapshot, Storage};
+#[cfg(feature = "sqlite")]
+pub use storage::{SqliteStorage, TransactionFilter};
+pub use tls::TlsConfig;
+pub use tower;
+pub use transactions::{Transaction, TransactionStop};
 
 mod accept_loop;
+mod action;
+pub mod actions;
+mod certificate;
+mod certificate_authority;
 mod client_loop;
+mod config;
+mod connection;
+mod connectors;
+mod context;
 mod controller_loop;
+mod credentials;
+mod disconnect;
 mod error;
+mod events;
+mod interceptor;
+mod keepalive;
 mod messages;
+mod outbound;
+mod registration;
+pub mod security;
+mod security_profile;
 mod serde;
 mod server_loop;
+mod session;
+mod station_state;
+mod storage;
+mod storage_loop;
+mod supervisor;
+mod tls;
+mod transactions;
 
 use accept_loop::AcceptHandle;
-use controller_loop::ControllerHandle;
-use server_loop::ServerHandle;
+use action::ActionRegistry;
+use actions::{
+    BootNotification, Heartbeat, LogStatusNotification, MeterValues, SecurityEventNotification,
+    SignCertificate, SignedFirmwareStatusNotification, StartTransaction, StatusNotification,
+    StopTransaction,
+};
+use certificate_authority::CertificateAuthorityHandler;
+use client_loop::ToClient;
+use connection::DefaultConnectionHandler;
+use events::Events;
+use interceptor::BoxedLayer;
+use messages::{
+    boot_notification::BootNotificationHandler, heartbeat::HeartbeatHandler,
+    log_status_notification::LogStatusNotificationHandler, meter_values::MeterValuesHandler,
+    security_event_notification::SecurityEventNotificationHandler,
+    sign_certificate::SignCertificateHandler,
+    signed_firmware_status_notification::SignedFirmwareStatusNotificationHandler,
+    start_transaction::StartTransactionHandler, status_notification::StatusNotificationHandler,
+    stop_transaction::StopTransactionHandler,
+};
+use outbound::OutboundCall;
+use security::{
+    LogStatusNotificationRequest, LogStatusNotificationResponse, SecurityEventNotificationRequest,
+    SecurityEventNotificationResponse, SignCertificateRequest, SignCertificateResponse,
+    SignedFirmwareStatusNotificationRequest, SignedFirmwareStatusNotificationResponse,
+};
+use server_loop::{ServerHandle, ToServer};
+use station_state::StationStates;
+use storage_loop::StorageHandle;
+use tls::Tls;
 
-pub trait HandleStartTransactionRequest {
-    fn handle(&self, request: StartTransactionRequest) -> StartTransactionResponse;
+pub struct Crush {
+    /// The only strong reference to the handle the [`CertificateAuthority`] delivers with, so it
+    /// is released once crush stops.
+    handle: Arc<CrushHandle>,
+    server_join: JoinHandle<()>,
+    accept_join: JoinHandle<()>,
 }
 
-#[derive(Clone)]
-pub struct Config {
-    address: SocketAddr,
-}
+impl Crush {
+    /// Returns a handle for querying the running instance from other tasks.
+    #[must_use]
+    pub fn handle(&self) -> CrushHandle {
+        CrushHandle::clone(&self.handle)
+    }
 
-impl Config {
+    /// Subscribes to the events of all station sessions.
+    ///
+    /// Only events that happen after subscribing are received. A subscriber that cannot keep
+    /// up misses the oldest events, see [`Config::with_event_capacity`], and is told how many
+    /// it missed through [`broadcast::error::RecvError::Lagged`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{Config, CrushBuilder};
+    /// # use tokio::sync::broadcast::error::RecvError;
+    /// # async fn example() -> Result<(), crush::BuildError> {
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let crush = CrushBuilder::new(config).try_build().await?;
+    /// let mut events = crush.events();
+    /// tokio::spawn(async move {
+    ///     loop {
+    ///         match events.recv().await {
+    ///             Ok(event) => tracing::info!("{}: {:?}", event.station_id(), event.kind()),
+    ///             Err(RecvError::Lagged(missed)) => tracing::warn!("Missed {missed} events"),
+    ///             Err(RecvError::Closed) => break,
+    ///         }
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
     #[must_use]
-    pub fn new(address: SocketAddr) -> Self {
-        Self { address }
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.handle.events()
+    }
+
+    /// Runs the Crush instance until its server loop or the task accepting connections ends.
+    ///
+    /// A crashed server loop is restarted, `run` returns once it crashed too often to be
+    /// restarted again. Connections are no longer accepted once `run` returned.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error of type `JoinError` if the server's task or the accepting
+    /// task fails to run or is canceled, such as by a panic.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{Config, CrushBuilder};
+    /// # async fn example() -> Result<(), crush::BuildError> {
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let crush = CrushBuilder::new(config).try_build().await?;
+    /// if let Err(e) = crush.run().await {
+    ///     tracing::warn!("Server failed: {}", e);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run(self) -> Result<(), JoinError> {
+        let Self {
+            handle: _handle,
+            mut server_join,
+            mut accept_join,
+        } = self;
+        let result = tokio::select! {
+            result = &mut server_join => result,
+            result = &mut accept_join => result,
+        };
+        accept_join.abort();
+        result
     }
 }
 
-pub struct Crush {
-    server_join: JoinHandle<()>,
+/// A cheaply clonable handle to a running [`Crush`] instance.
+#[derive(Clone)]
+pub struct CrushHandle {
+    server_handle: ServerHandle,
+    call_timeout: Duration,
+    events: Events,
+    tls: Option<Arc<Tls>>,
+    credentials: Option<Arc<dyn CredentialStore>>,
+    reconnect_timeout: Duration,
+    states: StationStates,
 }
 
-impl Crush {
-    /// Runs the Crush instance and awaits the completion of the server's join handle.
+impl CrushHandle {
+    /// Returns the ids of all stations with an open session.
+    ///
+    /// Sessions are removed as soon as their connection ends, no matter whether the station
+    /// closed it, the connection broke or writing to it timed out.
+    pub async fn connected_stations(&self) -> Vec<String> {
+        let (sender, receiver) = oneshot::channel();
+        if self
+            .server_handle
+            .send(ToServer::ConnectedStations(sender))
+            .await
+            .is_err()
+        {
+            return Vec::new();
+        }
+        receiver.await.unwrap_or_default()
+    }
+
+    /// Subscribes to the events of all station sessions, see [`Crush::events`].
+    #[must_use]
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Returns what is known about every connected station.
+    ///
+    /// A station with more than one session, see [`DuplicateConnectionPolicy`], is listed once
+    /// with its newest session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::CrushHandle;
+    /// # async fn example(handle: CrushHandle) {
+    /// for station in handle.stations().await {
+    ///     tracing::info!(
+    ///         "{} connected from {} since {}",
+    ///         station.station_id(),
+    ///         station.address(),
+    ///         station.connected_at()
+    ///     );
+    /// }
+    /// # }
+    /// ```
+    pub async fn stations(&self) -> Vec<StationInfo> {
+        let (sender, receiver) = oneshot::channel();
+        if self
+            .server_handle
+            .send(ToServer::Stations(sender))
+            .await
+            .is_err()
+        {
+            return Vec::new();
+        }
+        receiver.await.unwrap_or_default()
+    }
+
+    /// Returns what is known about the station with the id `station_id`, if it is connected.
+    pub async fn station(&self, station_id: &str) -> Option<StationInfo> {
+        let (sender, receiver) = oneshot::channel();
+        self.server_handle
+            .send(ToServer::Station(station_id.to_owned(), sender))
+            .await
+            .ok()?;
+        receiver.await.ok().flatten()
+    }
+
+    /// Aborts the task of the server loop as a crash would, for testing its supervision.
+    #[cfg(feature = "test-util")]
+    pub fn abort_server_loop(&self) {
+        self.server_handle.abort();
+    }
+
+    /// Aborts the task of the controller of `station_id` as a crash would, for testing its
+    /// supervision. Returns `false` if the station is not connected.
+    #[cfg(feature = "test-util")]
+    pub async fn abort_controller(&self, station_id: &str) -> bool {
+        let Some(controller) = self.server_handle.controller(station_id).await else {
+            return false;
+        };
+        controller.abort();
+        true
+    }
+
+    /// Returns the last `BootNotification` of `station_id` crush answered, even if the station
+    /// is not connected.
+    ///
+    /// Unlike [`StationInfo::boot_notification`] this is kept across connections and, with a
+    /// [`Storage`], across restarts.
+    #[must_use]
+    pub fn boot_notification(&self, station_id: &str) -> Option<BootNotificationRequest> {
+        self.states.boot_notification(station_id)
+    }
+
+    /// Returns the progress of the last signed firmware update of `station_id`.
+    ///
+    /// The progress is taken from the `SignedFirmwareStatusNotification`s of the station and
+    /// kept while it reboots into the new firmware, so it is known even when the station is
+    /// not connected. Returns `None` if the station never reported one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{security::FirmwareStatus, CrushHandle};
+    /// # fn example(handle: CrushHandle) {
+    /// if let Some(update) = handle.firmware_update("CP001") {
+    ///     if update.status() == FirmwareStatus::Installed {
+    ///         tracing::info!("CP001 installed firmware update {:?}", update.request_id());
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn firmware_update(&self, station_id: &str) -> Option<FirmwareUpdate> {
+        self.states.firmware_update(station_id)
+    }
+
+    /// Returns the connector status table of `station_id`.
+    ///
+    /// The table is built from the `StatusNotification`s the station sent that crush answered
+    /// with a CALLRESULT, also in earlier sessions. It is empty for unknown stations. Subscribe
+    /// to [`EventKind::ConnectorStatusChanged`] to follow changes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{rust_ocpp::v1_6::types::ChargePointStatus, CrushHandle};
+    /// # fn example(handle: CrushHandle) {
+    /// let connectors = handle.connectors("CP001");
+    /// let available = connectors
+    ///     .iter()
+    ///     .filter(|connector| *connector.status() == ChargePointStatus::Available)
+    ///     .count();
+    /// tracing::info!("CP001 has {available} available connectors");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn connectors(&self, station_id: &str) -> Connectors {
+        self.states.connectors(station_id)
+    }
+
+    /// Returns the transaction with the id `transaction_id`, active or not.
+    ///
+    /// Crush keeps every active transaction and the 1024 that ended last, older ones are only
+    /// kept by the [`Storage`].
+    #[must_use]
+    pub fn transaction(&self, transaction_id: i32) -> Option<Transaction> {
+        self.states.transactions().get(transaction_id)
+    }
+
+    /// Returns the transactions of `station_id`, oldest first, including those that stopped or
+    /// were orphaned.
+    ///
+    /// Like [`CrushHandle::transaction`], this leaves out the transactions that ended before the
+    /// last 1024 to end, query the [`Storage`] for the full history.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::CrushHandle;
+    /// # fn example(handle: CrushHandle) {
+    /// let energy: i64 = handle
+    ///     .transactions("CP001")
+    ///     .iter()
+    ///     .filter_map(|transaction| transaction.energy())
+    ///     .sum();
+    /// tracing::info!("CP001 charged {energy} Wh recently");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn transactions(&self, station_id: &str) -> Vec<Transaction> {
+        self.states.transactions().list(Some(station_id))
+    }
+
+    /// Returns the active transactions of all stations, oldest first.
+    ///
+    /// A transaction stays active until its `StopTransaction` or until crush notices the station
+    /// lost it, see [`EventKind::TransactionOrphaned`].
+    #[must_use]
+    pub fn active_transactions(&self) -> Vec<Transaction> {
+        self.states
+            .transactions()
+            .list(None)
+            .into_iter()
+            .filter(Transaction::is_active)
+            .collect()
+    }
+
+    /// Returns the progress of the last log upload of `station_id`.
+    ///
+    /// The progress is taken from the `LogStatusNotification`s of the station. Returns `None`
+    /// if the station never reported one.
+    #[must_use]
+    pub fn log_upload(&self, station_id: &str) -> Option<LogUpload> {
+        self.states.log_upload(station_id)
+    }
+
+    /// Returns how crush answers for `id_tag`, if it was set with [`CrushHandle::set_id_tag`].
+    ///
+    /// Handlers look id tags up with [`StationContext::id_tag`].
+    #[must_use]
+    pub fn id_tag(&self, id_tag: &str) -> Option<IdTagInfo> {
+        self.states.id_tag(id_tag)
+    }
+
+    /// Sets how crush answers for `id_tag`, replacing what was set before.
+    ///
+    /// Crush keeps the id tags in its [`Storage`] for the handlers of `Authorize`,
+    /// `StartTransaction` and `StopTransaction`, the default handlers do not consult them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{
+    /// #     rust_ocpp::v1_6::types::{AuthorizationStatus, IdTagInfo},
+    /// #     CrushHandle,
+    /// # };
+    /// # fn example(handle: CrushHandle) {
+    /// handle.set_id_tag(
+    ///     "04A2B3C4",
+    ///     IdTagInfo {
+    ///         expiry_date: None,
+    ///         parent_id_tag: None,
+    ///         status: AuthorizationStatus::Blocked,
+    ///     },
+    /// );
+    /// # }
+    /// ```
+    pub fn set_id_tag(&self, id_tag: impl Into<String>, info: IdTagInfo) {
+        self.states.set_id_tag(id_tag.into(), info);
+    }
+
+    /// Removes `id_tag`, returning whether it was set.
+    #[must_use]
+    pub fn remove_id_tag(&self, id_tag: &str) -> bool {
+        self.states.remove_id_tag(id_tag)
+    }
+
+    /// Returns the CALLs that were waiting for an answer when crush stopped the last time.
+    ///
+    /// Their answers are lost, whoever sent them has to find out whether the station acted on
+    /// them. Crush reports them once: they are removed from the [`Storage`] when it is loaded.
+    #[must_use]
+    pub fn interrupted_calls(&self) -> Vec<PendingCall> {
+        self.states.interrupted_calls()
+    }
+
+    /// Waits until every change crush made so far is written to its [`Storage`].
+    ///
+    /// Returns right away if no storage is configured. Write errors are logged rather than
+    /// returned, see [`Storage`].
+    pub async fn flush_storage(&self) {
+        self.states.storage().flush().await;
+    }
+
+    /// Reloads the TLS certificate and key from their files, see [`TlsConfig`].
+    ///
+    /// New connections use the reloaded certificate while open sessions are left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Fails if TLS is not configured or the files cannot be loaded, in which case the
+    /// previous certificate stays in use.
+    pub async fn reload_tls(&self) -> Result<(), TlsError> {
+        self.tls
+            .as_ref()
+            .ok_or(TlsError::NotConfigured)?
+            .reload()
+            .await
+    }
+
+    /// Moves a connected station to HTTP Basic authentication with a new `AuthorizationKey`.
+    ///
+    /// A random 20 byte key is staged in the [`CredentialStore`], so the station may
+    /// authenticate with either its current credential or the key, and sent hex encoded with
+    /// `ChangeConfiguration`. Once the station accepted it, `SecurityProfile` is changed to
+    /// `profile`. The station then has to reconnect within the timeout set with
+    /// [`Config::with_reconnect_timeout`], after which the key becomes its only credential.
+    /// Otherwise the key is dropped, so the station can fall back to its previous profile. The
+    /// same call rotates the key of a station that already authenticates. Stations still on
+    /// Security Profile 0 can only connect while [`Config::with_unsecured_stations`] is enabled.
     ///
     /// # Errors
     ///
-    /// This function returns an error of type `JoinError` if the server's task fails to run or is canceled.
-    /// The error can occur if the server handle encounters an issue during its execution, such as a panic
-    /// or cancellation of the server task.
+    /// Fails if no writable credential store is configured, `profile` needs TLS but crush has
+    /// no [`TlsConfig`], a `ChangeConfiguration` fails or is not accepted, or the station does
+    /// not reconnect in time. Nothing is sent to the station if the configuration rules the
+    /// upgrade out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system cannot provide randomness for the key.
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// let crush = Crush::new(config).await.unwrap();
-    /// if let Err(e) = crush.run().await {
-    ///     eprintln!("Server failed: {}", e);
+    /// ```rust,no_run
+    /// # use crush::{CrushHandle, SecurityProfile};
+    /// # async fn example(handle: CrushHandle) {
+    /// match handle
+    ///     .upgrade_security_profile("CP001", SecurityProfile::BasicAuthentication)
+    ///     .await
+    /// {
+    ///     Ok(()) => tracing::info!("CP001 authenticates with its AuthorizationKey"),
+    ///     Err(error) => tracing::warn!("CP001 was not upgraded: {error}"),
     /// }
+    /// # }
     /// ```
-    pub async fn run(self) -> Result<(), JoinError> {
-        self.server_join.await
+    pub async fn upgrade_security_profile(
+        &self,
+        station_id: &str,
+        profile: SecurityProfile,
+    ) -> Result<(), SecurityProfileError> {
+        let credentials = self
+            .credentials
+            .as_deref()
+            .ok_or(SecurityProfileError::NoCredentialStore)?;
+        security_profile::upgrade(
+            self,
+            credentials,
+            station_id,
+            profile,
+            self.reconnect_timeout,
+        )
+        .await
+    }
+
+    /// Sends a CALL for the action `A` to a connected station and waits for its response.
+    ///
+    /// When a station has more than one session, see [`DuplicateConnectionPolicy`], the call
+    /// goes to the session that connected last.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the station is not connected, answers with a CALLERROR, disconnects or does not
+    /// respond within the configured call timeout, or if the response does not match
+    /// `A::Response`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{actions::Reset, rust_ocpp::v1_6::{messages::reset::ResetRequest, types::ResetRequestStatus}, CrushHandle};
+    /// # async fn example(handle: CrushHandle) {
+    /// let request = ResetRequest {
+    ///     kind: ResetRequestStatus::Soft,
+    /// };
+    /// match handle.call::<Reset>("CP001", request).await {
+    ///     Ok(response) => tracing::info!("Reset: {:?}", response.status),
+    ///     Err(error) => tracing::warn!("Reset failed: {error}"),
+    /// }
+    /// # }
+    /// ```
+    pub async fn call<A: Action>(
+        &self,
+        station_id: &str,
+        request: A::Request,
+    ) -> Result<A::Response, CallError> {
+        let payload = serde_json::to_value(request)
+            .map_err(|error| CallError::Serialize(error.to_string()))?;
+
+        let session = self
+            .server_handle
+            .session(station_id)
+            .await
+            .ok_or_else(|| CallError::NotConnected(station_id.to_owned()))?;
+
+        let (reply, receiver) = oneshot::channel();
+        let call = OutboundCall {
+            action: A::NAME,
+            payload,
+            reply,
+        };
+        session
+            .send(ToClient::Call(call))
+            .await
+            .map_err(|_closed| CallError::Disconnected)?;
+
+        let response = timeout(self.call_timeout, receiver)
+            .await
+            .map_err(|_elapsed| CallError::Timeout)?
+            .map_err(|_closed| CallError::Disconnected)??;
+
+        serde_json::from_value(response)
+            .map_err(|error| CallError::InvalidResponse(error.to_string()))
     }
 }
 
 pub struct CrushBuilder {
     config: Config,
-    heartbeat_handler: Option<Box<dyn HandleHeartbeatRequest + Send + Sync>>,
-    boot_notification_handler: Option<Box<dyn HandleBootNotificationRequest + Send + Sync>>,
-    status_notification_handler: Option<Box<dyn HandleStatusNotificationRequest + Send + Sync>>,
+    registry: ActionRegistry,
+    layers: Vec<BoxedLayer>,
+    connection_handler: Arc<dyn HandleConnection>,
+    credentials: Option<Arc<dyn CredentialStore>>,
+    /// The running instance, for the handler of [`Self::with_certificate_authority`].
+    crush: Arc<OnceLock<Weak<CrushHandle>>>,
+    storage: Option<Arc<dyn Storage>>,
 }
 
 impl CrushBuilder {
@@ -81,6 +639,7 @@ impl CrushBuilder {
     /// # Examples
     ///
     /// ```rust
+    /// # use crush::{Config, CrushBuilder};
     /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
     /// let builder = CrushBuilder::new(config);
     /// ```
@@ -88,73 +647,710 @@ impl CrushBuilder {
     pub fn new(config: Config) -> Self {
         Self {
             config,
-            heartbeat_handler: None,
-            boot_notification_handler: None,
-            status_notification_handler: None,
+            registry: ActionRegistry::with_defaults(),
+            layers: Vec::new(),
+            connection_handler: Arc::new(DefaultConnectionHandler),
+            credentials: None,
+            crush: Arc::default(),
+            storage: None,
         }
     }
 
+    /// Requires every station to authenticate with HTTP Basic authentication against `store`.
+    ///
+    /// This is OCPP Security Profile 1: the username must be the station id from the
+    /// connection path and the password is checked by the [`CredentialStore`]. Stations that
+    /// fail are refused with `401 Unauthorized` and never upgraded. Authentication runs before
+    /// the connection handler.
+    #[must_use]
+    pub fn with_credential_store<Cs>(mut self, store: Cs) -> Self
+    where
+        Cs: CredentialStore + 'static,
+    {
+        self.credentials = Some(Arc::new(store));
+        self
+    }
+
+    /// Keeps the state crush builds from station messages in `storage`, so it survives
+    /// restarts.
+    ///
+    /// That is the station registrations and boot info, connector statuses, transactions, id
+    /// tags and the CALLs waiting for an answer. The storage is loaded by
+    /// [`CrushBuilder::try_build`], which reports if that fails. Without a storage the state
+    /// lives as long as the [`Crush`] instance.
+    #[must_use]
+    pub fn with_storage<St>(mut self, storage: St) -> Self
+    where
+        St: Storage + 'static,
+    {
+        self.storage = Some(Arc::new(storage));
+        self
+    }
+
+    /// Sets the handler deciding whether a station may connect.
+    ///
+    /// It runs before the connection is upgraded, so stations it rejects never get a WebSocket.
+    /// Without a connection handler every station is accepted.
+    #[must_use]
+    pub fn with_connection_handler<Hc>(mut self, handler: Hc) -> Self
+    where
+        Hc: HandleConnection + 'static,
+    {
+        self.connection_handler = Arc::new(handler);
+        self
+    }
+
+    /// Sets the connection handler from an async closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{http::StatusCode, Config, ConnectionDecision, CrushBuilder};
+    /// #[derive(Clone)]
+    /// struct Tenant(String);
+    ///
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_connect(|request| async move {
+    ///     if request.station_id().starts_with("ACME-") {
+    ///         ConnectionDecision::accept().with_metadata(Tenant("acme".to_owned()))
+    ///     } else {
+    ///         ConnectionDecision::reject(StatusCode::NOT_FOUND)
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_connect<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(ConnectionRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ConnectionDecision> + Send + 'static,
+    {
+        self.with_connection_handler(handler)
+    }
+
     /// Sets the heartbeat handler.
     ///
+    /// Any `Fn(StationContext, HeartbeatRequest)` returning a future can be used in place of a
+    /// type implementing [`HandleHeartbeatRequest`], see [`CrushBuilder::on_heartbeat`].
+    ///
     /// # Examples
     ///
     /// ```rust
+    /// # use async_trait::async_trait;
+    /// # use crush::{
+    /// #     chrono::Utc,
+    /// #     rust_ocpp::v1_6::messages::heart_beat::{HeartbeatRequest, HeartbeatResponse},
+    /// #     Config, CrushBuilder, HandleHeartbeatRequest, OcppResult, StationContext,
+    /// # };
+    /// struct MyHeartbeatHandler;
+    ///
+    /// #[async_trait]
+    /// impl HandleHeartbeatRequest for MyHeartbeatHandler {
+    ///     async fn handle(
+    ///         &self,
+    ///         _context: StationContext,
+    ///         _request: HeartbeatRequest,
+    ///     ) -> OcppResult<HeartbeatResponse> {
+    ///         Ok(HeartbeatResponse { current_time: Utc::now() })
+    ///     }
+    /// }
+    ///
     /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
     /// let builder = CrushBuilder::new(config).with_heartbeat_handler(MyHeartbeatHandler);
     /// ```
     #[must_use]
-    pub fn with_heartbeat_handler<Hr>(mut self, handler: Hr) -> Self
+    pub fn with_heartbeat_handler<Hr>(self, handler: Hr) -> Self
     where
         Hr: HandleHeartbeatRequest + Send + Sync + 'static,
     {
-        self.heartbeat_handler = Some(Box::new(handler));
-        self
+        self.with_action_handler::<Heartbeat, _>(HeartbeatHandler(handler))
     }
 
     /// Sets the boot notification handler.
     ///
+    /// Any `Fn(StationContext, BootNotificationRequest)` returning a future can be used in place
+    /// of a type implementing [`HandleBootNotificationRequest`], see
+    /// [`CrushBuilder::on_boot_notification`].
+    ///
     /// # Examples
     ///
     /// ```rust
+    /// # use async_trait::async_trait;
+    /// # use crush::{
+    /// #     chrono::Utc,
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::boot_notification::{BootNotificationRequest, BootNotificationResponse},
+    /// #         types::RegistrationStatus,
+    /// #     },
+    /// #     Config, CrushBuilder, HandleBootNotificationRequest, OcppResult, StationContext,
+    /// # };
+    /// struct MyBootNotificationHandler;
+    ///
+    /// #[async_trait]
+    /// impl HandleBootNotificationRequest for MyBootNotificationHandler {
+    ///     async fn handle(
+    ///         &self,
+    ///         _context: StationContext,
+    ///         _request: BootNotificationRequest,
+    ///     ) -> OcppResult<BootNotificationResponse> {
+    ///         Ok(BootNotificationResponse {
+    ///             current_time: Utc::now(),
+    ///             interval: 30,
+    ///             status: RegistrationStatus::Accepted,
+    ///         })
+    ///     }
+    /// }
+    ///
     /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
     /// let builder = CrushBuilder::new(config).with_boot_notification_handler(MyBootNotificationHandler);
     /// ```
     #[must_use]
-    pub fn with_boot_notification_handler<Br>(mut self, handler: Br) -> Self
+    pub fn with_boot_notification_handler<Br>(self, handler: Br) -> Self
     where
         Br: HandleBootNotificationRequest + Send + Sync + 'static,
     {
-        self.boot_notification_handler = Some(Box::new(handler));
+        self.with_action_handler::<BootNotification, _>(BootNotificationHandler(handler))
+    }
+
+    /// Sets the status notification handler.
+    ///
+    /// Any `Fn(StationContext, StatusNotificationRequest)` returning a future can be used in place
+    /// of a type implementing [`HandleStatusNotificationRequest`], see
+    /// [`CrushBuilder::on_status_notification`].
+    #[must_use]
+    pub fn with_status_notification_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleStatusNotificationRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<StatusNotification, _>(StatusNotificationHandler(handler))
+    }
+
+    /// Sets the start transaction handler.
+    ///
+    /// The handler authorizes the id tag, by default every id tag is accepted. Crush allocates
+    /// the transaction id and overwrites the one of the handler's response.
+    #[must_use]
+    pub fn with_start_transaction_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleStartTransactionRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<StartTransaction, _>(StartTransactionHandler(handler))
+    }
+
+    /// Sets the meter values handler.
+    ///
+    /// Crush adds the meter values to their transaction either way, see
+    /// [`Transaction::meter_values`].
+    #[must_use]
+    pub fn with_meter_values_handler<Mr>(self, handler: Mr) -> Self
+    where
+        Mr: HandleMeterValuesRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<MeterValues, _>(MeterValuesHandler(handler))
+    }
+
+    /// Sets the stop transaction handler.
+    ///
+    /// By default the id tag that stopped the transaction is accepted. Crush closes the
+    /// transaction either way, see [`CrushHandle::transaction`].
+    #[must_use]
+    pub fn with_stop_transaction_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleStopTransactionRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<StopTransaction, _>(StopTransactionHandler(handler))
+    }
+
+    /// Sets the security event notification handler of the security extension.
+    ///
+    /// By default security events are logged and acknowledged.
+    #[must_use]
+    pub fn with_security_event_notification_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleSecurityEventNotificationRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<SecurityEventNotification, _>(SecurityEventNotificationHandler(
+            handler,
+        ))
+    }
+
+    /// Sets the signed firmware status notification handler of the security extension.
+    ///
+    /// By default notifications are logged and acknowledged. Crush records the progress either
+    /// way, see [`CrushHandle::firmware_update`].
+    #[must_use]
+    pub fn with_signed_firmware_status_notification_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleSignedFirmwareStatusNotificationRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<SignedFirmwareStatusNotification, _>(
+            SignedFirmwareStatusNotificationHandler(handler),
+        )
+    }
+
+    /// Sets the log status notification handler of the security extension.
+    ///
+    /// By default notifications are logged and acknowledged. Crush records the progress either
+    /// way, see [`CrushHandle::log_upload`].
+    #[must_use]
+    pub fn with_log_status_notification_handler<Lr>(self, handler: Lr) -> Self
+    where
+        Lr: HandleLogStatusNotificationRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<LogStatusNotification, _>(LogStatusNotificationHandler(handler))
+    }
+
+    /// Sets the sign certificate handler of the security extension.
+    ///
+    /// The handler decides whether a station's certificate signing request is passed on to a
+    /// CA, which delivers the certificate later with a [`actions::CertificateSigned`] call. By
+    /// default every request is rejected.
+    ///
+    /// This replaces a [`CertificateAuthority`] set before with
+    /// [`Self::with_certificate_authority`], whichever is set last answers `SignCertificate`.
+    #[must_use]
+    pub fn with_sign_certificate_handler<Sr>(self, handler: Sr) -> Self
+    where
+        Sr: HandleSignCertificateRequest + Send + Sync + 'static,
+    {
+        self.with_action_handler::<SignCertificate, _>(SignCertificateHandler(handler))
+    }
+
+    /// Signs the certificates stations request with `SignCertificate` using a local CA.
+    ///
+    /// Requests whose CSR the [`CertificateAuthority`] accepts are answered with `Accepted`,
+    /// after which crush sends the signed chain to the station with
+    /// [`actions::CertificateSigned`]. All others are rejected.
+    ///
+    /// The CA is the sign certificate handler, so it replaces one set before with
+    /// [`Self::with_sign_certificate_handler`] and is replaced by one set after. Requests that
+    /// arrive while crush shuts down are rejected.
+    #[must_use]
+    pub fn with_certificate_authority(self, authority: CertificateAuthority) -> Self {
+        let handler = CertificateAuthorityHandler {
+            authority: Arc::new(authority),
+            crush: Arc::clone(&self.crush),
+        };
+        self.with_sign_certificate_handler(handler)
+    }
+
+    /// Sets the handler for the action `A`, replacing the default handler if crush ships one.
+    ///
+    /// Requests for actions without a registered handler are answered with a `NotSupported`
+    /// CALLERROR. Crush keeps recording what the answered requests report, and allocates the
+    /// transaction id of a `StartTransaction` whichever handler answers it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use async_trait::async_trait;
+    /// # use crush::{
+    /// #     actions::Authorize,
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::authorize::{AuthorizeRequest, AuthorizeResponse},
+    /// #         types::{AuthorizationStatus, IdTagInfo},
+    /// #     },
+    /// #     Config, CrushBuilder, HandleAction, OcppResult, StationContext,
+    /// # };
+    /// struct MyAuthorizeHandler;
+    ///
+    /// #[async_trait]
+    /// impl HandleAction<Authorize> for MyAuthorizeHandler {
+    ///     async fn handle(
+    ///         &self,
+    ///         _context: StationContext,
+    ///         _request: AuthorizeRequest,
+    ///     ) -> OcppResult<AuthorizeResponse> {
+    ///         Ok(AuthorizeResponse {
+    ///             id_tag_info: IdTagInfo {
+    ///                 expiry_date: None,
+    ///                 parent_id_tag: None,
+    ///                 status: AuthorizationStatus::Accepted,
+    ///             },
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).with_action_handler::<Authorize, _>(MyAuthorizeHandler);
+    /// ```
+    #[must_use]
+    pub fn with_action_handler<A, H>(mut self, handler: H) -> Self
+    where
+        A: Action,
+        H: HandleAction<A> + 'static,
+    {
+        self.registry.register::<A, H>(handler);
         self
     }
 
-    /// Builds a `Crush` instance with the provided configuration.
+    /// Sets the handler for the action `A` from an async closure.
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// This function returns an error of type `std::net::AddrParseError` if the `address` in the configuration
-    /// cannot be parsed as a valid `SocketAddr`. This can happen if the address string is incorrectly formatted.
+    /// ```rust
+    /// # use crush::{
+    /// #     actions::DataTransfer,
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::data_transfer::DataTransferResponse, types::DataTransferStatus,
+    /// #     },
+    /// #     Config, CrushBuilder,
+    /// # };
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_action::<DataTransfer, _>(|_context, request| async move {
+    ///     tracing::info!("Data transfer from vendor {}", request.vendor_string);
+    ///     Ok(DataTransferResponse {
+    ///         status: DataTransferStatus::Accepted,
+    ///         data: None,
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_action<A, Fut>(
+        self,
+        handler: impl Fn(StationContext, A::Request) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        A: Action,
+        Fut: Future<Output = OcppResult<A::Response>> + Send + 'static,
+    {
+        self.with_action_handler::<A, _>(handler)
+    }
+
+    /// Sets the heartbeat handler from an async closure.
     ///
     /// # Examples
     ///
     /// ```rust
+    /// # use crush::{
+    /// #     chrono::Utc, rust_ocpp::v1_6::messages::heart_beat::HeartbeatResponse, Config,
+    /// #     CrushBuilder,
+    /// # };
     /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
-    /// let crush = CrushBuilder::new(config).build();
+    /// let builder = CrushBuilder::new(config).on_heartbeat(|context, _request| async move {
+    ///     tracing::info!("Heartbeat from {}", context.station_id());
+    ///     Ok(HeartbeatResponse { current_time: Utc::now() })
+    /// });
     /// ```
     #[must_use]
-    pub fn build(self) -> Crush {
-        let controller_handle = ControllerHandle::new(
-            self.heartbeat_handler,
-            self.boot_notification_handler,
-            self.status_notification_handler,
-        );
+    pub fn on_heartbeat<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, HeartbeatRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<HeartbeatResponse>> + Send + 'static,
+    {
+        self.with_heartbeat_handler(handler)
+    }
 
-        let (server_handle, server_join) = ServerHandle::new(controller_handle.clone());
+    /// Sets the boot notification handler from an async closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{
+    /// #     chrono::Utc,
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::boot_notification::BootNotificationResponse, types::RegistrationStatus,
+    /// #     },
+    /// #     Config, CrushBuilder,
+    /// # };
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_boot_notification(|_context, _request| async {
+    ///     Ok(BootNotificationResponse {
+    ///         current_time: Utc::now(),
+    ///         interval: 30,
+    ///         status: RegistrationStatus::Accepted,
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_boot_notification<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, BootNotificationRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<BootNotificationResponse>> + Send + 'static,
+    {
+        self.with_boot_notification_handler(handler)
+    }
 
-        tokio::spawn(async move {
-            AcceptHandle::start(self.config.address, server_handle);
-        });
+    /// Sets the status notification handler from an async closure.
+    #[must_use]
+    pub fn on_status_notification<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, StatusNotificationRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<StatusNotificationResponse>> + Send + 'static,
+    {
+        self.with_status_notification_handler(handler)
+    }
+
+    /// Sets the start transaction handler from an async closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{
+    /// #     rust_ocpp::v1_6::{
+    /// #         messages::start_transaction::StartTransactionResponse,
+    /// #         types::{AuthorizationStatus, IdTagInfo},
+    /// #     },
+    /// #     Config, CrushBuilder,
+    /// # };
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_start_transaction(|_context, request| async move {
+    ///     let status = if request.id_tag.starts_with("04") {
+    ///         AuthorizationStatus::Accepted
+    ///     } else {
+    ///         AuthorizationStatus::Invalid
+    ///     };
+    ///     Ok(StartTransactionResponse {
+    ///         id_tag_info: IdTagInfo { expiry_date: None, parent_id_tag: None, status },
+    ///         // Replaced by the id crush allocates.
+    ///         transaction_id: 0,
+    ///     })
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_start_transaction<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, StartTransactionRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<StartTransactionResponse>> + Send + 'static,
+    {
+        self.with_start_transaction_handler(handler)
+    }
+
+    /// Sets the meter values handler from an async closure.
+    #[must_use]
+    pub fn on_meter_values<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, MeterValuesRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<MeterValuesResponse>> + Send + 'static,
+    {
+        self.with_meter_values_handler(handler)
+    }
+
+    /// Sets the stop transaction handler from an async closure.
+    #[must_use]
+    pub fn on_stop_transaction<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, StopTransactionRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<StopTransactionResponse>> + Send + 'static,
+    {
+        self.with_stop_transaction_handler(handler)
+    }
+
+    /// Sets the security event notification handler from an async closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{security::SecurityEventNotificationResponse, Config, CrushBuilder};
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_security_event_notification(|context, request| async move {
+    ///     tracing::info!("{} reported {} at {}", context.station_id(), request.kind, request.timestamp);
+    ///     Ok(SecurityEventNotificationResponse {})
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_security_event_notification<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, SecurityEventNotificationRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<SecurityEventNotificationResponse>> + Send + 'static,
+    {
+        self.with_security_event_notification_handler(handler)
+    }
+
+    /// Sets the signed firmware status notification handler from an async closure.
+    #[must_use]
+    pub fn on_signed_firmware_status_notification<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, SignedFirmwareStatusNotificationRequest) -> Fut
+            + Send
+            + Sync
+            + 'static,
+        Fut: Future<Output = OcppResult<SignedFirmwareStatusNotificationResponse>> + Send + 'static,
+    {
+        self.with_signed_firmware_status_notification_handler(handler)
+    }
+
+    /// Sets the log status notification handler from an async closure.
+    #[must_use]
+    pub fn on_log_status_notification<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, LogStatusNotificationRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<LogStatusNotificationResponse>> + Send + 'static,
+    {
+        self.with_log_status_notification_handler(handler)
+    }
+
+    /// Sets the sign certificate handler from an async closure.
+    #[must_use]
+    pub fn on_sign_certificate<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, SignCertificateRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<SignCertificateResponse>> + Send + 'static,
+    {
+        self.with_sign_certificate_handler(handler)
+    }
 
-        Crush { server_join }
+    /// Sets the handler for actions that have no handler registered.
+    ///
+    /// Without a fallback handler such requests are answered with a `NotSupported` CALLERROR.
+    /// The fallback receives the raw JSON payload, which makes it suitable for vendor specific
+    /// or prototype actions that have no [`Action`] type.
+    #[must_use]
+    pub fn with_fallback_handler<Fr>(mut self, handler: Fr) -> Self
+    where
+        Fr: HandleFallback + 'static,
+    {
+        self.registry.set_fallback(handler);
+        self
+    }
+
+    /// Sets the handler for actions without a registered handler from an async closure.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{Config, CrushBuilder, OcppResponseError};
+    /// # use serde_json::{json, Value};
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).on_fallback(|context, action, payload| async move {
+    ///     match action.as_str() {
+    ///         "VendorPing" => Ok(json!({ "nonce": payload["nonce"] })),
+    ///         _ => Err(OcppResponseError::UnsupportedMessageType {
+    ///             details: Value::String(format!("{action} from {}", context.station_id())),
+    ///         }),
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_fallback<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(StationContext, String, Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OcppResult<Value>> + Send + 'static,
+    {
+        self.with_fallback_handler(handler)
+    }
+
+    /// Wraps message handling in a [`tower::Layer`].
+    ///
+    /// Every CALL received from a station passes through the layers before it is dispatched to
+    /// its handler, which makes them the place for cross-cutting behavior such as audit logging,
+    /// metrics, rate limiting or authorization. Layers are applied in the order they are added,
+    /// the first one being the outermost. A layer rejects a call by returning an
+    /// [`OcppResponseError`] instead of calling the inner service, which is sent to the station
+    /// as a CALLERROR.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{tower::util::MapRequestLayer, Config, CrushBuilder, OcppCall};
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let builder = CrushBuilder::new(config).layer(MapRequestLayer::new(|call: OcppCall| {
+    ///     tracing::info!("{} -> {}", call.context().station_id(), call.frame());
+    ///     call
+    /// }));
+    /// ```
+    #[must_use]
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<CallService> + Send + 'static,
+        L::Service: Service<OcppCall, Response = Value, Error = OcppResponseError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as Service<OcppCall>>::Future: Send + 'static,
+    {
+        self.layers.push(interceptor::boxed_layer(layer));
+        self
+    }
+
+    /// Builds a `Crush` instance with the provided configuration, loading the storage first if
+    /// one was set with [`CrushBuilder::with_storage`].
+    ///
+    /// The loaded state is restored before this returns, so changes made through the handle
+    /// are not overwritten by it, and before the first connection is accepted. The TLS
+    /// certificate is loaded and the listening socket bound here as well, so a misconfigured
+    /// instance fails to build rather than to run.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BuildError`] if the storage could not be loaded, the TLS certificate could
+    /// not be loaded, or the address could not be listened on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use crush::{Config, CrushBuilder, FileStorage};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+    /// let crush = CrushBuilder::new(config)
+    ///     .with_storage(FileStorage::open("crush.jsonl")?)
+    ///     .try_build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn try_build(self) -> Result<Crush, BuildError> {
+        let snapshot = match &self.storage {
+            Some(storage) => Some(storage.load().await?),
+            None => None,
+        };
+        let tls = match &self.config.tls {
+            Some(config) => {
+                let tls = Tls::new(config.clone());
+                tls.reload().await?;
+                Some(Arc::new(tls))
+            }
+            None => None,
+        };
+        let address = self.config.address;
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|source| BuildError::Bind { address, source })?;
+        Ok(self.start(snapshot, tls, listener))
+    }
+
+    fn start(
+        mut self,
+        snapshot: Option<Snapshot>,
+        tls: Option<Arc<Tls>>,
+        listener: TcpListener,
+    ) -> Crush {
+        let (server_handle, server_join) =
+            ServerHandle::new(self.config.duplicate_connection_policy);
+        let handle = CrushHandle {
+            server_handle,
+            call_timeout: self.config.call_timeout,
+            events: Events::new(self.config.event_capacity),
+            tls,
+            credentials: self.credentials.clone(),
+            reconnect_timeout: self.config.reconnect_timeout,
+            states: StationStates::new(
+                self.storage
+                    .take()
+                    .map(StorageHandle::start)
+                    .unwrap_or_default(),
+            ),
+        };
+        if let Some(snapshot) = snapshot {
+            handle.states.restore(snapshot);
+        }
+
+        let handle = Arc::new(handle);
+        // Only set here, `start` consumes the builder.
+        let _unset = self.crush.set(Arc::downgrade(&handle));
+        let service = interceptor::build_service(self.registry, self.layers);
+        let accept_join = AcceptHandle::start(
+            &self.config,
+            listener,
+            &handle,
+            service,
+            self.connection_handler,
+        );
+
+        Crush {
+            handle,
+            server_join,
+            accept_join,
+        }
     }
 }
@@ -1,3 +1,10 @@
 pub(crate) mod boot_notification;
 pub(crate) mod heartbeat;
+pub(crate) mod log_status_notification;
+pub(crate) mod meter_values;
+pub(crate) mod security_event_notification;
+pub(crate) mod sign_certificate;
+pub(crate) mod signed_firmware_status_notification;
+pub(crate) mod start_transaction;
 pub(crate) mod status_notification;
+pub(crate) mod stop_transaction;
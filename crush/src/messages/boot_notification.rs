@@ -4,17 +4,36 @@ use rust_ocpp::v1_6::{
     messages::boot_notification::{BootNotificationRequest, BootNotificationResponse},
     types::RegistrationStatus,
 };
+use std::future::Future;
 
-use crate::error::OcppResult;
+use crate::{
+    action::HandleAction, actions::BootNotification, context::StationContext, error::OcppResult,
+};
 
 #[async_trait]
 pub trait HandleBootNotificationRequest: Send + Sync {
     async fn handle(
         &self,
+        context: StationContext,
         request: BootNotificationRequest,
     ) -> OcppResult<BootNotificationResponse>;
 }
 
+#[async_trait]
+impl<F, Fut> HandleBootNotificationRequest for F
+where
+    F: Fn(StationContext, BootNotificationRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<BootNotificationResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: BootNotificationRequest,
+    ) -> OcppResult<BootNotificationResponse> {
+        self(context, request).await
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct DefaultBootNotificationHandler;
 
@@ -22,6 +41,7 @@ pub(crate) struct DefaultBootNotificationHandler;
 impl HandleBootNotificationRequest for DefaultBootNotificationHandler {
     async fn handle(
         &self,
+        _context: StationContext,
         _request: BootNotificationRequest,
     ) -> OcppResult<BootNotificationResponse> {
         let current_time = Utc::now();
@@ -34,3 +54,19 @@ impl HandleBootNotificationRequest for DefaultBootNotificationHandler {
         })
     }
 }
+
+/// Adapts a [`HandleBootNotificationRequest`] implementation to the action registry.
+pub(crate) struct BootNotificationHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleBootNotificationRequest> HandleAction<BootNotification>
+    for BootNotificationHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: BootNotificationRequest,
+    ) -> OcppResult<BootNotificationResponse> {
+        self.0.handle(context, request).await
+    }
+}
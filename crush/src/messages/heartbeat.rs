@@ -1,20 +1,58 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use rust_ocpp::v1_6::messages::heart_beat::{HeartbeatRequest, HeartbeatResponse};
+use std::future::Future;
 
-use crate::error::OcppResult;
+use crate::{action::HandleAction, actions::Heartbeat, context::StationContext, error::OcppResult};
 
 #[async_trait]
 pub trait HandleHeartbeatRequest: Send + Sync {
-    async fn handle(&self, request: HeartbeatRequest) -> OcppResult<HeartbeatResponse>;
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleHeartbeatRequest for F
+where
+    F: Fn(StationContext, HeartbeatRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<HeartbeatResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse> {
+        self(context, request).await
+    }
 }
 
 pub(crate) struct DefaultHeartbeatHandler;
 
 #[async_trait]
 impl HandleHeartbeatRequest for DefaultHeartbeatHandler {
-    async fn handle(&self, _request: HeartbeatRequest) -> OcppResult<HeartbeatResponse> {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        _request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse> {
         let current_time = Utc::now();
         Ok(HeartbeatResponse { current_time })
     }
 }
+
+/// Adapts a [`HandleHeartbeatRequest`] implementation to the action registry.
+pub(crate) struct HeartbeatHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleHeartbeatRequest> HandleAction<Heartbeat> for HeartbeatHandler<H> {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse> {
+        self.0.handle(context, request).await
+    }
+}
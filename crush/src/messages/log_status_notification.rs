@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use std::future::Future;
+
+use crate::{
+    action::HandleAction,
+    actions::LogStatusNotification,
+    context::StationContext,
+    error::OcppResult,
+    security::{LogStatusNotificationRequest, LogStatusNotificationResponse},
+};
+
+#[async_trait]
+pub trait HandleLogStatusNotificationRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: LogStatusNotificationRequest,
+    ) -> OcppResult<LogStatusNotificationResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleLogStatusNotificationRequest for F
+where
+    F: Fn(StationContext, LogStatusNotificationRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<LogStatusNotificationResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: LogStatusNotificationRequest,
+    ) -> OcppResult<LogStatusNotificationResponse> {
+        self(context, request).await
+    }
+}
+
+/// Acknowledges the notification, crush records the progress for every station itself.
+pub(crate) struct DefaultLogStatusNotificationHandler;
+
+#[async_trait]
+impl HandleLogStatusNotificationRequest for DefaultLogStatusNotificationHandler {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: LogStatusNotificationRequest,
+    ) -> OcppResult<LogStatusNotificationResponse> {
+        tracing::info!(
+            "Log upload {:?} of {}: {:?}",
+            request.request_id,
+            context.station_id(),
+            request.status
+        );
+        Ok(LogStatusNotificationResponse {})
+    }
+}
+
+/// Adapts a [`HandleLogStatusNotificationRequest`] implementation to the action registry.
+pub(crate) struct LogStatusNotificationHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleLogStatusNotificationRequest> HandleAction<LogStatusNotification>
+    for LogStatusNotificationHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: LogStatusNotificationRequest,
+    ) -> OcppResult<LogStatusNotificationResponse> {
+        self.0.handle(context, request).await
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use rust_ocpp::v1_6::messages::meter_values::{MeterValuesRequest, MeterValuesResponse};
+use std::future::Future;
+
+use crate::{
+    action::HandleAction, actions::MeterValues, context::StationContext, error::OcppResult,
+};
+
+#[async_trait]
+pub trait HandleMeterValuesRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: MeterValuesRequest,
+    ) -> OcppResult<MeterValuesResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleMeterValuesRequest for F
+where
+    F: Fn(StationContext, MeterValuesRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<MeterValuesResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: MeterValuesRequest,
+    ) -> OcppResult<MeterValuesResponse> {
+        self(context, request).await
+    }
+}
+
+pub(crate) struct DefaultMeterValuesHandler;
+
+#[async_trait]
+impl HandleMeterValuesRequest for DefaultMeterValuesHandler {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        _request: MeterValuesRequest,
+    ) -> OcppResult<MeterValuesResponse> {
+        Ok(MeterValuesResponse {})
+    }
+}
+
+/// Adapts a [`HandleMeterValuesRequest`] implementation to the action registry.
+pub(crate) struct MeterValuesHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleMeterValuesRequest> HandleAction<MeterValues> for MeterValuesHandler<H> {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: MeterValuesRequest,
+    ) -> OcppResult<MeterValuesResponse> {
+        self.0.handle(context, request).await
+    }
+}
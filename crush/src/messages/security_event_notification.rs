@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use std::future::Future;
+
+use crate::{
+    action::HandleAction,
+    actions::SecurityEventNotification,
+    context::StationContext,
+    error::OcppResult,
+    security::{SecurityEventNotificationRequest, SecurityEventNotificationResponse},
+};
+
+#[async_trait]
+pub trait HandleSecurityEventNotificationRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SecurityEventNotificationRequest,
+    ) -> OcppResult<SecurityEventNotificationResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleSecurityEventNotificationRequest for F
+where
+    F: Fn(StationContext, SecurityEventNotificationRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<SecurityEventNotificationResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SecurityEventNotificationRequest,
+    ) -> OcppResult<SecurityEventNotificationResponse> {
+        self(context, request).await
+    }
+}
+
+/// Logs the event, the whitepaper requires security events to be acknowledged.
+pub(crate) struct DefaultSecurityEventNotificationHandler;
+
+#[async_trait]
+impl HandleSecurityEventNotificationRequest for DefaultSecurityEventNotificationHandler {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SecurityEventNotificationRequest,
+    ) -> OcppResult<SecurityEventNotificationResponse> {
+        tracing::warn!(
+            "Security event {} on {} at {}: {}",
+            request.kind,
+            context.station_id(),
+            request.timestamp,
+            request.tech_info.as_deref().unwrap_or_default()
+        );
+        Ok(SecurityEventNotificationResponse {})
+    }
+}
+
+/// Adapts a [`HandleSecurityEventNotificationRequest`] implementation to the action registry.
+pub(crate) struct SecurityEventNotificationHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleSecurityEventNotificationRequest> HandleAction<SecurityEventNotification>
+    for SecurityEventNotificationHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SecurityEventNotificationRequest,
+    ) -> OcppResult<SecurityEventNotificationResponse> {
+        self.0.handle(context, request).await
+    }
+}
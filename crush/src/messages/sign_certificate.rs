@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use std::future::Future;
+
+use crate::{
+    action::HandleAction,
+    actions::SignCertificate,
+    context::StationContext,
+    error::OcppResult,
+    security::{GenericStatus, SignCertificateRequest, SignCertificateResponse},
+};
+
+#[async_trait]
+pub trait HandleSignCertificateRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignCertificateRequest,
+    ) -> OcppResult<SignCertificateResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleSignCertificateRequest for F
+where
+    F: Fn(StationContext, SignCertificateRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<SignCertificateResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignCertificateRequest,
+    ) -> OcppResult<SignCertificateResponse> {
+        self(context, request).await
+    }
+}
+
+/// Rejects every request, as there is no CA to forward it to.
+pub(crate) struct DefaultSignCertificateHandler;
+
+#[async_trait]
+impl HandleSignCertificateRequest for DefaultSignCertificateHandler {
+    async fn handle(
+        &self,
+        context: StationContext,
+        _request: SignCertificateRequest,
+    ) -> OcppResult<SignCertificateResponse> {
+        tracing::info!(
+            "Rejecting SignCertificate of {}, no certificate authority is configured",
+            context.station_id()
+        );
+        Ok(SignCertificateResponse {
+            status: GenericStatus::Rejected,
+        })
+    }
+}
+
+/// Adapts a [`HandleSignCertificateRequest`] implementation to the action registry.
+pub(crate) struct SignCertificateHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleSignCertificateRequest> HandleAction<SignCertificate> for SignCertificateHandler<H> {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignCertificateRequest,
+    ) -> OcppResult<SignCertificateResponse> {
+        self.0.handle(context, request).await
+    }
+}
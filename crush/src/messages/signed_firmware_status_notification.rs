@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use std::future::Future;
+
+use crate::{
+    action::HandleAction,
+    actions::SignedFirmwareStatusNotification,
+    context::StationContext,
+    error::OcppResult,
+    security::{SignedFirmwareStatusNotificationRequest, SignedFirmwareStatusNotificationResponse},
+};
+
+#[async_trait]
+pub trait HandleSignedFirmwareStatusNotificationRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignedFirmwareStatusNotificationRequest,
+    ) -> OcppResult<SignedFirmwareStatusNotificationResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleSignedFirmwareStatusNotificationRequest for F
+where
+    F: Fn(StationContext, SignedFirmwareStatusNotificationRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<SignedFirmwareStatusNotificationResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignedFirmwareStatusNotificationRequest,
+    ) -> OcppResult<SignedFirmwareStatusNotificationResponse> {
+        self(context, request).await
+    }
+}
+
+/// Acknowledges the notification, crush records the progress for every station itself.
+pub(crate) struct DefaultSignedFirmwareStatusNotificationHandler;
+
+#[async_trait]
+impl HandleSignedFirmwareStatusNotificationRequest
+    for DefaultSignedFirmwareStatusNotificationHandler
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignedFirmwareStatusNotificationRequest,
+    ) -> OcppResult<SignedFirmwareStatusNotificationResponse> {
+        tracing::info!(
+            "Firmware update {:?} of {}: {:?}",
+            request.request_id,
+            context.station_id(),
+            request.status
+        );
+        Ok(SignedFirmwareStatusNotificationResponse {})
+    }
+}
+
+/// Adapts a [`HandleSignedFirmwareStatusNotificationRequest`] implementation to the action registry.
+pub(crate) struct SignedFirmwareStatusNotificationHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleSignedFirmwareStatusNotificationRequest>
+    HandleAction<SignedFirmwareStatusNotification> for SignedFirmwareStatusNotificationHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: SignedFirmwareStatusNotificationRequest,
+    ) -> OcppResult<SignedFirmwareStatusNotificationResponse> {
+        self.0.handle(context, request).await
+    }
+}
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use rust_ocpp::v1_6::{
+    messages::start_transaction::{StartTransactionRequest, StartTransactionResponse},
+    types::{AuthorizationStatus, IdTagInfo},
+};
+use std::future::Future;
+
+use crate::{
+    action::HandleAction, actions::StartTransaction, context::StationContext, error::OcppResult,
+};
+
+/// Decides whether the id tag of a `StartTransaction` may charge.
+///
+/// Crush allocates the transaction id once the handler answered, it replaces the
+/// `transaction_id` of the response.
+#[async_trait]
+pub trait HandleStartTransactionRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StartTransactionRequest,
+    ) -> OcppResult<StartTransactionResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleStartTransactionRequest for F
+where
+    F: Fn(StationContext, StartTransactionRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<StartTransactionResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StartTransactionRequest,
+    ) -> OcppResult<StartTransactionResponse> {
+        self(context, request).await
+    }
+}
+
+/// Accepts every id tag.
+pub(crate) struct DefaultStartTransactionHandler;
+
+#[async_trait]
+impl HandleStartTransactionRequest for DefaultStartTransactionHandler {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        _request: StartTransactionRequest,
+    ) -> OcppResult<StartTransactionResponse> {
+        Ok(StartTransactionResponse {
+            id_tag_info: accepted(),
+            transaction_id: 0,
+        })
+    }
+}
+
+/// Adapts a [`HandleStartTransactionRequest`] implementation to the action registry.
+pub(crate) struct StartTransactionHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleStartTransactionRequest> HandleAction<StartTransaction>
+    for StartTransactionHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StartTransactionRequest,
+    ) -> OcppResult<StartTransactionResponse> {
+        self.0.handle(context, request).await
+    }
+}
+
+/// The answer for an id tag that may charge.
+pub(crate) fn accepted() -> IdTagInfo {
+    IdTagInfo {
+        expiry_date: None,
+        parent_id_tag: None,
+        status: AuthorizationStatus::Accepted,
+    }
+}
@@ -2,24 +2,61 @@ use async_trait::async_trait;
 use rust_ocpp::v1_6::messages::status_notification::{
     StatusNotificationRequest, StatusNotificationResponse,
 };
+use std::future::Future;
 
-use crate::error::OcppResult;
+use crate::{
+    action::HandleAction, actions::StatusNotification, context::StationContext, error::OcppResult,
+};
 
 #[async_trait]
 pub trait HandleStatusNotificationRequest: Send + Sync {
     async fn handle(
         &self,
+        context: StationContext,
         request: StatusNotificationRequest,
     ) -> OcppResult<StatusNotificationResponse>;
 }
+
+#[async_trait]
+impl<F, Fut> HandleStatusNotificationRequest for F
+where
+    F: Fn(StationContext, StatusNotificationRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<StatusNotificationResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StatusNotificationRequest,
+    ) -> OcppResult<StatusNotificationResponse> {
+        self(context, request).await
+    }
+}
+
 pub(crate) struct DefaultStatusNotificationHandler;
 
 #[async_trait]
 impl HandleStatusNotificationRequest for DefaultStatusNotificationHandler {
     async fn handle(
         &self,
+        _context: StationContext,
         _request: StatusNotificationRequest,
     ) -> OcppResult<StatusNotificationResponse> {
         Ok(StatusNotificationResponse {})
     }
 }
+
+/// Adapts a [`HandleStatusNotificationRequest`] implementation to the action registry.
+pub(crate) struct StatusNotificationHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleStatusNotificationRequest> HandleAction<StatusNotification>
+    for StatusNotificationHandler<H>
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StatusNotificationRequest,
+    ) -> OcppResult<StatusNotificationResponse> {
+        self.0.handle(context, request).await
+    }
+}
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use rust_ocpp::v1_6::messages::stop_transaction::{
+    StopTransactionRequest, StopTransactionResponse,
+};
+use std::future::Future;
+
+use crate::{
+    action::HandleAction, actions::StopTransaction, context::StationContext, error::OcppResult,
+    messages::start_transaction::accepted,
+};
+
+#[async_trait]
+pub trait HandleStopTransactionRequest: Send + Sync {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StopTransactionRequest,
+    ) -> OcppResult<StopTransactionResponse>;
+}
+
+#[async_trait]
+impl<F, Fut> HandleStopTransactionRequest for F
+where
+    F: Fn(StationContext, StopTransactionRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = OcppResult<StopTransactionResponse>> + Send,
+{
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StopTransactionRequest,
+    ) -> OcppResult<StopTransactionResponse> {
+        self(context, request).await
+    }
+}
+
+/// Accepts the id tag that stopped the transaction, if there is one.
+pub(crate) struct DefaultStopTransactionHandler;
+
+#[async_trait]
+impl HandleStopTransactionRequest for DefaultStopTransactionHandler {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        request: StopTransactionRequest,
+    ) -> OcppResult<StopTransactionResponse> {
+        Ok(StopTransactionResponse {
+            id_tag_info: request.id_tag.map(|_| accepted()),
+        })
+    }
+}
+
+/// Adapts a [`HandleStopTransactionRequest`] implementation to the action registry.
+pub(crate) struct StopTransactionHandler<H>(pub(crate) H);
+
+#[async_trait]
+impl<H: HandleStopTransactionRequest> HandleAction<StopTransaction> for StopTransactionHandler<H> {
+    async fn handle(
+        &self,
+        context: StationContext,
+        request: StopTransactionRequest,
+    ) -> OcppResult<StopTransactionResponse> {
+        self.0.handle(context, request).await
+    }
+}
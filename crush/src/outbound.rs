@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    config::RegistrationPolicy,
+    error::CallError,
+    events::{Direction, EventKind},
+    registration,
+    serde::OcppResponse,
+    session::Session,
+};
+
+/// A CALL crush sends to a station, waiting for the station's CALLRESULT or CALLERROR.
+pub(crate) struct OutboundCall {
+    pub(crate) action: &'static str,
+    pub(crate) payload: Value,
+    pub(crate) reply: oneshot::Sender<Result<Value, CallError>>,
+}
+
+type Reply = oneshot::Sender<Result<Value, CallError>>;
+
+/// A CALL crush sent to a station that was waiting for its answer, as saved to a
+/// [`crate::Storage`].
+///
+/// See [`crate::CrushHandle::interrupted_calls`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingCall {
+    station_id: String,
+    unique_id: String,
+    action: String,
+    payload: Value,
+    sent_at: DateTime<Utc>,
+}
+
+impl PendingCall {
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    #[must_use]
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    #[must_use]
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    #[must_use]
+    pub fn payload(&self) -> &Value {
+        &self.payload
+    }
+
+    #[must_use]
+    pub fn sent_at(&self) -> DateTime<Utc> {
+        self.sent_at
+    }
+}
+
+/// The CALLs of a session that have been sent but not answered yet, by unique id.
+pub(crate) struct PendingCalls {
+    session: Arc<Session>,
+    registration_policy: RegistrationPolicy,
+    next_id: Mutex<u64>,
+    calls: Mutex<HashMap<String, (&'static str, Reply)>>,
+}
+
+impl PendingCalls {
+    pub(crate) fn new(session: Arc<Session>, registration_policy: RegistrationPolicy) -> Self {
+        Self {
+            session,
+            registration_policy,
+            next_id: Mutex::default(),
+            calls: Mutex::default(),
+        }
+    }
+
+    /// Registers `call` and returns the CALL frame to send for it.
+    ///
+    /// Calls the registration status of the station does not permit are failed right away,
+    /// see [`RegistrationPolicy::Reject`].
+    pub(crate) fn register(&self, call: OutboundCall) -> Option<String> {
+        if self.registration_policy == RegistrationPolicy::Reject {
+            let status = self.session.registration_status();
+            if !registration::permits_call(status.as_ref(), call.action) {
+                drop(call.reply.send(Err(CallError::Unregistered { status })));
+                return None;
+            }
+        }
+        if let Some(triggered) = registration::triggered_action(call.action, &call.payload) {
+            self.session.triggered(triggered);
+        }
+
+        let unique_id = {
+            let mut next_id = self.next_id.lock().unwrap_or_else(PoisonError::into_inner);
+            *next_id += 1;
+            next_id.to_string()
+        };
+
+        let frame = match serde_json::to_string(&(2, &unique_id, call.action, &call.payload)) {
+            Ok(frame) => frame,
+            Err(error) => {
+                drop(
+                    call.reply
+                        .send(Err(CallError::Serialize(error.to_string()))),
+                );
+                return None;
+            }
+        };
+
+        self.session.states().call_sent(|| PendingCall {
+            station_id: self.session.station_id().to_owned(),
+            unique_id: unique_id.clone(),
+            action: call.action.to_owned(),
+            payload: call.payload.clone(),
+            sent_at: Utc::now(),
+        });
+        self.session.emit(|| EventKind::CallSent {
+            unique_id: unique_id.clone(),
+            action: call.action.to_owned(),
+            payload: call.payload,
+        });
+
+        let mut calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+        // Callers that gave up waiting, e.g. after a timeout, leave their entry behind.
+        calls.retain(|abandoned_id, (_, reply)| {
+            let waiting = !reply.is_closed();
+            if !waiting {
+                self.call_done(abandoned_id);
+            }
+            waiting
+        });
+        calls.insert(unique_id, (call.action, call.reply));
+        Some(frame)
+    }
+
+    /// Hands `response` to the caller waiting for it.
+    pub(crate) fn resolve(&self, response: OcppResponse) {
+        let unique_id = match &response {
+            OcppResponse::CallResult { uuid, .. } | OcppResponse::CallError { uuid, .. } => uuid,
+        };
+        let pending = self
+            .calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(unique_id);
+        let Some((action, reply)) = pending else {
+            tracing::warn!("Received a response to unknown CALL {unique_id}");
+            return;
+        };
+        self.call_done(unique_id);
+
+        let result = match response {
+            OcppResponse::CallResult { uuid, payload } => {
+                self.session.emit(|| EventKind::CallResult {
+                    unique_id: uuid,
+                    action: action.to_owned(),
+                    payload: payload.clone(),
+                });
+                Ok(payload)
+            }
+            OcppResponse::CallError {
+                uuid,
+                error_code,
+                error_description,
+                error_details,
+            } => {
+                self.session.emit(|| EventKind::Error {
+                    direction: Direction::Received,
+                    unique_id: uuid,
+                    action: action.to_owned(),
+                    error_code: error_code.clone(),
+                    error_description: error_description.clone(),
+                    error_details: error_details.clone(),
+                });
+                Err(CallError::Failed {
+                    code: error_code,
+                    description: error_description,
+                    details: error_details,
+                })
+            }
+        };
+        drop(reply.send(result));
+    }
+
+    fn call_done(&self, unique_id: &str) {
+        self.session
+            .states()
+            .call_done(self.session.station_id(), unique_id);
+    }
+}
+
+impl Drop for PendingCalls {
+    /// Once the session is gone nobody waits for the answers of the calls left, their callers
+    /// are told the session ended.
+    fn drop(&mut self) {
+        let calls = self.calls.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for unique_id in calls.keys() {
+            self.session
+                .states()
+                .call_done(self.session.station_id(), unique_id);
+        }
+    }
+}
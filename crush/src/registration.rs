@@ -0,0 +1,74 @@
+use rust_ocpp::v1_6::{
+    messages::trigger_message::TriggerMessageRequest,
+    types::{MessageTrigger, RegistrationStatus},
+};
+use serde_json::Value;
+
+use crate::{
+    action::Action,
+    actions::{
+        BootNotification, ChangeConfiguration, DiagnosticsStatusNotification,
+        ExtendedTriggerMessage, FirmwareStatusNotification, GetConfiguration, Heartbeat,
+        LogStatusNotification, MeterValues, SignCertificate, SignedFirmwareStatusNotification,
+        StatusNotification, TriggerMessage,
+    },
+    security::{ExtendedMessageTrigger, ExtendedTriggerMessageRequest},
+};
+
+/// The CALLs crush may send to a station that is not accepted yet, they retrieve information
+/// or change the configuration.
+const CONFIGURATION_ACTIONS: [&str; 4] = [
+    ChangeConfiguration::NAME,
+    GetConfiguration::NAME,
+    TriggerMessage::NAME,
+    ExtendedTriggerMessage::NAME,
+];
+
+/// Whether a station whose last boot got `status` may send a CALL for `action`.
+///
+/// A station crush has not seen boot is not known to be unregistered: charge points only boot
+/// after a restart, not when they reconnect, so it is let through until it boots.
+pub(crate) fn accepts_call(status: Option<&RegistrationStatus>, action: &str) -> bool {
+    action == BootNotification::NAME || matches!(status, Some(&RegistrationStatus::Accepted) | None)
+}
+
+/// Whether crush may send a CALL for `action` to a station whose last boot got `status`.
+pub(crate) fn permits_call(status: Option<&RegistrationStatus>, action: &str) -> bool {
+    match status {
+        Some(&RegistrationStatus::Accepted) | None => true,
+        Some(&RegistrationStatus::Pending) => CONFIGURATION_ACTIONS.contains(&action),
+        Some(&RegistrationStatus::Rejected) => false,
+    }
+}
+
+/// The CALL a station answers the `TriggerMessage` or `ExtendedTriggerMessage` with `payload`
+/// with, which it may send whatever its registration status.
+pub(crate) fn triggered_action(action: &str, payload: &Value) -> Option<&'static str> {
+    if action == TriggerMessage::NAME {
+        let request = serde_json::from_value::<TriggerMessageRequest>(payload.clone()).ok()?;
+        return Some(match request.requested_message {
+            MessageTrigger::BootNotification => BootNotification::NAME,
+            MessageTrigger::DiagnosticsStatusNotification => DiagnosticsStatusNotification::NAME,
+            MessageTrigger::FirmwareStatusNotification => FirmwareStatusNotification::NAME,
+            MessageTrigger::Heartbeat => Heartbeat::NAME,
+            MessageTrigger::MeterValues => MeterValues::NAME,
+            MessageTrigger::StatusNotification => StatusNotification::NAME,
+        });
+    }
+    if action == ExtendedTriggerMessage::NAME {
+        let request =
+            serde_json::from_value::<ExtendedTriggerMessageRequest>(payload.clone()).ok()?;
+        return Some(match request.requested_message {
+            ExtendedMessageTrigger::BootNotification => BootNotification::NAME,
+            ExtendedMessageTrigger::LogStatusNotification => LogStatusNotification::NAME,
+            ExtendedMessageTrigger::FirmwareStatusNotification => {
+                SignedFirmwareStatusNotification::NAME
+            }
+            ExtendedMessageTrigger::Heartbeat => Heartbeat::NAME,
+            ExtendedMessageTrigger::MeterValues => MeterValues::NAME,
+            ExtendedMessageTrigger::SignChargePointCertificate => SignCertificate::NAME,
+            ExtendedMessageTrigger::StatusNotification => StatusNotification::NAME,
+        });
+    }
+    None
+}
@@ -0,0 +1,386 @@
+//! Messages of the OCPP 1.6 security extension, which `rust_ocpp` does not cover.
+//!
+//! The types follow the "Improved security for OCPP 1.6-J" whitepaper and serialize to the
+//! JSON the whitepaper's schemas describe. Their [`Action`](crate::Action) markers live in
+//! [`crate::actions`] next to those of the core profile.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// `SecurityEventNotification.req`, a security related event that happened on the station.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityEventNotificationRequest {
+    /// The type of the event, e.g. `FirmwareUpdated` or `InvalidCentralSystemCertificate`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// When the event happened.
+    pub timestamp: DateTime<Utc>,
+    /// Additional technical information about the event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tech_info: Option<String>,
+}
+
+/// `SecurityEventNotification.conf`, which carries no fields.
+#[allow(
+    clippy::empty_structs_with_brackets,
+    reason = "a unit struct would serialize to null instead of an empty JSON object"
+)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SecurityEventNotificationResponse {}
+
+/// `SignCertificate.req`, asking the central system to have a new station certificate signed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignCertificateRequest {
+    /// The PEM encoded PKCS#10 certificate signing request.
+    pub csr: String,
+}
+
+/// `SignCertificate.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignCertificateResponse {
+    /// Whether the request will be forwarded to a CA, which answers with `CertificateSigned`.
+    pub status: GenericStatus,
+}
+
+/// `GenericStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenericStatus {
+    Accepted,
+    Rejected,
+}
+
+/// `CertificateSigned.req`, delivering the signed station certificate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSignedRequest {
+    /// The PEM encoded certificate chain, starting with the station certificate.
+    pub certificate_chain: String,
+}
+
+/// `CertificateSigned.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSignedResponse {
+    pub status: CertificateSignedStatus,
+}
+
+/// `CertificateSignedStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateSignedStatus {
+    Accepted,
+    Rejected,
+}
+
+/// `InstallCertificate.req`, installing a root certificate on the station.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallCertificateRequest {
+    pub certificate_type: CertificateUse,
+    /// The PEM encoded certificate.
+    pub certificate: String,
+}
+
+/// `InstallCertificate.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallCertificateResponse {
+    pub status: CertificateStatus,
+}
+
+/// `CertificateUseEnumType`, the kinds of root certificates a station keeps.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateUse {
+    /// Verifies the certificate of the central system.
+    CentralSystemRootCertificate,
+    /// Verifies the signatures of firmware images.
+    ManufacturerRootCertificate,
+}
+
+/// `CertificateStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateStatus {
+    Accepted,
+    Rejected,
+    Failed,
+}
+
+/// `DeleteCertificate.req`, removing an installed root certificate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteCertificateRequest {
+    pub certificate_hash_data: CertificateHashData,
+}
+
+/// `DeleteCertificate.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteCertificateResponse {
+    pub status: DeleteCertificateStatus,
+}
+
+/// `DeleteCertificateStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteCertificateStatus {
+    Accepted,
+    Failed,
+    NotFound,
+}
+
+/// `CertificateHashDataType`, identifying a certificate by its issuer and serial number.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateHashData {
+    pub hash_algorithm: HashAlgorithm,
+    /// The hex encoded hash of the issuer's distinguished name.
+    pub issuer_name_hash: String,
+    /// The hex encoded hash of the issuer's public key.
+    pub issuer_key_hash: String,
+    /// The hex encoded serial number of the certificate.
+    pub serial_number: String,
+}
+
+/// `HashAlgorithmEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[serde(rename = "SHA256")]
+    Sha256,
+    #[serde(rename = "SHA384")]
+    Sha384,
+    #[serde(rename = "SHA512")]
+    Sha512,
+}
+
+/// `GetInstalledCertificateIds.req`, listing the root certificates of one kind.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetInstalledCertificateIdsRequest {
+    pub certificate_type: CertificateUse,
+}
+
+/// `GetInstalledCertificateIds.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetInstalledCertificateIdsResponse {
+    pub status: GetInstalledCertificateStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate_hash_data: Option<Vec<CertificateHashData>>,
+}
+
+/// `GetInstalledCertificateStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetInstalledCertificateStatus {
+    Accepted,
+    NotFound,
+}
+
+/// `ExtendedTriggerMessage.req`, the `TriggerMessage` that also covers the extension's messages.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedTriggerMessageRequest {
+    pub requested_message: ExtendedMessageTrigger,
+    /// Only set when the request applies to a specific connector.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connector_id: Option<u32>,
+}
+
+/// `ExtendedTriggerMessage.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedTriggerMessageResponse {
+    pub status: ExtendedTriggerMessageStatus,
+}
+
+/// `MessageTriggerEnumType` of the security extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedMessageTrigger {
+    BootNotification,
+    LogStatusNotification,
+    FirmwareStatusNotification,
+    Heartbeat,
+    MeterValues,
+    /// Makes the station send a `SignCertificate` request for a new certificate.
+    SignChargePointCertificate,
+    StatusNotification,
+}
+
+/// `TriggerMessageStatusEnumType` of the security extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedTriggerMessageStatus {
+    Accepted,
+    Rejected,
+    NotImplemented,
+}
+
+/// `SignedUpdateFirmware.req`, asking the station to install a signed firmware image.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedUpdateFirmwareRequest {
+    /// How often the station retries downloading the firmware.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Seconds between download attempts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_interval: Option<u32>,
+    /// Identifies the update in the station's `SignedFirmwareStatusNotification`s.
+    pub request_id: i32,
+    pub firmware: Firmware,
+}
+
+/// `FirmwareType`, where to get a firmware image and how to verify it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Firmware {
+    /// The URI the firmware is downloaded from.
+    pub location: String,
+    /// When the station should start downloading.
+    pub retrieve_date_time: DateTime<Utc>,
+    /// When the station should install the firmware, right after downloading if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_date_time: Option<DateTime<Utc>>,
+    /// The PEM encoded certificate the firmware was signed with.
+    pub signing_certificate: String,
+    /// The base64 encoded signature of the firmware image.
+    pub signature: String,
+}
+
+/// `SignedUpdateFirmware.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedUpdateFirmwareResponse {
+    pub status: UpdateFirmwareStatus,
+}
+
+/// `UpdateFirmwareStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateFirmwareStatus {
+    Accepted,
+    Rejected,
+    /// Accepted, cancelling an update that was still in progress.
+    AcceptedCanceled,
+    InvalidCertificate,
+    RevokedCertificate,
+}
+
+/// `SignedFirmwareStatusNotification.req`, the progress of a signed firmware update.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedFirmwareStatusNotificationRequest {
+    pub status: FirmwareStatus,
+    /// The id of the `SignedUpdateFirmware` request, not set for `Idle`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<i32>,
+}
+
+/// `SignedFirmwareStatusNotification.conf`, which carries no fields.
+#[allow(
+    clippy::empty_structs_with_brackets,
+    reason = "a unit struct would serialize to null instead of an empty JSON object"
+)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SignedFirmwareStatusNotificationResponse {}
+
+/// `FirmwareStatusEnumType` of the security extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareStatus {
+    Downloaded,
+    DownloadFailed,
+    Downloading,
+    DownloadScheduled,
+    DownloadPaused,
+    Idle,
+    InstallationFailed,
+    Installing,
+    Installed,
+    InstallRebooting,
+    InstallScheduled,
+    InstallVerificationFailed,
+    InvalidSignature,
+    SignatureVerified,
+}
+
+/// `GetLog.req`, asking the station to upload one of its logs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogRequest {
+    pub log: LogParameters,
+    pub log_type: LogType,
+    /// Identifies the upload in the station's `LogStatusNotification`s.
+    pub request_id: i32,
+    /// How often the station retries uploading the log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Seconds between upload attempts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_interval: Option<u32>,
+}
+
+/// `LogParametersType`, where to upload a log and which part of it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogParameters {
+    /// The URL the log is uploaded to.
+    pub remote_location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest_timestamp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_timestamp: Option<DateTime<Utc>>,
+}
+
+/// `LogEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogType {
+    DiagnosticsLog,
+    SecurityLog,
+}
+
+/// `GetLog.conf`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLogResponse {
+    pub status: LogStatus,
+    /// The name of the file the log is uploaded as.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+/// `LogStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStatus {
+    Accepted,
+    Rejected,
+    /// Accepted, cancelling an upload that was still in progress.
+    AcceptedCanceled,
+}
+
+/// `LogStatusNotification.req`, the progress of a log upload.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogStatusNotificationRequest {
+    pub status: UploadLogStatus,
+    /// The id of the `GetLog` request, not set for `Idle`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<i32>,
+}
+
+/// `LogStatusNotification.conf`, which carries no fields.
+#[allow(
+    clippy::empty_structs_with_brackets,
+    reason = "a unit struct would serialize to null instead of an empty JSON object"
+)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct LogStatusNotificationResponse {}
+
+/// `UploadLogStatusEnumType`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadLogStatus {
+    BadMessage,
+    Idle,
+    NotSupportedOperation,
+    PermissionDenied,
+    Uploaded,
+    UploadFailure,
+    Uploading,
+}
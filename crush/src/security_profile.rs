@@ -0,0 +1,148 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use rust_ocpp::v1_6::{
+    messages::change_configuration::ChangeConfigurationRequest, types::ConfigurationStatus,
+};
+use std::{fmt::Write, time::Duration};
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::timeout,
+};
+
+use crate::{
+    actions::ChangeConfiguration,
+    credentials::CredentialStore,
+    error::SecurityProfileError,
+    events::{Event, EventKind},
+    CrushHandle,
+};
+
+/// The length of generated `AuthorizationKey`s, the maximum the whitepaper allows.
+const AUTHORIZATION_KEY_LEN: usize = 20;
+
+/// The OCPP security profiles a station can be moved to by rotating its `AuthorizationKey`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecurityProfile {
+    /// Security Profile 1, HTTP Basic authentication over an unencrypted connection.
+    BasicAuthentication,
+    /// Security Profile 2, HTTP Basic authentication over TLS.
+    TlsWithBasicAuthentication,
+}
+
+impl SecurityProfile {
+    /// The value of the `SecurityProfile` configuration key.
+    fn value(self) -> u8 {
+        match self {
+            Self::BasicAuthentication => 1,
+            Self::TlsWithBasicAuthentication => 2,
+        }
+    }
+}
+
+/// Moves `station_id` to `profile` with a new `AuthorizationKey`, see
+/// [`CrushHandle::upgrade_security_profile`].
+pub(crate) async fn upgrade(
+    crush: &CrushHandle,
+    credentials: &dyn CredentialStore,
+    station_id: &str,
+    profile: SecurityProfile,
+    reconnect_timeout: Duration,
+) -> Result<(), SecurityProfileError> {
+    if profile == SecurityProfile::TlsWithBasicAuthentication && crush.tls.is_none() {
+        return Err(SecurityProfileError::TlsNotConfigured);
+    }
+
+    // The station may reconnect with the new key as soon as it accepted it, so the key is
+    // accepted alongside the current credential and the reconnect is watched for before.
+    let events = crush.events();
+    let password = authorization_key();
+    if !credentials.rotate_password(station_id, &password).await {
+        return Err(SecurityProfileError::ReadOnlyStore);
+    }
+    let changed = async {
+        change_configuration(crush, station_id, "AuthorizationKey", hex(&password)).await?;
+        tracing::info!("{station_id} accepted a new AuthorizationKey");
+        change_configuration(
+            crush,
+            station_id,
+            "SecurityProfile",
+            profile.value().to_string(),
+        )
+        .await
+    };
+    if let Err(error) = changed.await {
+        credentials.revert_password(station_id).await;
+        return Err(error);
+    }
+
+    if timeout(reconnect_timeout, reconnected(events, station_id))
+        .await
+        .ok()
+        != Some(true)
+    {
+        credentials.revert_password(station_id).await;
+        tracing::warn!(
+            "{station_id} did not reconnect with its new AuthorizationKey, restored its previous credentials"
+        );
+        return Err(SecurityProfileError::ReconnectTimeout);
+    }
+    credentials.commit_password(station_id).await;
+    tracing::info!(
+        "{station_id} reconnected with Security Profile {}",
+        profile.value()
+    );
+    Ok(())
+}
+
+async fn change_configuration(
+    crush: &CrushHandle,
+    station_id: &str,
+    key: &'static str,
+    value: String,
+) -> Result<(), SecurityProfileError> {
+    let request = ChangeConfigurationRequest {
+        key: key.to_owned(),
+        value,
+    };
+    let response = crush
+        .call::<ChangeConfiguration>(station_id, request)
+        .await
+        .map_err(|error| SecurityProfileError::Call { key, error })?;
+    match response.status {
+        ConfigurationStatus::Accepted => Ok(()),
+        status => Err(SecurityProfileError::NotAccepted { key, status }),
+    }
+}
+
+/// Waits for the next session of `station_id`, returning `false` if crush shut down.
+async fn reconnected(mut events: Receiver<Event>, station_id: &str) -> bool {
+    loop {
+        match events.recv().await {
+            Ok(event)
+                if event.station_id() == station_id
+                    && matches!(event.kind(), EventKind::Connected { .. }) =>
+            {
+                return true;
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return false,
+        }
+    }
+}
+
+fn authorization_key() -> [u8; AUTHORIZATION_KEY_LEN] {
+    let mut key = [0; AUTHORIZATION_KEY_LEN];
+    SystemRandom::new()
+        .fill(&mut key)
+        .expect("the system random number generator failed");
+    key
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            write!(hex, "{byte:02x}").expect("writing to a String cannot fail");
+            hex
+        })
+}
@@ -1,22 +1,11 @@
-use rust_ocpp::v1_6::messages::{
-    boot_notification::{BootNotificationRequest, BootNotificationResponse},
-    heart_beat::{HeartbeatRequest, HeartbeatResponse},
-    status_notification::{StatusNotificationRequest, StatusNotificationResponse},
-};
 use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
-use serde_json::{Result as JsonResult, Serializer as JsonSerializer, Value};
+use serde_json::{Map, Result as JsonResult, Serializer as JsonSerializer, Value};
 
 use crate::OcppResponseError;
 pub(crate) struct OcppRequest {
-    pub payload: OcppRequestMessage,
     pub uuid: String,
-}
-
-#[derive(Debug)]
-pub(crate) enum OcppRequestMessage {
-    StatusNotification(StatusNotificationRequest),
-    BootNotification(BootNotificationRequest),
-    Heartbeat(HeartbeatRequest),
+    pub action: String,
+    pub payload: Value,
 }
 
 impl<'de> Deserialize<'de> for OcppRequest {
@@ -57,70 +46,25 @@ impl<'de> Deserialize<'de> for OcppRequest {
             })
         })?;
 
-        let payload = match message_type {
-            "BootNotification" => {
-                let payload: BootNotificationRequest =
-                    serde_json::from_value(value.get(3).cloned().ok_or_else(|| {
-                        D::Error::custom::<OcppResponseError>(
-                            OcppResponseError::InvalidRequestFormat {
-                                details: Value::String(
-                                    "Missing payload for BootNotification.".to_owned(),
-                                ),
-                            },
-                        )
-                    })?)
-                    .map_err(|error| {
-                        D::Error::custom::<OcppResponseError>(
-                            OcppResponseError::InvalidRequestFormat {
-                                details: Value::String(format!(
-                                    "Failed to deserialize BootNotificationRequest: {error}"
-                                )),
-                            },
-                        )
-                    })?;
-                OcppRequestMessage::BootNotification(payload)
-            }
-            "Heartbeat" => OcppRequestMessage::Heartbeat(HeartbeatRequest {}),
-            "StatusNotification" => {
-                let payload: StatusNotificationRequest =
-                    serde_json::from_value(value.get(3).cloned().ok_or_else(|| {
-                        D::Error::custom::<OcppResponseError>(
-                            OcppResponseError::InvalidRequestFormat {
-                                details: Value::String(
-                                    "Missing payload for StatusNotification.".to_owned(),
-                                ),
-                            },
-                        )
-                    })?)
-                    .map_err(|error| {
-                        D::Error::custom::<OcppResponseError>(
-                            OcppResponseError::InvalidRequestFormat {
-                                details: Value::String(format!(
-                                    "Failed to deserialize StatusNotificationRequest: {error}"
-                                )),
-                            },
-                        )
-                    })?;
-                OcppRequestMessage::StatusNotification(payload)
-            }
-            _ => {
-                return Err(D::Error::custom::<OcppResponseError>(
-                    OcppResponseError::UnsupportedMessageType {
-                        details: Value::String(format!("Unknown message type: '{message_type}'.")),
-                    },
-                ));
-            }
-        };
+        let action = message_type.to_owned();
+
+        // Stations leave out the payload of actions without fields, such as `Heartbeat`.
+        let payload = value
+            .get(3)
+            .cloned()
+            .unwrap_or_else(|| Value::Object(Map::new()));
 
-        Ok(OcppRequest { payload, uuid })
+        Ok(OcppRequest {
+            uuid,
+            action,
+            payload,
+        })
     }
 }
 
 #[derive(Debug, Serialize)]
 pub(crate) enum OcppResponseMessage {
-    StatusNotification(StatusNotificationResponse),
-    BootNotification(BootNotificationResponse),
-    Heartbeat(HeartbeatResponse),
+    CallResult(Value),
     CallError {
         error_code: String,
         error_description: String,
@@ -137,27 +81,10 @@ impl OcppResponseMessage {
         let mut json_serializer = JsonSerializer::new(Vec::new());
 
         match self {
-            OcppResponseMessage::BootNotification(payload) => {
-                let mut state = json_serializer.serialize_seq(Some(4))?;
-                state.serialize_element(&message_type_id)?;
-                state.serialize_element(uuid)?;
-                state.serialize_element(&"BootNotification")?;
-                state.serialize_element(payload)?;
-                state.end()?;
-            }
-            OcppResponseMessage::Heartbeat(payload) => {
+            OcppResponseMessage::CallResult(payload) => {
                 let mut state = json_serializer.serialize_seq(Some(3))?;
                 state.serialize_element(&message_type_id)?;
                 state.serialize_element(uuid)?;
-                state.serialize_element("Heartbeat")?;
-                state.serialize_element(payload)?;
-                state.end()?;
-            }
-            OcppResponseMessage::StatusNotification(payload) => {
-                let mut state = json_serializer.serialize_seq(Some(3))?;
-                state.serialize_element(&message_type_id)?;
-                state.serialize_element(uuid)?;
-                state.serialize_element("StatusNotification")?;
                 state.serialize_element(payload)?;
                 state.end()?;
             }
@@ -182,3 +109,72 @@ impl OcppResponseMessage {
         Ok(json_string)
     }
 }
+
+/// A CALLRESULT or CALLERROR answering a CALL crush sent to a station.
+pub(crate) enum OcppResponse {
+    CallResult {
+        uuid: String,
+        payload: Value,
+    },
+    CallError {
+        uuid: String,
+        error_code: String,
+        error_description: String,
+        error_details: Value,
+    },
+}
+
+impl OcppResponse {
+    /// Parses `frame` if it is a CALLRESULT or CALLERROR, leaving CALLs to [`OcppRequest`].
+    pub(crate) fn parse(frame: &str) -> Option<JsonResult<Self>> {
+        let message_type_id = frame
+            .trim_start()
+            .strip_prefix('[')?
+            .trim_start()
+            .chars()
+            .next()?;
+        if message_type_id != '3' && message_type_id != '4' {
+            return None;
+        }
+        Some(serde_json::from_str(frame))
+    }
+}
+
+impl<'de> Deserialize<'de> for OcppResponse {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: Vec<Value> = Deserialize::deserialize(deserializer)?;
+
+        let uuid = value
+            .get(1)
+            .and_then(Value::as_str)
+            .ok_or_else(|| D::Error::custom("Invalid UUID: expected a string but found none."))?
+            .to_owned();
+
+        match value.first().and_then(Value::as_u64) {
+            Some(3) => Ok(Self::CallResult {
+                uuid,
+                payload: value.get(2).cloned().unwrap_or_default(),
+            }),
+            Some(4) => Ok(Self::CallError {
+                uuid,
+                error_code: value
+                    .get(2)
+                    .and_then(Value::as_str)
+                    .unwrap_or("GenericError")
+                    .to_owned(),
+                error_description: value
+                    .get(3)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+                error_details: value.get(4).cloned().unwrap_or_default(),
+            }),
+            _ => Err(D::Error::custom(
+                "Invalid message type id: expected 3 or 4.",
+            )),
+        }
+    }
+}
@@ -8,104 +8,199 @@ use std::{
 
 use tokio::{
     sync::{
-        mpsc::{channel, Receiver, Sender},
-        oneshot,
+        mpsc::{channel, Sender},
+        oneshot, watch,
     },
     task::JoinHandle,
 };
 
 use crate::{
     client_loop::{ClientHandle, ToClient},
-    controller_loop::{ControllerHandle, ToController},
+    config::DuplicateConnectionPolicy,
+    disconnect::DisconnectReason,
     error::{CrushError, CrushResult},
+    session::StationInfo,
+    supervisor::{supervise, Mailbox, Supervised},
 };
 
 pub(crate) enum ToServer {
-    NewClient(ClientHandle),
-    ClientGone(usize),
-    ClientMessage(usize, String),
+    /// Registers a new session, answering whether the duplicate connection policy admits it.
+    NewClient(ClientHandle, oneshot::Sender<bool>),
+    /// Registers a session admitted before the server was restarted again.
+    Rejoin(ClientHandle),
+    ClientGone(usize, DisconnectReason),
+    ConnectedStations(oneshot::Sender<Vec<String>>),
+    Stations(oneshot::Sender<Vec<StationInfo>>),
+    Station(String, oneshot::Sender<Option<StationInfo>>),
+    /// Looks up the mailbox of the session outbound calls to a station go to.
+    Session(String, oneshot::Sender<Option<Sender<ToClient>>>),
+    /// Looks up the controller of the newest session of a station.
+    Controller(String, oneshot::Sender<Option<Supervised>>),
 }
 
 struct Server {
-    controller_handle: ControllerHandle,
-    receiver: Receiver<ToServer>,
+    policy: DuplicateConnectionPolicy,
     clients: HashMap<usize, ClientHandle>,
+    /// The live sessions of every station id, oldest first.
+    stations: HashMap<String, Vec<usize>>,
 }
 
 impl Server {
-    fn new(controller_handle: ControllerHandle, receiver: Receiver<ToServer>) -> Self {
+    fn new(policy: DuplicateConnectionPolicy) -> Self {
         Self {
-            controller_handle,
-            receiver,
+            policy,
             clients: HashMap::default(),
+            stations: HashMap::default(),
         }
     }
-    async fn handle_message(&mut self, msg: ToServer) -> CrushResult<()> {
+    fn handle_message(&mut self, msg: ToServer) {
         match msg {
-            ToServer::NewClient(client_handle) => {
-                self.clients.insert(client_handle.id, client_handle);
+            ToServer::NewClient(client_handle, sender) => {
+                let accepted = self.add_client(client_handle);
+                if sender.send(accepted).is_err() {
+                    // The session still ends and reports back once its upgrade fails.
+                    tracing::debug!("Connection went away while being registered");
+                }
+            }
+            ToServer::Rejoin(client_handle) => self.rejoin_client(client_handle),
+            ToServer::ClientGone(id, reason) => {
+                tracing::info!("Client with {id} disconnected: {reason}");
+                self.remove_client(id);
+            }
+            ToServer::ConnectedStations(sender) => {
+                let stations = self.stations.keys().cloned().collect();
+                drop(sender.send(stations));
+            }
+            ToServer::Stations(sender) => {
+                let stations = self
+                    .stations
+                    .keys()
+                    .filter_map(|station_id| self.newest_client(station_id))
+                    .map(|client_handle| client_handle.session().info())
+                    .collect();
+                drop(sender.send(stations));
             }
-            ToServer::ClientGone(id) => {
-                tracing::info!("Client with {id} disconnected");
-                drop(self.clients.remove(&id));
+            ToServer::Station(station_id, sender) => {
+                let station = self
+                    .newest_client(&station_id)
+                    .map(|client_handle| client_handle.session().info());
+                drop(sender.send(station));
             }
-            ToServer::ClientMessage(id, message) => {
-                if let Some(client_handle) = self.clients.get_mut(&id) {
-                    let (sender, receiver) = oneshot::channel();
-                    let to_controller = ToController::Message(message, sender);
-                    self.controller_handle.send(to_controller).await;
+            ToServer::Session(station_id, sender) => {
+                let session = self.newest_client(&station_id).map(ClientHandle::sender);
+                drop(sender.send(session));
+            }
+            ToServer::Controller(station_id, sender) => {
+                let controller = self
+                    .newest_client(&station_id)
+                    .map(|client_handle| client_handle.controller().clone());
+                drop(sender.send(controller));
+            }
+        }
+    }
+    /// The session of `station_id` that connected last, which outbound calls go to.
+    fn newest_client(&self, station_id: &str) -> Option<&ClientHandle> {
+        self.stations
+            .get(station_id)
+            .and_then(|ids| ids.last())
+            .and_then(|id| self.clients.get(id))
+    }
+    fn add_client(&mut self, client_handle: ClientHandle) -> bool {
+        let sessions = self
+            .stations
+            .entry(client_handle.name().to_owned())
+            .or_default();
 
-                    let response = match receiver.await {
-                        Ok(response) => response,
-                        Err(error) => {
-                            tracing::error!(
-                                "Failed to receive answer from controller_loop: {error}"
-                            );
-                            return Ok(());
+        if !sessions.is_empty() {
+            match self.policy {
+                DuplicateConnectionPolicy::Reject => {
+                    tracing::warn!(
+                        "Rejecting connection of {}, the station is already connected",
+                        client_handle.name()
+                    );
+                    return false;
+                }
+                DuplicateConnectionPolicy::Replace => {
+                    tracing::info!("Replacing the existing session of {}", client_handle.name());
+                    // The replaced sessions stay in `clients` until they report back as gone.
+                    for id in sessions.drain(..) {
+                        if let Some(replaced) = self.clients.get(&id) {
+                            replaced.close(DisconnectReason::Replaced);
                         }
-                    };
-
-                    let to_client = ToClient::Message(response);
-
-                    client_handle.send(to_client).await;
+                    }
                 }
+                DuplicateConnectionPolicy::AllowBoth => {}
             }
+        }
+
+        sessions.push(client_handle.id);
+        self.clients.insert(client_handle.id, client_handle);
+        true
+    }
+    /// Registers a session the previous server admitted, unless it is known already.
+    fn rejoin_client(&mut self, client_handle: ClientHandle) {
+        if self.clients.contains_key(&client_handle.id) {
+            return;
+        }
+        tracing::debug!(
+            "Session {} of {} rejoined",
+            client_handle.id,
+            client_handle.name()
+        );
+        let sessions = self
+            .stations
+            .entry(client_handle.name().to_owned())
+            .or_default();
+        // Ids grow with every connection, so this keeps the sessions oldest first.
+        let position = sessions.partition_point(|id| *id < client_handle.id);
+        sessions.insert(position, client_handle.id);
+        self.clients.insert(client_handle.id, client_handle);
+    }
+    fn remove_client(&mut self, id: usize) {
+        let Some(client_handle) = self.clients.remove(&id) else {
+            return;
         };
-        Ok(())
+        if let Some(sessions) = self.stations.get_mut(client_handle.name()) {
+            sessions.retain(|session| *session != id);
+            if sessions.is_empty() {
+                self.stations.remove(client_handle.name());
+            }
+        }
     }
 }
 
-async fn run_server(mut server_actor: Server) -> CrushResult<()> {
-    while let Some(msg) = server_actor.receiver.recv().await {
-        if let Err(error) = server_actor.handle_message(msg).await {
-            tracing::error!("{error}");
-        };
+/// Runs the server loop until every `ServerHandle` is gone.
+///
+/// A panic while handling a message takes the loop down, and the supervisor starts it again
+/// with no sessions registered. The sessions then rejoin, see [`ServerHandle::restarts`].
+async fn run_server(mut receiver: Mailbox<ToServer>, policy: DuplicateConnectionPolicy) {
+    let mut server_actor = Server::new(policy);
+    while let Some(msg) = receiver.recv().await {
+        server_actor.handle_message(msg);
     }
-    Ok(())
 }
 
 #[derive(Clone)]
 pub(crate) struct ServerHandle {
     sender: Sender<ToServer>,
     next_id: Arc<AtomicUsize>,
+    supervised: Supervised,
 }
 
 impl ServerHandle {
-    pub(crate) fn new(controller_handle: ControllerHandle) -> (Self, JoinHandle<()>) {
+    pub(crate) fn new(policy: DuplicateConnectionPolicy) -> (Self, JoinHandle<()>) {
         let (sender, receiver) = channel(64);
 
-        let actor = Server::new(controller_handle, receiver);
-
-        let server_join = tokio::spawn(async move {
-            if let Err(error) = run_server(actor).await {
-                tracing::error!("{error}");
-            }
-        });
+        let (supervised, server_join) =
+            supervise("Server loop".to_owned(), receiver, move |mailbox| {
+                run_server(mailbox, policy)
+            });
 
         (
             Self {
                 sender,
                 next_id: Arc::default(),
+                supervised,
             },
             server_join,
         )
@@ -113,10 +208,42 @@ impl ServerHandle {
     pub(crate) fn next_id(&self) -> usize {
         self.next_id.fetch_add(1, Ordering::Relaxed)
     }
-    pub(crate) async fn send(&mut self, msg: ToServer) {
-        assert!(
-            self.sender.send(msg).await.is_ok(),
-            "Server loop has shut down"
-        );
+    /// Changes every time the server loop was restarted and forgot the sessions it knew.
+    pub(crate) fn restarts(&self) -> watch::Receiver<usize> {
+        self.supervised.restarts()
+    }
+    /// Aborts the task of the server loop, as if it crashed.
+    #[cfg_attr(
+        not(feature = "test-util"),
+        expect(dead_code, reason = "only used by the test-util hooks")
+    )]
+    pub(crate) fn abort(&self) {
+        self.supervised.abort();
+    }
+    /// The mailbox of the session outbound calls to `station_id` go to, if it is connected.
+    pub(crate) async fn session(&self, station_id: &str) -> Option<Sender<ToClient>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(ToServer::Session(station_id.to_owned(), sender))
+            .await
+            .ok()?;
+        receiver.await.ok().flatten()
+    }
+    /// The controller of the newest session of `station_id`, if it is connected.
+    #[cfg_attr(
+        not(feature = "test-util"),
+        expect(dead_code, reason = "only used by the test-util hooks")
+    )]
+    pub(crate) async fn controller(&self, station_id: &str) -> Option<Supervised> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(ToServer::Controller(station_id.to_owned(), sender))
+            .await
+            .ok()?;
+        receiver.await.ok().flatten()
+    }
+    pub(crate) async fn send(&self, msg: ToServer) -> CrushResult<()> {
+        if self.sender.send(msg).await.is_err() {
+            return Err(CrushError::ServerLoopClosed);
+        }
+        Ok(())
     }
 }
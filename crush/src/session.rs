@@ -0,0 +1,471 @@
+use chrono::{DateTime, Utc};
+use hyper::http::Extensions;
+use rust_ocpp::v1_6::{
+    messages::{
+        boot_notification::{BootNotificationRequest, BootNotificationResponse},
+        meter_values::MeterValuesRequest,
+        start_transaction::StartTransactionRequest,
+        status_notification::StatusNotificationRequest,
+        stop_transaction::StopTransactionRequest,
+    },
+    types::RegistrationStatus,
+};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use tokio::sync::watch;
+
+use crate::{
+    certificate::ClientCertificate,
+    connectors::{ConnectorStatus, Update},
+    context::StationContext,
+    events::{EventKind, Events},
+    security::{LogStatusNotificationRequest, SignedFirmwareStatusNotificationRequest},
+    station_state::{FirmwareUpdate, LogUpload, StationStates},
+    storage_loop::Written,
+    transactions::Transaction,
+};
+
+/// A started or stopped transaction, which is saved before the station is answered and
+/// published after.
+pub(crate) enum TransactionChange {
+    Started {
+        transaction: Transaction,
+        orphaned: Vec<Transaction>,
+    },
+    Stopped(Transaction),
+}
+
+/// A snapshot of what crush knows about a connected station.
+///
+/// Returned by [`crate::CrushHandle::stations`] and [`crate::CrushHandle::station`]; it does
+/// not change when the session does, query again for fresh data.
+#[derive(Debug, Clone)]
+pub struct StationInfo {
+    station_id: String,
+    address: SocketAddr,
+    protocol: Option<String>,
+    connected_at: DateTime<Utc>,
+    last_message_at: Option<DateTime<Utc>>,
+    boot_notification: Option<BootNotificationRequest>,
+    registration_status: Option<RegistrationStatus>,
+    heartbeat_interval: Option<Duration>,
+    unresponsive_since: Option<DateTime<Utc>>,
+    firmware_update: Option<FirmwareUpdate>,
+    log_upload: Option<LogUpload>,
+    client_certificate: Option<Arc<ClientCertificate>>,
+    metadata: Arc<Extensions>,
+}
+
+impl StationInfo {
+    /// The charge point identity taken from the `/ocpp/{station_id}` connection path.
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    /// The remote address the station connected from.
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The WebSocket subprotocol agreed on during the upgrade, such as `ocpp1.6`.
+    ///
+    /// `None` if the station did not offer any protocol crush supports.
+    #[must_use]
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// When the WebSocket connection was accepted.
+    #[must_use]
+    pub fn connected_at(&self) -> DateTime<Utc> {
+        self.connected_at
+    }
+
+    /// When the station last sent an OCPP message, if it has sent any.
+    #[must_use]
+    pub fn last_message_at(&self) -> Option<DateTime<Utc>> {
+        self.last_message_at
+    }
+
+    /// The certificate the station authenticated with, if it connected over mutual TLS.
+    #[must_use]
+    pub fn client_certificate(&self) -> Option<&ClientCertificate> {
+        self.client_certificate.as_deref()
+    }
+
+    /// The metadata of type `T` attached when the connection was accepted.
+    #[must_use]
+    pub fn metadata<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.metadata.get()
+    }
+
+    /// The last `BootNotification` the station sent on this connection and crush answered.
+    #[must_use]
+    pub fn boot_notification(&self) -> Option<&BootNotificationRequest> {
+        self.boot_notification.as_ref()
+    }
+
+    /// The status crush registered the station with, from its last answered `BootNotification`.
+    ///
+    /// The status is kept when the station reconnects without booting again, `None` if it
+    /// never booted since crush started.
+    #[must_use]
+    pub fn registration_status(&self) -> Option<RegistrationStatus> {
+        self.registration_status.clone()
+    }
+
+    /// The heartbeat interval the station was told in its last answered `BootNotification`.
+    ///
+    /// Like the registration status it is kept when the station reconnects. `None` if the
+    /// station never booted since crush started or was told an interval of zero.
+    #[must_use]
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        self.heartbeat_interval
+    }
+
+    /// Since when the station has been unresponsive, `None` while it sends messages in time.
+    ///
+    /// See [`crate::Config::with_unresponsive_after`].
+    #[must_use]
+    pub fn unresponsive_since(&self) -> Option<DateTime<Utc>> {
+        self.unresponsive_since
+    }
+
+    /// The progress of the station's last signed firmware update, also from earlier sessions.
+    #[must_use]
+    pub fn firmware_update(&self) -> Option<&FirmwareUpdate> {
+        self.firmware_update.as_ref()
+    }
+
+    /// The progress of the station's last log upload, also from earlier sessions.
+    #[must_use]
+    pub fn log_upload(&self) -> Option<&LogUpload> {
+        self.log_upload.as_ref()
+    }
+}
+
+/// The state of a single station connection, shared by the tasks serving it.
+pub(crate) struct Session {
+    station_id: String,
+    address: SocketAddr,
+    protocol: Option<String>,
+    connected_at: DateTime<Utc>,
+    client_certificate: Option<Arc<ClientCertificate>>,
+    metadata: Arc<Extensions>,
+    events: Events,
+    states: StationStates,
+    heartbeat_interval: watch::Sender<Option<Duration>>,
+    state: Mutex<SessionState>,
+}
+
+#[derive(Default)]
+struct SessionState {
+    last_message_at: Option<DateTime<Utc>>,
+    boot_notification: Option<BootNotificationRequest>,
+    registration_status: Option<RegistrationStatus>,
+    unresponsive_since: Option<DateTime<Utc>>,
+    /// The CALLs crush asked the station for since it last booted.
+    triggered: HashSet<&'static str>,
+}
+
+impl Session {
+    pub(crate) fn new(
+        station_id: String,
+        address: SocketAddr,
+        protocol: Option<String>,
+        client_certificate: Option<Arc<ClientCertificate>>,
+        metadata: Extensions,
+        events: Events,
+        states: StationStates,
+    ) -> Self {
+        let heartbeat_interval = watch::Sender::new(states.heartbeat_interval(&station_id));
+        let state = SessionState {
+            registration_status: states.registration_status(&station_id),
+            ..SessionState::default()
+        };
+        Self {
+            station_id,
+            address,
+            protocol,
+            connected_at: Utc::now(),
+            client_certificate,
+            metadata: Arc::new(metadata),
+            events,
+            states,
+            heartbeat_interval,
+            state: Mutex::new(state),
+        }
+    }
+
+    pub(crate) fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    pub(crate) fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub(crate) fn states(&self) -> &StationStates {
+        &self.states
+    }
+
+    /// Handler context for the calls of this session.
+    pub(crate) fn context(&self) -> StationContext {
+        StationContext::new(
+            self.station_id.clone(),
+            self.address,
+            self.client_certificate.clone(),
+            Arc::clone(&self.metadata),
+            self.states.clone(),
+        )
+    }
+
+    pub(crate) fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Publishes an event about this station on the event stream.
+    pub(crate) fn emit(&self, kind: impl FnOnce() -> EventKind) {
+        self.events.emit(&self.station_id, kind);
+    }
+
+    pub(crate) fn message_received(&self) {
+        let unresponsive_since = {
+            let mut state = self.state();
+            state.last_message_at = Some(Utc::now());
+            state.unresponsive_since.take()
+        };
+        if let Some(unresponsive_since) = unresponsive_since {
+            tracing::info!("{} is responsive again", self.station_id);
+            self.emit(|| EventKind::Recovered { unresponsive_since });
+        }
+    }
+
+    /// Marks the station unresponsive, unless it already is.
+    pub(crate) fn unresponsive(&self) {
+        let last_message_at = {
+            let mut state = self.state();
+            if state.unresponsive_since.is_some() {
+                return;
+            }
+            state.unresponsive_since = Some(Utc::now());
+            state.last_message_at
+        };
+        tracing::warn!(
+            "{} is unresponsive, its last message arrived at {last_message_at:?}",
+            self.station_id
+        );
+        self.emit(|| EventKind::Unresponsive { last_message_at });
+    }
+
+    /// Watches the heartbeat interval of the station, which changes whenever it boots.
+    pub(crate) fn heartbeat_intervals(&self) -> watch::Receiver<Option<Duration>> {
+        self.heartbeat_interval.subscribe()
+    }
+
+    pub(crate) fn booted(
+        &self,
+        request: BootNotificationRequest,
+        response: BootNotificationResponse,
+    ) {
+        self.emit(|| EventKind::Booted {
+            request: request.clone(),
+            status: response.status.clone(),
+        });
+        let heartbeat_interval =
+            (response.interval > 0).then(|| Duration::from_secs(response.interval.into()));
+        self.states.booted(
+            &self.station_id,
+            request.clone(),
+            response.status.clone(),
+            heartbeat_interval,
+        );
+        self.heartbeat_interval.send_replace(heartbeat_interval);
+        // A station that boots has lost its transactions, unless it stops them afterwards.
+        for transaction in self.states.transactions().rebooted(&self.station_id) {
+            self.orphaned(transaction);
+        }
+        let mut state = self.state();
+        state.boot_notification = Some(request);
+        state.registration_status = Some(response.status);
+        state.triggered.clear();
+    }
+
+    /// The status of the station's last answered `BootNotification`, see [`StationInfo`].
+    pub(crate) fn registration_status(&self) -> Option<RegistrationStatus> {
+        self.state().registration_status.clone()
+    }
+
+    /// Remembers that crush asked the station to send a CALL for `action`.
+    pub(crate) fn triggered(&self, action: &'static str) {
+        self.state().triggered.insert(action);
+    }
+
+    /// Whether crush asked the station to send a CALL for `action` since it last booted.
+    pub(crate) fn was_triggered(&self, action: &str) -> bool {
+        self.state().triggered.contains(action)
+    }
+
+    pub(crate) fn connector_status(&self, request: &StatusNotificationRequest) {
+        let status = ConnectorStatus::new(request);
+        let connector_id = status.connector_id();
+        match self
+            .states
+            .update_connector(&self.station_id, status.clone())
+        {
+            Update::Stale { current } => tracing::debug!(
+                "Ignoring status of connector {connector_id} of {}, it is older than {current}",
+                self.station_id
+            ),
+            Update::Refreshed => {}
+            Update::Changed { previous } => {
+                self.emit(|| EventKind::ConnectorStatusChanged { previous, status });
+            }
+        }
+    }
+
+    /// Records the start of transaction `id`, returning the change to publish once the write
+    /// completed and the station was answered.
+    pub(crate) fn start_transaction(
+        &self,
+        request: &StartTransactionRequest,
+        id: i32,
+    ) -> (TransactionChange, Written) {
+        let (transaction, orphaned, written) =
+            self.states
+                .transactions()
+                .start(&self.station_id, id, request);
+        (
+            TransactionChange::Started {
+                transaction,
+                orphaned,
+            },
+            written,
+        )
+    }
+
+    pub(crate) fn meter_values(&self, request: &MeterValuesRequest) {
+        if request.transaction_id.is_some()
+            && !self
+                .states
+                .transactions()
+                .meter_values(&self.station_id, request)
+        {
+            tracing::debug!(
+                "Meter values of {} for unknown transaction {:?}",
+                self.station_id,
+                request.transaction_id
+            );
+        }
+    }
+
+    /// Records the stop of a transaction like [`Session::start_transaction`], returning `None`
+    /// if the transaction is unknown.
+    pub(crate) fn stop_transaction(
+        &self,
+        request: &StopTransactionRequest,
+    ) -> Option<(TransactionChange, Written)> {
+        let Some((transaction, written)) =
+            self.states.transactions().stop(&self.station_id, request)
+        else {
+            tracing::warn!(
+                "{} stopped unknown transaction {}",
+                self.station_id,
+                request.transaction_id
+            );
+            return None;
+        };
+        Some((TransactionChange::Stopped(transaction), written))
+    }
+
+    /// Publishes a change recorded by [`Session::start_transaction`] or
+    /// [`Session::stop_transaction`].
+    pub(crate) fn transaction_changed(&self, change: TransactionChange) {
+        match change {
+            TransactionChange::Started {
+                transaction,
+                orphaned,
+            } => {
+                for previous in orphaned {
+                    self.orphaned(previous);
+                }
+                tracing::info!(
+                    "{} started transaction {} on connector {}",
+                    self.station_id,
+                    transaction.id(),
+                    transaction.connector_id()
+                );
+                self.emit(|| EventKind::TransactionStarted { transaction });
+            }
+            TransactionChange::Stopped(transaction) => {
+                tracing::info!(
+                    "{} stopped transaction {} after {:?} Wh",
+                    self.station_id,
+                    transaction.id(),
+                    transaction.energy()
+                );
+                self.emit(|| EventKind::TransactionStopped { transaction });
+            }
+        }
+    }
+
+    /// Waits while the storage is too far behind to take more changes.
+    pub(crate) async fn storage_ready(&self) {
+        self.states.storage().ready().await;
+    }
+
+    fn orphaned(&self, transaction: Transaction) {
+        tracing::warn!(
+            "{} lost transaction {} on connector {}",
+            self.station_id,
+            transaction.id(),
+            transaction.connector_id()
+        );
+        self.emit(|| EventKind::TransactionOrphaned { transaction });
+    }
+
+    pub(crate) fn firmware_status(&self, request: &SignedFirmwareStatusNotificationRequest) {
+        let update = FirmwareUpdate::new(request.request_id, request.status);
+        self.emit(|| EventKind::FirmwareProgress {
+            update: update.clone(),
+        });
+        self.states.set_firmware_update(&self.station_id, update);
+    }
+
+    pub(crate) fn log_status(&self, request: &LogStatusNotificationRequest) {
+        let upload = LogUpload::new(request.request_id, request.status);
+        self.emit(|| EventKind::LogProgress {
+            upload: upload.clone(),
+        });
+        self.states.set_log_upload(&self.station_id, upload);
+    }
+
+    pub(crate) fn info(&self) -> StationInfo {
+        let state = self.state();
+        StationInfo {
+            station_id: self.station_id.clone(),
+            address: self.address,
+            protocol: self.protocol.clone(),
+            connected_at: self.connected_at,
+            last_message_at: state.last_message_at,
+            boot_notification: state.boot_notification.clone(),
+            registration_status: state.registration_status.clone(),
+            heartbeat_interval: *self.heartbeat_interval.borrow(),
+            unresponsive_since: state.unresponsive_since,
+            firmware_update: self.states.firmware_update(&self.station_id),
+            log_upload: self.states.log_upload(&self.station_id),
+            client_certificate: self.client_certificate.clone(),
+            metadata: Arc::clone(&self.metadata),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
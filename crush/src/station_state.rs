@@ -0,0 +1,376 @@
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::{
+    messages::boot_notification::BootNotificationRequest,
+    types::{IdTagInfo, RegistrationStatus},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{
+    connectors::{ConnectorStatus, Connectors, Update},
+    outbound::PendingCall,
+    security::{FirmwareStatus, UploadLogStatus},
+    storage::Snapshot,
+    storage_loop::{StorageHandle, ToStorage},
+    transactions::Transactions,
+};
+
+/// The progress of the last signed firmware update of a station.
+///
+/// Taken from the `SignedFirmwareStatusNotification`s the station sends after
+/// [`crate::actions::SignedUpdateFirmware`], see [`crate::CrushHandle::firmware_update`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareUpdate {
+    request_id: Option<i32>,
+    status: FirmwareStatus,
+    updated_at: DateTime<Utc>,
+}
+
+impl FirmwareUpdate {
+    pub(crate) fn new(request_id: Option<i32>, status: FirmwareStatus) -> Self {
+        Self {
+            request_id,
+            status,
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// The `requestId` of the `SignedUpdateFirmware` the progress belongs to.
+    #[must_use]
+    pub fn request_id(&self) -> Option<i32> {
+        self.request_id
+    }
+
+    /// The status the station reported last.
+    #[must_use]
+    pub fn status(&self) -> FirmwareStatus {
+        self.status
+    }
+
+    /// When crush received the status.
+    #[must_use]
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+/// The progress of the last log upload of a station.
+///
+/// Taken from the `LogStatusNotification`s the station sends after
+/// [`crate::actions::GetLog`], see [`crate::CrushHandle::log_upload`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogUpload {
+    request_id: Option<i32>,
+    status: UploadLogStatus,
+    updated_at: DateTime<Utc>,
+}
+
+impl LogUpload {
+    pub(crate) fn new(request_id: Option<i32>, status: UploadLogStatus) -> Self {
+        Self {
+            request_id,
+            status,
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// The `requestId` of the `GetLog` the progress belongs to.
+    #[must_use]
+    pub fn request_id(&self) -> Option<i32> {
+        self.request_id
+    }
+
+    /// The status the station reported last.
+    #[must_use]
+    pub fn status(&self) -> UploadLogStatus {
+        self.status
+    }
+
+    /// When crush received the status.
+    #[must_use]
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+/// What crush keeps about a station beyond the lifetime of its sessions, as saved to a
+/// [`crate::Storage`].
+///
+/// The connector statuses are saved on their own, they change far more often.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredStation {
+    station_id: String,
+    boot_notification: Option<BootNotificationRequest>,
+    registration_status: Option<RegistrationStatus>,
+    heartbeat_interval: Option<Duration>,
+    firmware_update: Option<FirmwareUpdate>,
+    log_upload: Option<LogUpload>,
+}
+
+impl StoredStation {
+    fn new(station_id: &str) -> Self {
+        Self {
+            station_id: station_id.to_owned(),
+            boot_notification: None,
+            registration_status: None,
+            heartbeat_interval: None,
+            firmware_update: None,
+            log_upload: None,
+        }
+    }
+
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    /// The last `BootNotification` of the station crush answered.
+    #[must_use]
+    pub fn boot_notification(&self) -> Option<&BootNotificationRequest> {
+        self.boot_notification.as_ref()
+    }
+
+    /// The status the last `BootNotification` was answered with.
+    #[must_use]
+    pub fn registration_status(&self) -> Option<&RegistrationStatus> {
+        self.registration_status.as_ref()
+    }
+
+    /// The heartbeat interval the last `BootNotification` was answered with, `None` for zero.
+    #[must_use]
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        self.heartbeat_interval
+    }
+
+    #[must_use]
+    pub fn firmware_update(&self) -> Option<&FirmwareUpdate> {
+        self.firmware_update.as_ref()
+    }
+
+    #[must_use]
+    pub fn log_upload(&self) -> Option<&LogUpload> {
+        self.log_upload.as_ref()
+    }
+}
+
+#[derive(Debug)]
+struct StationState {
+    station: StoredStation,
+    connectors: Connectors,
+}
+
+/// The state of every station that ever connected, shared by its sessions and
+/// [`crate::CrushHandle`].
+///
+/// Firmware updates reboot the station, so their progress has to outlive the session that
+/// started them. Every change is also queued for the storage, if there is one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StationStates {
+    states: Arc<Mutex<HashMap<String, StationState>>>,
+    transactions: Transactions,
+    id_tags: Arc<Mutex<BTreeMap<String, IdTagInfo>>>,
+    /// The CALLs that were waiting for an answer when crush stopped before.
+    interrupted_calls: Arc<Mutex<Vec<PendingCall>>>,
+    storage: StorageHandle,
+}
+
+impl StationStates {
+    pub(crate) fn new(storage: StorageHandle) -> Self {
+        Self {
+            transactions: Transactions::new(storage.clone()),
+            storage,
+            ..Self::default()
+        }
+    }
+
+    /// Takes over what the storage loaded, before the first station connects.
+    ///
+    /// The calls that were waiting for an answer can no longer get one, so they are removed
+    /// from the storage and only kept for [`StationStates::interrupted_calls`].
+    pub(crate) fn restore(&self, snapshot: Snapshot) {
+        {
+            let mut lock = self.lock();
+            for station in snapshot.stations {
+                let station_id = station.station_id.clone();
+                entry(&mut lock, &station_id).station = station;
+            }
+            for (station_id, status) in snapshot.connector_statuses {
+                entry(&mut lock, &station_id).connectors.update(status);
+            }
+        }
+        self.transactions.restore(snapshot.transactions);
+        self.id_tags().extend(snapshot.id_tags);
+        for call in &snapshot.pending_calls {
+            self.call_done(call.station_id(), call.unique_id());
+        }
+        *self
+            .interrupted_calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = snapshot.pending_calls;
+    }
+
+    pub(crate) fn storage(&self) -> &StorageHandle {
+        &self.storage
+    }
+
+    /// The transactions of all stations.
+    pub(crate) fn transactions(&self) -> &Transactions {
+        &self.transactions
+    }
+
+    /// The status the station's last `BootNotification` was answered with.
+    pub(crate) fn registration_status(&self, station_id: &str) -> Option<RegistrationStatus> {
+        self.lock()
+            .get(station_id)
+            .and_then(|state| state.station.registration_status.clone())
+    }
+
+    /// The heartbeat interval the station's last `BootNotification` was answered with.
+    pub(crate) fn heartbeat_interval(&self, station_id: &str) -> Option<Duration> {
+        self.lock()
+            .get(station_id)
+            .and_then(|state| state.station.heartbeat_interval)
+    }
+
+    pub(crate) fn boot_notification(&self, station_id: &str) -> Option<BootNotificationRequest> {
+        self.lock()
+            .get(station_id)
+            .and_then(|state| state.station.boot_notification.clone())
+    }
+
+    /// Records a `BootNotification` of the station and its answer.
+    pub(crate) fn booted(
+        &self,
+        station_id: &str,
+        request: BootNotificationRequest,
+        status: RegistrationStatus,
+        heartbeat_interval: Option<Duration>,
+    ) {
+        entry(&mut self.lock(), station_id).connectors.rebooted();
+        self.update_station(station_id, |station| {
+            station.boot_notification = Some(request);
+            station.registration_status = Some(status);
+            station.heartbeat_interval = heartbeat_interval;
+        });
+    }
+
+    pub(crate) fn connectors(&self, station_id: &str) -> Connectors {
+        self.lock()
+            .get(station_id)
+            .map(|state| state.connectors.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn update_connector(&self, station_id: &str, status: ConnectorStatus) -> Update {
+        let mut lock = self.lock();
+        let update = entry(&mut lock, station_id)
+            .connectors
+            .update(status.clone());
+        if !matches!(update, Update::Stale { .. }) {
+            self.storage.save(|| ToStorage::ConnectorStatus {
+                station_id: station_id.to_owned(),
+                status,
+            });
+        }
+        update
+    }
+
+    pub(crate) fn firmware_update(&self, station_id: &str) -> Option<FirmwareUpdate> {
+        self.lock()
+            .get(station_id)
+            .and_then(|state| state.station.firmware_update.clone())
+    }
+
+    pub(crate) fn set_firmware_update(&self, station_id: &str, update: FirmwareUpdate) {
+        self.update_station(station_id, |station| {
+            station.firmware_update = Some(update);
+        });
+    }
+
+    pub(crate) fn log_upload(&self, station_id: &str) -> Option<LogUpload> {
+        self.lock()
+            .get(station_id)
+            .and_then(|state| state.station.log_upload.clone())
+    }
+
+    pub(crate) fn set_log_upload(&self, station_id: &str, upload: LogUpload) {
+        self.update_station(station_id, |station| station.log_upload = Some(upload));
+    }
+
+    pub(crate) fn id_tag(&self, id_tag: &str) -> Option<IdTagInfo> {
+        self.id_tags().get(id_tag).cloned()
+    }
+
+    pub(crate) fn set_id_tag(&self, id_tag: String, info: IdTagInfo) {
+        let mut id_tags = self.id_tags();
+        self.storage.save(|| ToStorage::IdTag {
+            id_tag: id_tag.clone(),
+            info: Some(info.clone()),
+        });
+        id_tags.insert(id_tag, info);
+    }
+
+    pub(crate) fn remove_id_tag(&self, id_tag: &str) -> bool {
+        let mut id_tags = self.id_tags();
+        let removed = id_tags.remove(id_tag).is_some();
+        if removed {
+            self.storage.save(|| ToStorage::IdTag {
+                id_tag: id_tag.to_owned(),
+                info: None,
+            });
+        }
+        removed
+    }
+
+    /// Records a CALL that is waiting for the station's answer.
+    pub(crate) fn call_sent(&self, call: impl FnOnce() -> PendingCall) {
+        self.storage.save(|| ToStorage::CallSent(call()));
+    }
+
+    /// Records that a CALL got its answer or was given up on.
+    pub(crate) fn call_done(&self, station_id: &str, unique_id: &str) {
+        self.storage.save(|| ToStorage::CallDone {
+            station_id: station_id.to_owned(),
+            unique_id: unique_id.to_owned(),
+        });
+    }
+
+    pub(crate) fn interrupted_calls(&self) -> Vec<PendingCall> {
+        self.interrupted_calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Applies `update` to the record of the station and queues the result for the storage.
+    fn update_station(&self, station_id: &str, update: impl FnOnce(&mut StoredStation)) {
+        let mut lock = self.lock();
+        let station = &mut entry(&mut lock, station_id).station;
+        update(station);
+        self.storage.save(|| ToStorage::Station(station.clone()));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, StationState>> {
+        self.states.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn id_tags(&self) -> MutexGuard<'_, BTreeMap<String, IdTagInfo>> {
+        self.id_tags.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn entry<'states>(
+    states: &'states mut HashMap<String, StationState>,
+    station_id: &str,
+) -> &'states mut StationState {
+    states
+        .entry(station_id.to_owned())
+        .or_insert_with(|| StationState {
+            station: StoredStation::new(station_id),
+            connectors: Connectors::default(),
+        })
+}
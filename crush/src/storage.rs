@@ -0,0 +1,394 @@
+use async_trait::async_trait;
+use rust_ocpp::v1_6::types::{IdTagInfo, MeterValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    connectors::ConnectorStatus, error::StorageError, outbound::PendingCall,
+    station_state::StoredStation, transactions::Transaction,
+};
+
+mod file;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use file::FileStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteStorage, TransactionFilter};
+
+/// Keeps the state crush builds from station messages across restarts.
+///
+/// Set a storage with [`crate::CrushBuilder::with_storage`]. Crush loads it before it accepts
+/// the first connection and then writes every change in the background, in the order the
+/// changes happened. Write errors are logged, they do not fail the OCPP exchange that caused
+/// them, except for the transactions a `StartTransaction` or `StopTransaction` changes: those
+/// are written before the station is answered, and answered with an `InternalError` CALLERROR
+/// if the write fails, so the station retries and no transaction id is handed out twice.
+/// A storage that falls behind makes stations wait for their answers.
+/// [`crate::CrushHandle::flush_storage`] waits until the changes made so far are written.
+///
+/// Records are passed by reference and serialize with `serde`, so a storage can keep them as
+/// JSON without knowing their fields.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Loads everything saved before.
+    async fn load(&self) -> Result<Snapshot, StorageError>;
+
+    /// Saves the registration, boot info and progress of a station, replacing the previous
+    /// record of the station.
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError>;
+
+    /// Saves the status of a connector, replacing its previous status.
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError>;
+
+    /// Saves transactions that changed together, replacing their previous records.
+    ///
+    /// A `StartTransaction` orphans the transaction it replaces, so both have to be saved or
+    /// neither.
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError>;
+
+    /// Appends the meter values of a `MeterValues` to a saved transaction.
+    ///
+    /// Stations send meter values for as long as a transaction runs, so they are saved as they
+    /// arrive instead of saving the whole transaction again each time.
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError>;
+
+    /// Saves how crush answers for `id_tag`, see [`crate::CrushHandle::set_id_tag`].
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError>;
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError>;
+
+    /// Saves a CALL sent to a station that is waiting for its answer.
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError>;
+
+    /// Removes a CALL that was answered or given up on.
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+impl<T: Storage + ?Sized> Storage for Arc<T> {
+    async fn load(&self) -> Result<Snapshot, StorageError> {
+        (**self).load().await
+    }
+
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError> {
+        (**self).save_station(station).await
+    }
+
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError> {
+        (**self).save_connector_status(station_id, status).await
+    }
+
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        (**self).save_transactions(transactions).await
+    }
+
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError> {
+        (**self)
+            .save_meter_values(transaction_id, meter_values)
+            .await
+    }
+
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError> {
+        (**self).save_id_tag(id_tag, info).await
+    }
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError> {
+        (**self).remove_id_tag(id_tag).await
+    }
+
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError> {
+        (**self).save_pending_call(call).await
+    }
+
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError> {
+        (**self).remove_pending_call(station_id, unique_id).await
+    }
+}
+
+/// Everything a [`Storage`] holds, as returned by [`Storage::load`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Snapshot {
+    pub stations: Vec<StoredStation>,
+    /// The connector statuses by station id.
+    pub connector_statuses: Vec<(String, ConnectorStatus)>,
+    pub transactions: Vec<Transaction>,
+    /// The id tags with how crush answers for them.
+    pub id_tags: Vec<(String, IdTagInfo)>,
+    pub pending_calls: Vec<PendingCall>,
+}
+
+/// A single change to the records, also the line format of [`FileStorage`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Entry {
+    Station(StoredStation),
+    ConnectorStatus {
+        station_id: String,
+        status: ConnectorStatus,
+    },
+    Transactions {
+        transactions: Vec<Transaction>,
+    },
+    MeterValues {
+        transaction_id: i32,
+        meter_values: Vec<MeterValue>,
+    },
+    IdTag {
+        id_tag: String,
+        info: IdTagInfo,
+    },
+    IdTagRemoved {
+        id_tag: String,
+    },
+    PendingCall(PendingCall),
+    PendingCallRemoved {
+        station_id: String,
+        unique_id: String,
+    },
+}
+
+/// The current records, which [`Entry`]s are applied to.
+#[derive(Debug, Default)]
+struct Records {
+    stations: BTreeMap<String, StoredStation>,
+    connector_statuses: BTreeMap<(String, u32), ConnectorStatus>,
+    transactions: BTreeMap<i32, Transaction>,
+    id_tags: BTreeMap<String, IdTagInfo>,
+    pending_calls: BTreeMap<(String, String), PendingCall>,
+}
+
+impl Records {
+    fn apply(&mut self, entry: Entry) {
+        match entry {
+            Entry::Station(station) => {
+                self.stations
+                    .insert(station.station_id().to_owned(), station);
+            }
+            Entry::ConnectorStatus { station_id, status } => {
+                self.connector_statuses
+                    .insert((station_id, status.connector_id()), status);
+            }
+            Entry::Transactions { transactions } => {
+                for transaction in transactions {
+                    self.transactions.insert(transaction.id(), transaction);
+                }
+            }
+            Entry::MeterValues {
+                transaction_id,
+                meter_values,
+            } => {
+                if let Some(transaction) = self.transactions.get_mut(&transaction_id) {
+                    transaction.add_meter_values(meter_values);
+                }
+            }
+            Entry::IdTag { id_tag, info } => {
+                self.id_tags.insert(id_tag, info);
+            }
+            Entry::IdTagRemoved { id_tag } => {
+                self.id_tags.remove(&id_tag);
+            }
+            Entry::PendingCall(call) => {
+                self.pending_calls.insert(
+                    (call.station_id().to_owned(), call.unique_id().to_owned()),
+                    call,
+                );
+            }
+            Entry::PendingCallRemoved {
+                station_id,
+                unique_id,
+            } => {
+                self.pending_calls.remove(&(station_id, unique_id));
+            }
+        }
+    }
+
+    /// How many entries it takes to write the records.
+    fn len(&self) -> usize {
+        self.stations.len()
+            + self.connector_statuses.len()
+            + self.transactions.len()
+            + self.id_tags.len()
+            + self.pending_calls.len()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            stations: self.stations.values().cloned().collect(),
+            connector_statuses: self
+                .connector_statuses
+                .iter()
+                .map(|((station_id, _), status)| (station_id.clone(), status.clone()))
+                .collect(),
+            transactions: self.transactions.values().cloned().collect(),
+            id_tags: self
+                .id_tags
+                .iter()
+                .map(|(id_tag, info)| (id_tag.clone(), info.clone()))
+                .collect(),
+            pending_calls: self.pending_calls.values().cloned().collect(),
+        }
+    }
+
+    /// The entries that recreate the records, one per record.
+    fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
+        let stations = self.stations.values().cloned().map(Entry::Station);
+        let connector_statuses = self
+            .connector_statuses
+            .iter()
+            .map(|((station_id, _), status)| Entry::ConnectorStatus {
+                station_id: station_id.clone(),
+                status: status.clone(),
+            });
+        let transactions = self
+            .transactions
+            .values()
+            .map(|transaction| Entry::Transactions {
+                transactions: vec![transaction.clone()],
+            });
+        let id_tags = self.id_tags.iter().map(|(id_tag, info)| Entry::IdTag {
+            id_tag: id_tag.clone(),
+            info: info.clone(),
+        });
+        let pending_calls = self.pending_calls.values().cloned().map(Entry::PendingCall);
+        stations
+            .chain(connector_statuses)
+            .chain(transactions)
+            .chain(id_tags)
+            .chain(pending_calls)
+    }
+}
+
+/// A [`Storage`] keeping the records in memory, so they outlive the [`crate::Crush`] instance
+/// but not the process.
+///
+/// Meant for tests and for embedding crush in a process that restarts it.
+///
+/// # Examples
+///
+/// ```rust
+/// # use crush::{Config, CrushBuilder, InMemoryStorage};
+/// # use std::sync::Arc;
+/// let storage = Arc::new(InMemoryStorage::new());
+///
+/// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+/// let builder = CrushBuilder::new(config).with_storage(Arc::clone(&storage));
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryStorage {
+    records: Mutex<Records>,
+}
+
+impl InMemoryStorage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Records> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl Storage for InMemoryStorage {
+    async fn load(&self) -> Result<Snapshot, StorageError> {
+        Ok(self.lock().snapshot())
+    }
+
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError> {
+        self.lock().apply(Entry::Station(station.clone()));
+        Ok(())
+    }
+
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError> {
+        self.lock().apply(Entry::ConnectorStatus {
+            station_id: station_id.to_owned(),
+            status: status.clone(),
+        });
+        Ok(())
+    }
+
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        self.lock().apply(Entry::Transactions {
+            transactions: transactions.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError> {
+        self.lock().apply(Entry::MeterValues {
+            transaction_id,
+            meter_values: meter_values.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError> {
+        self.lock().apply(Entry::IdTag {
+            id_tag: id_tag.to_owned(),
+            info: info.clone(),
+        });
+        Ok(())
+    }
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError> {
+        self.lock().apply(Entry::IdTagRemoved {
+            id_tag: id_tag.to_owned(),
+        });
+        Ok(())
+    }
+
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError> {
+        self.lock().apply(Entry::PendingCall(call.clone()));
+        Ok(())
+    }
+
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError> {
+        self.lock().apply(Entry::PendingCallRemoved {
+            station_id: station_id.to_owned(),
+            unique_id: unique_id.to_owned(),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use async_trait::async_trait;
+use rust_ocpp::v1_6::types::{IdTagInfo, MeterValue};
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::task::spawn_blocking;
+
+use super::{Entry, Records, Snapshot, Storage};
+use crate::{
+    connectors::ConnectorStatus, error::StorageError, outbound::PendingCall,
+    station_state::StoredStation, transactions::Transaction,
+};
+
+/// The log is never compacted below this many lines, rewriting a small log gains nothing.
+const COMPACT_AFTER: usize = 1_000;
+
+/// A [`Storage`] appending every change as a line of JSON to a file, for small deployments.
+///
+/// Records that change are appended again rather than updated in place. Once the log has
+/// more than twice the lines its records need, it is compacted: rewritten next to the log
+/// with one line per record and moved over it. Every line is synced to disk before the write
+/// counts as done, on a blocking thread so the runtime keeps serving stations meanwhile. A
+/// line that fails to be written is removed again, so it cannot corrupt the log.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use crush::{Config, CrushBuilder, FileStorage, StorageError};
+/// # fn example() -> Result<(), StorageError> {
+/// let storage = FileStorage::open("/var/lib/crush/state.jsonl")?;
+///
+/// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+/// let builder = CrushBuilder::new(config).with_storage(storage);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileStorage {
+    shared: Arc<Shared>,
+}
+
+/// The log, shared with the blocking threads writing it.
+#[derive(Debug)]
+struct Shared {
+    path: PathBuf,
+    log: Mutex<Log>,
+}
+
+#[derive(Debug)]
+struct Log {
+    file: File,
+    records: Records,
+    lines: usize,
+    /// Set when a failed write could not be removed again, nothing is appended after it.
+    torn: bool,
+}
+
+impl FileStorage {
+    /// Opens the log at `path`, creating it if it does not exist, and replays it.
+    ///
+    /// A crash while appending can leave a partial last line behind, which is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the log cannot be read or written, or if a line other than a partial last
+    /// one is not a valid record.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let path = path.into();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(StorageError::io(&path, error)),
+        };
+
+        let mut records = Records::default();
+        let mut lines = 0;
+        let mut valid_len = 0;
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            if !line.ends_with('\n') {
+                tracing::warn!(
+                    "Dropping the partial last line of {}, crush stopped while writing it",
+                    path.display()
+                );
+                break;
+            }
+            let entry = serde_json::from_str(line).map_err(|error| StorageError::Corrupt {
+                path: path.clone(),
+                line: index + 1,
+                reason: error.to_string(),
+            })?;
+            records.apply(entry);
+            lines += 1;
+            valid_len += line.len();
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|error| StorageError::io(&path, error))?;
+        if valid_len < content.len() {
+            file.set_len(valid_len as u64)
+                .map_err(|error| StorageError::io(&path, error))?;
+        }
+
+        let shared = Shared {
+            path,
+            log: Mutex::new(Log {
+                file,
+                records,
+                lines,
+                torn: false,
+            }),
+        };
+        {
+            let mut log = shared.lock();
+            if log.is_wasteful() {
+                shared.compact_log(&mut log)?;
+            }
+        }
+        Ok(Self {
+            shared: Arc::new(shared),
+        })
+    }
+
+    /// The path of the log.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.shared.path
+    }
+
+    /// Rewrites the log with one line per record.
+    ///
+    /// Crush compacts the log by itself as it grows, this is for compacting it right away,
+    /// e.g. before a backup.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the compacted log cannot be written, the previous log is kept then.
+    pub fn compact(&self) -> Result<(), StorageError> {
+        self.shared.compact_log(&mut self.shared.lock())
+    }
+
+    /// Appends `entry` on a blocking thread.
+    async fn append(&self, entry: Entry) -> Result<(), StorageError> {
+        let shared = Arc::clone(&self.shared);
+        spawn_blocking(move || shared.append(entry))
+            .await
+            .map_err(|error| StorageError::Other(Box::new(error)))?
+    }
+}
+
+impl Shared {
+    fn compact_log(&self, log: &mut Log) -> Result<(), StorageError> {
+        let mut temporary = OsString::from(&self.path);
+        temporary.push(".compact");
+        let temporary = PathBuf::from(temporary);
+
+        let mut writer = BufWriter::new(
+            File::create(&temporary).map_err(|error| StorageError::io(&temporary, error))?,
+        );
+        let mut lines = 0;
+        for entry in log.records.entries() {
+            write_line(&mut writer, &entry).map_err(|error| error.at(&temporary))?;
+            lines += 1;
+        }
+        writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+            .and_then(|file| file.sync_all())
+            .map_err(|error| StorageError::io(&temporary, error))?;
+
+        fs::rename(&temporary, &self.path).map_err(|error| StorageError::io(&self.path, error))?;
+        log.file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|error| StorageError::io(&self.path, error))?;
+        tracing::debug!(
+            "Compacted {} from {} to {lines} lines",
+            self.path.display(),
+            log.lines
+        );
+        log.lines = lines;
+        Ok(())
+    }
+
+    fn append(&self, entry: Entry) -> Result<(), StorageError> {
+        let mut log = self.lock();
+        if log.torn {
+            return Err(StorageError::io(
+                &self.path,
+                io::Error::other("the log ends in a partial line, reopen it to drop the line"),
+            ));
+        }
+        let len = log
+            .file
+            .metadata()
+            .map_err(|error| StorageError::io(&self.path, error))?
+            .len();
+        let written = write_line(&mut log.file, &entry)
+            .and_then(|()| log.file.sync_data().map_err(LineError::Io));
+        if let Err(error) = written {
+            if let Err(truncate_error) = log.file.set_len(len) {
+                tracing::error!(
+                    "Failed to remove a partial line from {}, not writing to it anymore: {truncate_error}",
+                    self.path.display()
+                );
+                log.torn = true;
+            }
+            return Err(error.at(&self.path));
+        }
+        log.records.apply(entry);
+        log.lines += 1;
+        if log.is_wasteful() {
+            // The line is written either way, compacting is tried again with the next one.
+            if let Err(error) = self.compact_log(&mut log) {
+                tracing::error!("Failed to compact {}: {error}", self.path.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Log {
+    /// Whether compacting would at least halve the log.
+    fn is_wasteful(&self) -> bool {
+        self.lines > COMPACT_AFTER.max(self.records.len() * 2)
+    }
+}
+
+enum LineError {
+    Serialize(serde_json::Error),
+    Io(io::Error),
+}
+
+impl LineError {
+    fn at(self, path: &Path) -> StorageError {
+        match self {
+            Self::Serialize(error) => StorageError::Serialize(error.to_string()),
+            Self::Io(error) => StorageError::io(path, error),
+        }
+    }
+}
+
+fn write_line(writer: &mut impl Write, entry: &Entry) -> Result<(), LineError> {
+    let mut line = serde_json::to_vec(entry).map_err(LineError::Serialize)?;
+    line.push(b'\n');
+    writer.write_all(&line).map_err(LineError::Io)
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn load(&self) -> Result<Snapshot, StorageError> {
+        // The lock is held while a line is synced.
+        let shared = Arc::clone(&self.shared);
+        spawn_blocking(move || shared.lock().records.snapshot())
+            .await
+            .map_err(|error| StorageError::Other(Box::new(error)))
+    }
+
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError> {
+        self.append(Entry::Station(station.clone())).await
+    }
+
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError> {
+        self.append(Entry::ConnectorStatus {
+            station_id: station_id.to_owned(),
+            status: status.clone(),
+        })
+        .await
+    }
+
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        // A single line, so a crash cannot save only some of them.
+        self.append(Entry::Transactions {
+            transactions: transactions.to_vec(),
+        })
+        .await
+    }
+
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError> {
+        self.append(Entry::MeterValues {
+            transaction_id,
+            meter_values: meter_values.to_vec(),
+        })
+        .await
+    }
+
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError> {
+        self.append(Entry::IdTag {
+            id_tag: id_tag.to_owned(),
+            info: info.clone(),
+        })
+        .await
+    }
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError> {
+        self.append(Entry::IdTagRemoved {
+            id_tag: id_tag.to_owned(),
+        })
+        .await
+    }
+
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError> {
+        self.append(Entry::PendingCall(call.clone())).await
+    }
+
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError> {
+        self.append(Entry::PendingCallRemoved {
+            station_id: station_id.to_owned(),
+            unique_id: unique_id.to_owned(),
+        })
+        .await
+    }
+}
@@ -0,0 +1,468 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction as Batch};
+use rust_ocpp::v1_6::types::{IdTagInfo, MeterValue};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::task::spawn_blocking;
+
+use super::{Snapshot, Storage};
+use crate::{
+    connectors::ConnectorStatus, error::StorageError, outbound::PendingCall,
+    station_state::StoredStation, transactions::Transaction,
+};
+
+/// The schema, one migration per version in `PRAGMA user_version`.
+///
+/// Released migrations are never changed, a schema change is a new migration.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE stations (
+        station_id TEXT PRIMARY KEY,
+        record TEXT NOT NULL
+    );
+    CREATE TABLE connector_statuses (
+        station_id TEXT NOT NULL,
+        connector_id INTEGER NOT NULL,
+        record TEXT NOT NULL,
+        PRIMARY KEY (station_id, connector_id)
+    );
+    CREATE TABLE transactions (
+        id INTEGER PRIMARY KEY,
+        station_id TEXT NOT NULL,
+        connector_id INTEGER NOT NULL,
+        id_tag TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        stopped_at TEXT,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX transactions_by_station ON transactions (station_id, started_at);
+    CREATE INDEX transactions_by_id_tag ON transactions (id_tag, started_at);
+    CREATE INDEX transactions_by_start ON transactions (started_at);
+    CREATE TABLE id_tags (
+        id_tag TEXT PRIMARY KEY,
+        info TEXT NOT NULL
+    );
+    CREATE TABLE pending_calls (
+        station_id TEXT NOT NULL,
+        unique_id TEXT NOT NULL,
+        record TEXT NOT NULL,
+        PRIMARY KEY (station_id, unique_id)
+    );
+"];
+
+/// A [`Storage`] on an embedded `SQLite` database, for single-node deployments that want to
+/// query their charging history.
+///
+/// Requires the `sqlite` feature. Opening the database migrates its schema to the version of
+/// this crush. Crush writes it on blocking threads, so the runtime keeps serving stations while
+/// `SQLite` waits for the disk. The transactions changed by a single `StartTransaction` or
+/// `StopTransaction` are written in one database transaction, which is committed before the
+/// station is answered, and they are indexed for [`SqliteStorage::transactions`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use crush::{Config, CrushBuilder, SqliteStorage, StorageError, TransactionFilter};
+/// # use std::sync::Arc;
+/// # async fn example() -> Result<(), StorageError> {
+/// let storage = Arc::new(SqliteStorage::open("/var/lib/crush/crush.db")?);
+///
+/// let config = Config::new("127.0.0.1:9100".parse().unwrap());
+/// let builder = CrushBuilder::new(config).with_storage(Arc::clone(&storage));
+///
+/// let charged: i64 = storage
+///     .transactions(&TransactionFilter::new().with_id_tag("04A2B3C4"))
+///     .await?
+///     .iter()
+///     .filter_map(|transaction| transaction.energy())
+///     .sum();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `path`, creating it if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the database cannot be opened or migrated, or if it was migrated by a newer
+    /// crush.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let connection = Connection::open(path)?;
+        // Readers do not block the writer and the other way round.
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(connection)
+    }
+
+    /// Opens a database that lives as long as the storage, e.g. for tests.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `SQLite` cannot allocate the database.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self, StorageError> {
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// The transactions `filter` selects, ordered by when they started.
+    ///
+    /// Like the writes, the query runs on a blocking thread.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the database cannot be read or holds a record this crush cannot read.
+    pub async fn transactions(
+        &self,
+        filter: &TransactionFilter,
+    ) -> Result<Vec<Transaction>, StorageError> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(station_id) = &filter.station_id {
+            conditions.push("station_id = ?");
+            values.push(station_id.clone());
+        }
+        if let Some(id_tag) = &filter.id_tag {
+            conditions.push("id_tag = ?");
+            values.push(id_tag.clone());
+        }
+        if let Some(started_after) = filter.started_after {
+            conditions.push("started_at >= ?");
+            values.push(timestamp(started_after));
+        }
+        if let Some(started_before) = filter.started_before {
+            conditions.push("started_at < ?");
+            values.push(timestamp(started_before));
+        }
+        let mut sql = "SELECT record FROM transactions".to_owned();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY started_at, id");
+
+        self.run(move |connection| {
+            let mut statement = connection.prepare(&sql)?;
+            let rows =
+                statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?;
+            rows.map(|record| decode("transaction", &record?)).collect()
+        })
+        .await
+    }
+
+    /// Runs `operation` on the connection on a blocking thread.
+    async fn run<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut Connection) -> Result<T, StorageError> + Send + 'static,
+    ) -> Result<T, StorageError> {
+        let connection = Arc::clone(&self.connection);
+        spawn_blocking(move || operation(&mut lock(&connection)))
+            .await
+            .map_err(|error| StorageError::Other(Box::new(error)))?
+    }
+}
+
+fn lock(connection: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    connection.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Which transactions [`SqliteStorage::transactions`] returns, all of them by default.
+///
+/// The time range is on when the transactions started according to their stations.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    station_id: Option<String>,
+    id_tag: Option<String>,
+    started_after: Option<DateTime<Utc>>,
+    started_before: Option<DateTime<Utc>>,
+}
+
+impl TransactionFilter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the transactions of `station_id`.
+    #[must_use]
+    pub fn with_station(mut self, station_id: impl Into<String>) -> Self {
+        self.station_id = Some(station_id.into());
+        self
+    }
+
+    /// Selects the transactions `id_tag` started.
+    #[must_use]
+    pub fn with_id_tag(mut self, id_tag: impl Into<String>) -> Self {
+        self.id_tag = Some(id_tag.into());
+        self
+    }
+
+    /// Selects the transactions that started at or after `started_after`.
+    #[must_use]
+    pub fn with_started_after(mut self, started_after: DateTime<Utc>) -> Self {
+        self.started_after = Some(started_after);
+        self
+    }
+
+    /// Selects the transactions that started before `started_before`.
+    #[must_use]
+    pub fn with_started_before(mut self, started_before: DateTime<Utc>) -> Self {
+        self.started_before = Some(started_before);
+        self
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), StorageError> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(StorageError::UnsupportedSchema {
+            version,
+            supported: MIGRATIONS.len(),
+        });
+    }
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let batch = connection.transaction()?;
+        batch.execute_batch(migration)?;
+        batch.pragma_update(None, "user_version", applied + 1)?;
+        batch.commit()?;
+        tracing::info!("Migrated the SQLite storage to version {}", applied + 1);
+    }
+    Ok(())
+}
+
+/// Formats `time` so that the text sorts like the time.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn encode(record: &impl Serialize) -> Result<String, StorageError> {
+    serde_json::to_string(record).map_err(|error| StorageError::Serialize(error.to_string()))
+}
+
+fn decode<T: DeserializeOwned>(kind: &'static str, record: &str) -> Result<T, StorageError> {
+    serde_json::from_str(record).map_err(|error| StorageError::InvalidRecord {
+        kind,
+        reason: error.to_string(),
+    })
+}
+
+/// The records `sql` selects, as its only column.
+fn records<T: DeserializeOwned>(
+    connection: &Connection,
+    kind: &'static str,
+    sql: &str,
+) -> Result<Vec<T>, StorageError> {
+    let mut statement = connection.prepare(sql)?;
+    let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+    rows.map(|record| decode(kind, &record?)).collect()
+}
+
+/// The records `sql` selects as its second column, by the key in its first.
+fn keyed_records<T: DeserializeOwned>(
+    connection: &Connection,
+    kind: &'static str,
+    sql: &str,
+) -> Result<Vec<(String, T)>, StorageError> {
+    let mut statement = connection.prepare(sql)?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    rows.map(|row| {
+        let (key, record) = row?;
+        Ok((key, decode(kind, &record)?))
+    })
+    .collect()
+}
+
+fn save_transaction(batch: &Batch<'_>, transaction: &Transaction) -> Result<(), StorageError> {
+    batch.execute(
+        "INSERT OR REPLACE INTO transactions
+            (id, station_id, connector_id, id_tag, started_at, stopped_at, record)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            transaction.id(),
+            transaction.station_id(),
+            transaction.connector_id(),
+            transaction.id_tag(),
+            timestamp(transaction.started_at()),
+            transaction.stop().map(|stop| timestamp(stop.stopped_at())),
+            encode(transaction)?,
+        ],
+    )?;
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn load(&self) -> Result<Snapshot, StorageError> {
+        self.run(|connection| {
+            Ok(Snapshot {
+                stations: records(
+                    connection,
+                    "station",
+                    "SELECT record FROM stations ORDER BY station_id",
+                )?,
+                connector_statuses: keyed_records(
+                    connection,
+                    "connector status",
+                    "SELECT station_id, record FROM connector_statuses
+                        ORDER BY station_id, connector_id",
+                )?,
+                transactions: records(
+                    connection,
+                    "transaction",
+                    "SELECT record FROM transactions ORDER BY id",
+                )?,
+                id_tags: keyed_records(
+                    connection,
+                    "id tag",
+                    "SELECT id_tag, info FROM id_tags ORDER BY id_tag",
+                )?,
+                pending_calls: records(
+                    connection,
+                    "pending call",
+                    "SELECT record FROM pending_calls ORDER BY station_id, unique_id",
+                )?,
+            })
+        })
+        .await
+    }
+
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError> {
+        let station_id = station.station_id().to_owned();
+        let record = encode(station)?;
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO stations (station_id, record) VALUES (?1, ?2)",
+                params![station_id, record],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError> {
+        let station_id = station_id.to_owned();
+        let connector_id = status.connector_id();
+        let record = encode(status)?;
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO connector_statuses (station_id, connector_id, record)
+                    VALUES (?1, ?2, ?3)",
+                params![station_id, connector_id, record],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        let transactions = transactions.to_vec();
+        self.run(move |connection| {
+            let batch = connection.transaction()?;
+            for transaction in &transactions {
+                save_transaction(&batch, transaction)?;
+            }
+            batch.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError> {
+        let meter_values = meter_values.to_vec();
+        self.run(move |connection| {
+            let batch = connection.transaction()?;
+            let record = batch
+                .query_row(
+                    "SELECT record FROM transactions WHERE id = ?1",
+                    params![transaction_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?;
+            // Like the other storages, meter values of unknown transactions are dropped.
+            if let Some(record) = record {
+                let mut transaction: Transaction = decode("transaction", &record)?;
+                transaction.add_meter_values(meter_values);
+                save_transaction(&batch, &transaction)?;
+            }
+            batch.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError> {
+        let id_tag = id_tag.to_owned();
+        let info = encode(info)?;
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO id_tags (id_tag, info) VALUES (?1, ?2)",
+                params![id_tag, info],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError> {
+        let id_tag = id_tag.to_owned();
+        self.run(move |connection| {
+            connection.execute("DELETE FROM id_tags WHERE id_tag = ?1", params![id_tag])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError> {
+        let station_id = call.station_id().to_owned();
+        let unique_id = call.unique_id().to_owned();
+        let record = encode(call)?;
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO pending_calls (station_id, unique_id, record)
+                    VALUES (?1, ?2, ?3)",
+                params![station_id, unique_id, record],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError> {
+        let station_id = station_id.to_owned();
+        let unique_id = unique_id.to_owned();
+        self.run(move |connection| {
+            connection.execute(
+                "DELETE FROM pending_calls WHERE station_id = ?1 AND unique_id = ?2",
+                params![station_id, unique_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,229 @@
+use futures::FutureExt;
+use rust_ocpp::v1_6::types::{IdTagInfo, MeterValue};
+use std::{
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot, Notify,
+};
+
+use crate::{
+    connectors::ConnectorStatus, error::StorageError, outbound::PendingCall,
+    station_state::StoredStation, storage::Storage, supervisor::panic_message,
+    transactions::Transaction,
+};
+
+/// How many changes may wait for the storage before controllers stop taking CALLs, see
+/// [`StorageHandle::ready`].
+const QUEUE_LIMIT: usize = 1_024;
+
+/// A change to write to the storage.
+pub(crate) enum ToStorage {
+    Station(StoredStation),
+    ConnectorStatus {
+        station_id: String,
+        status: ConnectorStatus,
+    },
+    Transactions(Vec<Transaction>),
+    MeterValues {
+        transaction_id: i32,
+        meter_values: Vec<MeterValue>,
+    },
+    /// Saves the id tag, or removes it if there is no info.
+    IdTag {
+        id_tag: String,
+        info: Option<IdTagInfo>,
+    },
+    CallSent(PendingCall),
+    CallDone {
+        station_id: String,
+        unique_id: String,
+    },
+    /// Answers once the changes sent before are written.
+    Flush(oneshot::Sender<()>),
+}
+
+/// A queued change, with whoever waits for it to be written.
+struct Queued {
+    change: ToStorage,
+    written: Option<oneshot::Sender<Result<(), StorageError>>>,
+}
+
+/// The changes that are queued but not written yet.
+#[derive(Debug, Default)]
+struct Backlog {
+    len: AtomicUsize,
+    drained: Notify,
+}
+
+/// Writes the changes to the storage one at a time, in the order they were sent.
+///
+/// A panicking storage does not take the loop down: the change is logged as failed and the
+/// loop goes on with the next one.
+async fn run_storage(
+    storage: Arc<dyn Storage>,
+    mut receiver: UnboundedReceiver<Queued>,
+    backlog: Arc<Backlog>,
+) {
+    while let Some(Queued { change, written }) = receiver.recv().await {
+        let result = match AssertUnwindSafe(write(storage.as_ref(), change))
+            .catch_unwind()
+            .await
+        {
+            Ok(result) => result,
+            Err(panic) => Err(StorageError::Other(
+                format!(
+                    "Storage panicked while writing: {}",
+                    panic_message(panic.as_ref())
+                )
+                .into(),
+            )),
+        };
+        if backlog.len.fetch_sub(1, Ordering::AcqRel) == QUEUE_LIMIT {
+            backlog.drained.notify_waiters();
+        }
+        match written {
+            // The waiting caller reports the error.
+            Some(written) => {
+                if let Err(Err(error)) = written.send(result) {
+                    tracing::error!("Failed to write to the storage: {error}");
+                }
+            }
+            None => {
+                if let Err(error) = result {
+                    tracing::error!("Failed to write to the storage: {error}");
+                }
+            }
+        }
+    }
+}
+
+async fn write(storage: &dyn Storage, change: ToStorage) -> Result<(), StorageError> {
+    match change {
+        ToStorage::Station(station) => storage.save_station(&station).await,
+        ToStorage::ConnectorStatus { station_id, status } => {
+            storage.save_connector_status(&station_id, &status).await
+        }
+        ToStorage::Transactions(transactions) => storage.save_transactions(&transactions).await,
+        ToStorage::MeterValues {
+            transaction_id,
+            meter_values,
+        } => {
+            storage
+                .save_meter_values(transaction_id, &meter_values)
+                .await
+        }
+        ToStorage::IdTag {
+            id_tag,
+            info: Some(info),
+        } => storage.save_id_tag(&id_tag, &info).await,
+        ToStorage::IdTag { id_tag, info: None } => storage.remove_id_tag(&id_tag).await,
+        ToStorage::CallSent(call) => storage.save_pending_call(&call).await,
+        ToStorage::CallDone {
+            station_id,
+            unique_id,
+        } => storage.remove_pending_call(&station_id, &unique_id).await,
+        ToStorage::Flush(done) => {
+            // The caller may have stopped waiting.
+            done.send(()).ok();
+            Ok(())
+        }
+    }
+}
+
+/// A change queued with [`StorageHandle::save_durably`], which can be waited for.
+pub(crate) struct Written(Option<oneshot::Receiver<Result<(), StorageError>>>);
+
+impl Written {
+    /// Waits until the change is written, returning why it was not.
+    pub(crate) async fn wait(self) -> Result<(), StorageError> {
+        let Some(written) = self.0 else {
+            return Ok(());
+        };
+        written.await.unwrap_or_else(|_| {
+            Err(StorageError::Other(
+                "Storage loop has shut down, the change was not saved".into(),
+            ))
+        })
+    }
+}
+
+/// The mailbox of the storage loop, which drops every change if no storage is configured.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StorageHandle {
+    sender: Option<UnboundedSender<Queued>>,
+    backlog: Arc<Backlog>,
+}
+
+impl StorageHandle {
+    pub(crate) fn start(storage: Arc<dyn Storage>) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let backlog = Arc::<Backlog>::default();
+        tokio::spawn(run_storage(storage, receiver, Arc::clone(&backlog)));
+        Self {
+            sender: Some(sender),
+            backlog,
+        }
+    }
+
+    /// Queues the change `change` builds, which is only built if there is a storage.
+    ///
+    /// Callers hold the lock of the state they changed, so changes are queued in the order
+    /// they happened.
+    pub(crate) fn save(&self, change: impl FnOnce() -> ToStorage) {
+        self.queue(change, None);
+    }
+
+    /// Like [`StorageHandle::save`], returning a handle to wait for the write with.
+    pub(crate) fn save_durably(&self, change: impl FnOnce() -> ToStorage) -> Written {
+        if self.sender.is_none() {
+            return Written(None);
+        }
+        let (written, receiver) = oneshot::channel();
+        self.queue(change, Some(written));
+        Written(Some(receiver))
+    }
+
+    fn queue(
+        &self,
+        change: impl FnOnce() -> ToStorage,
+        written: Option<oneshot::Sender<Result<(), StorageError>>>,
+    ) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        self.backlog.len.fetch_add(1, Ordering::AcqRel);
+        let queued = Queued {
+            change: change(),
+            written,
+        };
+        if sender.send(queued).is_err() {
+            self.backlog.len.fetch_sub(1, Ordering::AcqRel);
+            tracing::error!("Storage loop has shut down, a change was not saved");
+        }
+    }
+
+    /// Waits while the storage is too far behind, so a slow storage slows stations down
+    /// rather than queueing their changes without limit.
+    pub(crate) async fn ready(&self) {
+        loop {
+            let drained = self.backlog.drained.notified();
+            if self.backlog.len.load(Ordering::Acquire) < QUEUE_LIMIT {
+                return;
+            }
+            drained.await;
+        }
+    }
+
+    /// Waits until the changes queued so far are written.
+    pub(crate) async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        self.save(|| ToStorage::Flush(done));
+        drop(written.await);
+    }
+}
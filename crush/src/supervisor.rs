@@ -0,0 +1,125 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc::Receiver, watch, Mutex as AsyncMutex, OwnedMutexGuard},
+    task::{AbortHandle, JoinHandle},
+    time::{sleep, Instant},
+};
+
+/// How long the supervisor waits before restarting a crashed actor, doubled for every other
+/// crash within [`RESTART_WINDOW`].
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+
+/// How many times an actor may crash within [`RESTART_WINDOW`] before the supervisor gives up
+/// on it.
+const MAX_RESTARTS: usize = 5;
+
+const RESTART_WINDOW: Duration = Duration::from_mins(1);
+
+/// The mailbox of a supervised actor, which outlives the tasks the actor runs in.
+///
+/// Only one task holds it at a time. It is released when the task ends, however it ends, so
+/// messages sent while the actor is restarted wait for the next task.
+pub(crate) type Mailbox<M> = OwnedMutexGuard<Receiver<M>>;
+
+/// Extracts the message a panic was raised with, for logging a crashed actor.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// A handle to an actor run by [`supervise`].
+#[derive(Debug, Clone)]
+pub(crate) struct Supervised {
+    task: Arc<Mutex<Option<AbortHandle>>>,
+    restarts: watch::Receiver<usize>,
+}
+
+impl Supervised {
+    /// Aborts the task the actor currently runs in, as if it crashed.
+    pub(crate) fn abort(&self) {
+        if let Some(task) = &*self.task.lock().unwrap_or_else(PoisonError::into_inner) {
+            task.abort();
+        }
+    }
+
+    /// Changes every time the actor was restarted.
+    pub(crate) fn restarts(&self) -> watch::Receiver<usize> {
+        let mut restarts = self.restarts.clone();
+        restarts.mark_unchanged();
+        restarts
+    }
+}
+
+/// Runs the actor `start` returns on the mailbox `receiver`, and starts it again with fresh
+/// state whenever its task panics or is aborted.
+///
+/// The message the actor was handling when it died is lost, the ones queued after it are
+/// handled by the restarted actor. Restarts are delayed by an exponential backoff, and an actor
+/// that crashes more than [`MAX_RESTARTS`] times within [`RESTART_WINDOW`] is not restarted
+/// anymore: its mailbox is dropped, so senders see it as closed. Supervision also ends with the
+/// actor once it returns, which it does when its mailbox is closed.
+pub(crate) fn supervise<M, F, Fut>(
+    name: String,
+    receiver: Receiver<M>,
+    mut start: F,
+) -> (Supervised, JoinHandle<()>)
+where
+    M: Send + 'static,
+    F: FnMut(Mailbox<M>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mailbox = Arc::new(AsyncMutex::new(receiver));
+    let (restarted, restarts) = watch::channel(0);
+    let supervised = Supervised {
+        task: Arc::default(),
+        restarts,
+    };
+
+    let task = Arc::clone(&supervised.task);
+    let supervisor_join = tokio::spawn(async move {
+        let mut crashes = VecDeque::new();
+        loop {
+            let actor = tokio::spawn(start(Arc::clone(&mailbox).lock_owned().await));
+            *task.lock().unwrap_or_else(PoisonError::into_inner) = Some(actor.abort_handle());
+            let Err(error) = actor.await else {
+                return;
+            };
+            if error.is_panic() {
+                tracing::error!(
+                    "{name} panicked: {}",
+                    panic_message(error.into_panic().as_ref())
+                );
+            } else {
+                tracing::error!("{name} was aborted");
+            }
+
+            let now = Instant::now();
+            crashes.retain(|crashed_at| now.duration_since(*crashed_at) < RESTART_WINDOW);
+            crashes.push_back(now);
+            if crashes.len() > MAX_RESTARTS {
+                tracing::error!(
+                    "{name} crashed {} times within {RESTART_WINDOW:?}, giving up on it",
+                    crashes.len()
+                );
+                return;
+            }
+            let backoff = INITIAL_BACKOFF.saturating_mul(1 << (crashes.len() - 1));
+            tracing::info!("Restarting {name} in {backoff:?}");
+            sleep(backoff).await;
+            restarted.send_modify(|count| *count += 1);
+        }
+    });
+
+    (supervised, supervisor_join)
+}
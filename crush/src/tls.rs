@@ -0,0 +1,247 @@
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    version::{TLS12, TLS13},
+    RootCertStore, ServerConfig,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, time::sleep};
+use tokio_rustls::TlsAcceptor;
+
+use crate::error::TlsError;
+
+/// The cipher suites the OCPP 1.6 security whitepaper requires a Central System to offer.
+///
+/// The whitepaper lists the RSA key exchange variants for servers with an RSA certificate,
+/// which rustls does not implement, so their ECDHE counterparts are offered instead.
+const CIPHER_SUITES: [rustls::SupportedCipherSuite; 6] = [
+    ring::cipher_suite::TLS13_AES_256_GCM_SHA384,
+    ring::cipher_suite::TLS13_AES_128_GCM_SHA256,
+    ring::cipher_suite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+    ring::cipher_suite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+    ring::cipher_suite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+    ring::cipher_suite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+];
+
+/// Where the server certificate for TLS, OCPP Security Profile 2, is loaded from.
+///
+/// # Examples
+///
+/// ```rust
+/// # use crush::{Config, TlsConfig};
+/// # use std::time::Duration;
+/// let tls = TlsConfig::from_pem_files("/etc/crush/server.crt", "/etc/crush/server.key")
+///     .with_reload_interval(Duration::from_secs(300));
+/// let config = Config::new("0.0.0.0:443".parse().unwrap()).with_tls(tls);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    certificate: PathBuf,
+    private_key: PathBuf,
+    client_ca: Option<PathBuf>,
+    reload_interval: Option<Duration>,
+}
+
+impl TlsConfig {
+    /// Loads the certificate chain and its private key from PEM files.
+    ///
+    /// The chain starts with the server certificate, followed by any intermediate CA
+    /// certificates. The key may be a PKCS#8, PKCS#1 or SEC1 private key.
+    #[must_use]
+    pub fn from_pem_files(
+        certificate: impl Into<PathBuf>,
+        private_key: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            certificate: certificate.into(),
+            private_key: private_key.into(),
+            client_ca: None,
+            reload_interval: Some(Duration::from_mins(1)),
+        }
+    }
+
+    /// Requires stations to present a client certificate, OCPP Security Profile 3.
+    ///
+    /// The chain must lead to one of the CA certificates in the PEM bundle at `path`, and the
+    /// common name or serial number attribute of its subject must be the station id from the
+    /// connection path. Stations failing the handshake are dropped, stations connecting with
+    /// another station's certificate are refused with `403 Forbidden`. The bundle is reloaded
+    /// together with the server certificate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use crush::{Config, TlsConfig};
+    /// let tls = TlsConfig::from_pem_files("/etc/crush/server.crt", "/etc/crush/server.key")
+    ///     .with_client_ca("/etc/crush/charge-point-ca.crt");
+    /// let config = Config::new("0.0.0.0:443".parse().unwrap()).with_tls(tls);
+    /// ```
+    #[must_use]
+    pub fn with_client_ca(mut self, path: impl Into<PathBuf>) -> Self {
+        self.client_ca = Some(path.into());
+        self
+    }
+
+    /// Sets how often the files are checked for changes, 60 seconds by default.
+    ///
+    /// A changed certificate is used for new connections, sessions that are already open keep
+    /// the certificate they were established with. A `Duration::ZERO` interval disables the
+    /// check, [`crate::CrushHandle::reload_tls`] still reloads the files on demand.
+    #[must_use]
+    pub fn with_reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = (!interval.is_zero()).then_some(interval);
+        self
+    }
+
+    pub(crate) fn requires_client_certificate(&self) -> bool {
+        self.client_ca.is_some()
+    }
+
+    fn files(&self) -> impl Iterator<Item = &Path> {
+        [
+            Some(&self.certificate),
+            Some(&self.private_key),
+            self.client_ca.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::as_path)
+    }
+}
+
+/// The TLS state shared by the accept loop and [`crate::CrushHandle`].
+pub(crate) struct Tls {
+    config: TlsConfig,
+    provider: Arc<CryptoProvider>,
+    acceptor: RwLock<Option<TlsAcceptor>>,
+}
+
+impl Tls {
+    /// Prepares TLS without loading the certificate yet, see [`Tls::reload`].
+    pub(crate) fn new(config: TlsConfig) -> Self {
+        let provider = Arc::new(CryptoProvider {
+            cipher_suites: CIPHER_SUITES.to_vec(),
+            ..ring::default_provider()
+        });
+        Self {
+            config,
+            provider,
+            acceptor: RwLock::default(),
+        }
+    }
+
+    /// The acceptor for new connections, `None` until the certificate has been loaded.
+    pub(crate) fn acceptor(&self) -> Option<TlsAcceptor> {
+        self.acceptor
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Loads the certificate files, keeping the previous certificate if that fails.
+    ///
+    /// Connections that are already established are not affected, only handshakes that start
+    /// afterwards use the reloaded files.
+    pub(crate) async fn reload(&self) -> Result<(), TlsError> {
+        let chain = certificates(&self.config.certificate).await?;
+        let private_key = read(&self.config.private_key).await?;
+        let key = PrivateKeyDer::from_pem_slice(&private_key)
+            .map_err(|error| TlsError::pem(&self.config.private_key, &error))?;
+
+        let builder = ServerConfig::builder_with_provider(Arc::clone(&self.provider))
+            .with_protocol_versions(&[&TLS13, &TLS12])
+            .expect("the cipher suites cover TLS 1.2 and 1.3");
+        let builder = match &self.config.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in certificates(path).await? {
+                    roots.add(certificate)?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(roots),
+                    Arc::clone(&self.provider),
+                )
+                .build()
+                .map_err(|error| TlsError::ClientCa {
+                    path: path.clone(),
+                    reason: error.to_string(),
+                })?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let server_config = builder.with_single_cert(chain, key)?;
+
+        *self
+            .acceptor
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) =
+            Some(TlsAcceptor::from(Arc::new(server_config)));
+        tracing::info!(
+            "Loaded TLS certificate from {}",
+            self.config.certificate.display()
+        );
+        Ok(())
+    }
+
+    /// Reloads the certificate whenever one of its files changes.
+    #[allow(
+        clippy::infinite_loop,
+        reason = "Watching the files only stops when the runtime shuts down"
+    )]
+    pub(crate) async fn watch(self: Arc<Self>) {
+        let Some(interval) = self.config.reload_interval else {
+            return;
+        };
+
+        let mut loaded = self.modified().await;
+        loop {
+            sleep(interval).await;
+            let modified = self.modified().await;
+            if modified == loaded {
+                continue;
+            }
+            match self.reload().await {
+                Ok(()) => loaded = modified,
+                Err(error) => tracing::error!("Failed to reload TLS certificate: {error}"),
+            }
+        }
+    }
+
+    async fn modified(&self) -> Vec<Option<SystemTime>> {
+        let mut modified = Vec::new();
+        for path in self.config.files() {
+            modified.push(
+                fs::metadata(path)
+                    .await
+                    .ok()
+                    .and_then(|metadata| metadata.modified().ok()),
+            );
+        }
+        modified
+    }
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).await.map_err(|source| TlsError::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Reads all certificates of a PEM file, failing if there are none.
+async fn certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let pem = read(path).await?;
+    let certificates = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| TlsError::pem(path, &error))?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_owned()));
+    }
+    Ok(certificates)
+}
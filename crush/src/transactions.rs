@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::{
+    messages::{
+        meter_values::MeterValuesRequest, start_transaction::StartTransactionRequest,
+        stop_transaction::StopTransactionRequest,
+    },
+    types::{MeterValue, Reason},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::storage_loop::{StorageHandle, ToStorage, Written};
+
+/// How many transactions that stopped or were orphaned are kept in memory. Older ones are
+/// forgotten, a [`crate::Storage`] keeps all of them.
+const ENDED_LIMIT: usize = 1_024;
+
+/// A charging session, from its `StartTransaction` to its `StopTransaction`.
+///
+/// See [`crate::CrushHandle::transaction`] and [`crate::CrushHandle::transactions`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    id: i32,
+    station_id: String,
+    connector_id: u32,
+    id_tag: String,
+    reservation_id: Option<i32>,
+    meter_start: i32,
+    started_at: DateTime<Utc>,
+    meter_values: Vec<MeterValue>,
+    stop: Option<TransactionStop>,
+    orphaned_at: Option<DateTime<Utc>>,
+}
+
+impl Transaction {
+    /// The id crush allocated in its answer to `StartTransaction`.
+    #[must_use]
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    #[must_use]
+    pub fn station_id(&self) -> &str {
+        &self.station_id
+    }
+
+    #[must_use]
+    pub fn connector_id(&self) -> u32 {
+        self.connector_id
+    }
+
+    /// The id tag that started the transaction.
+    #[must_use]
+    pub fn id_tag(&self) -> &str {
+        &self.id_tag
+    }
+
+    /// The reservation the transaction was started on, if any.
+    #[must_use]
+    pub fn reservation_id(&self) -> Option<i32> {
+        self.reservation_id
+    }
+
+    /// The energy meter reading in Wh when the transaction started.
+    #[must_use]
+    pub fn meter_start(&self) -> i32 {
+        self.meter_start
+    }
+
+    /// When the transaction started according to the station.
+    #[must_use]
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// The meter values sent with `MeterValues` and with the `StopTransaction`, oldest first.
+    #[must_use]
+    pub fn meter_values(&self) -> &[MeterValue] {
+        &self.meter_values
+    }
+
+    /// How the transaction ended, `None` while it is ongoing or orphaned.
+    #[must_use]
+    pub fn stop(&self) -> Option<&TransactionStop> {
+        self.stop.as_ref()
+    }
+
+    /// When crush noticed the station lost the transaction, see
+    /// [`crate::EventKind::TransactionOrphaned`].
+    #[must_use]
+    pub fn orphaned_at(&self) -> Option<DateTime<Utc>> {
+        self.orphaned_at
+    }
+
+    /// Whether the transaction has neither stopped nor been orphaned.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.stop.is_none() && self.orphaned_at.is_none()
+    }
+
+    /// The energy charged in Wh, `meterStop - meterStart`, once the transaction stopped.
+    #[must_use]
+    pub fn energy(&self) -> Option<i64> {
+        self.stop
+            .as_ref()
+            .map(|stop| i64::from(stop.meter_stop) - i64::from(self.meter_start))
+    }
+
+    pub(crate) fn add_meter_values(&mut self, meter_values: impl IntoIterator<Item = MeterValue>) {
+        self.meter_values.extend(meter_values);
+    }
+}
+
+/// How a transaction ended, taken from its `StopTransaction`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionStop {
+    id_tag: Option<String>,
+    meter_stop: i32,
+    stopped_at: DateTime<Utc>,
+    reason: Option<Reason>,
+}
+
+impl TransactionStop {
+    /// The id tag that stopped the transaction, if the station sent one.
+    #[must_use]
+    pub fn id_tag(&self) -> Option<&str> {
+        self.id_tag.as_deref()
+    }
+
+    /// The energy meter reading in Wh when the transaction stopped.
+    #[must_use]
+    pub fn meter_stop(&self) -> i32 {
+        self.meter_stop
+    }
+
+    /// When the transaction stopped according to the station.
+    #[must_use]
+    pub fn stopped_at(&self) -> DateTime<Utc> {
+        self.stopped_at
+    }
+
+    /// Why the transaction stopped, OCPP implies `Local` if the station did not say.
+    #[must_use]
+    pub fn reason(&self) -> Option<&Reason> {
+        self.reason.as_ref()
+    }
+}
+
+/// The active transactions and the [`ENDED_LIMIT`] that ended last, shared by all sessions.
+///
+/// Transaction ids are allocated in increasing order and never reused, so they also order the
+/// transactions by when they started. Changed transactions are queued for the storage, starts
+/// and stops are saved before the station is answered so no id it was given can be reused.
+/// Meter values are saved on their own, so a long transaction is not saved over and over.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Transactions {
+    inner: Arc<Mutex<Inner>>,
+    storage: StorageHandle,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    last_id: i32,
+    transactions: BTreeMap<i32, Transaction>,
+    /// The transactions that stopped or were orphaned, in the order they ended.
+    ended: VecDeque<i32>,
+}
+
+impl Inner {
+    /// Remembers that transaction `id` ended, forgetting the one that ended first if there are
+    /// too many.
+    fn ended(&mut self, id: i32) {
+        self.ended.push_back(id);
+        while self.ended.len() > ENDED_LIMIT {
+            if let Some(forgotten) = self.ended.pop_front() {
+                self.transactions.remove(&forgotten);
+            }
+        }
+    }
+}
+
+impl Transactions {
+    pub(crate) fn new(storage: StorageHandle) -> Self {
+        Self {
+            inner: Arc::default(),
+            storage,
+        }
+    }
+
+    /// Takes over the transactions the storage loaded, allocating ids after theirs.
+    pub(crate) fn restore(&self, transactions: Vec<Transaction>) {
+        let mut inner = self.lock();
+        for transaction in transactions {
+            inner.last_id = inner.last_id.max(transaction.id);
+            inner.transactions.insert(transaction.id, transaction);
+        }
+        let ended = inner
+            .transactions
+            .values()
+            .filter(|transaction| !transaction.is_active())
+            .map(Transaction::id)
+            .collect::<Vec<_>>();
+        for id in ended {
+            inner.ended(id);
+        }
+    }
+
+    /// Allocates the id for a `StartTransaction` that is being answered.
+    pub(crate) fn allocate_id(&self) -> i32 {
+        let mut inner = self.lock();
+        inner.last_id = inner.last_id.saturating_add(1);
+        inner.last_id
+    }
+
+    /// Records the start of transaction `id` and orphans any active transaction of the
+    /// connector, which the station must have lost.
+    pub(crate) fn start(
+        &self,
+        station_id: &str,
+        id: i32,
+        request: &StartTransactionRequest,
+    ) -> (Transaction, Vec<Transaction>, Written) {
+        let transaction = Transaction {
+            id,
+            station_id: station_id.to_owned(),
+            connector_id: request.connector_id,
+            id_tag: request.id_tag.clone(),
+            reservation_id: request.reservation_id,
+            meter_start: request.meter_start,
+            started_at: request.timestamp,
+            meter_values: Vec::new(),
+            stop: None,
+            orphaned_at: None,
+        };
+        let mut inner = self.lock();
+        let orphaned = orphan(&mut inner, |active| {
+            active.station_id == station_id && active.connector_id == request.connector_id
+        });
+        inner.transactions.insert(id, transaction.clone());
+        let written = self.storage.save_durably(|| {
+            let mut changed = orphaned.clone();
+            changed.push(transaction.clone());
+            ToStorage::Transactions(changed)
+        });
+        (transaction, orphaned, written)
+    }
+
+    /// Adds the meter values of a `MeterValues` to its transaction, returning whether there is
+    /// one.
+    pub(crate) fn meter_values(&self, station_id: &str, request: &MeterValuesRequest) -> bool {
+        let Some(id) = request.transaction_id else {
+            return false;
+        };
+        let mut inner = self.lock();
+        match inner.transactions.get_mut(&id) {
+            Some(transaction) if transaction.station_id == station_id => {
+                transaction.add_meter_values(request.meter_value.iter().cloned());
+                self.storage.save(|| ToStorage::MeterValues {
+                    transaction_id: id,
+                    meter_values: request.meter_value.clone(),
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Closes the transaction of a `StopTransaction`, returning it unless it is unknown or
+    /// already stopped.
+    pub(crate) fn stop(
+        &self,
+        station_id: &str,
+        request: &StopTransactionRequest,
+    ) -> Option<(Transaction, Written)> {
+        let mut inner = self.lock();
+        let transaction =
+            inner
+                .transactions
+                .get_mut(&request.transaction_id)
+                .filter(|transaction| {
+                    transaction.station_id == station_id && transaction.stop.is_none()
+                })?;
+        if let Some(transaction_data) = &request.transaction_data {
+            transaction.add_meter_values(transaction_data.iter().cloned());
+        }
+        // An orphaned transaction ended before.
+        let ended = transaction.is_active();
+        transaction.stop = Some(TransactionStop {
+            id_tag: request.id_tag.clone(),
+            meter_stop: request.meter_stop,
+            stopped_at: request.timestamp,
+            reason: request.reason.clone(),
+        });
+        let transaction = transaction.clone();
+        let written = self
+            .storage
+            .save_durably(|| ToStorage::Transactions(vec![transaction.clone()]));
+        if ended {
+            inner.ended(transaction.id);
+        }
+        Some((transaction, written))
+    }
+
+    /// Orphans the active transactions of a station that booted, returning them.
+    pub(crate) fn rebooted(&self, station_id: &str) -> Vec<Transaction> {
+        let mut inner = self.lock();
+        let orphaned = orphan(&mut inner, |active| active.station_id == station_id);
+        if !orphaned.is_empty() {
+            self.storage
+                .save(|| ToStorage::Transactions(orphaned.clone()));
+        }
+        orphaned
+    }
+
+    pub(crate) fn get(&self, id: i32) -> Option<Transaction> {
+        self.lock().transactions.get(&id).cloned()
+    }
+
+    /// The transactions of `station_id`, or of all stations, oldest first.
+    pub(crate) fn list(&self, station_id: Option<&str>) -> Vec<Transaction> {
+        self.lock()
+            .transactions
+            .values()
+            .filter(|transaction| {
+                station_id.is_none_or(|station_id| transaction.station_id == station_id)
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Marks the active transactions `select` picks orphaned.
+fn orphan(inner: &mut Inner, select: impl Fn(&Transaction) -> bool) -> Vec<Transaction> {
+    let now = Utc::now();
+    let orphaned = inner
+        .transactions
+        .values_mut()
+        .filter(|transaction| transaction.is_active() && select(transaction))
+        .map(|transaction| {
+            transaction.orphaned_at = Some(now);
+            transaction.clone()
+        })
+        .collect::<Vec<_>>();
+    for transaction in &orphaned {
+        inner.ended(transaction.id);
+    }
+    orphaned
+}
@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use crush::{
+    actions::{Authorize, Heartbeat, RemoteStartTransaction, SignCertificate, StartTransaction},
+    chrono::{TimeZone, Utc},
+    rust_ocpp::v1_6::{
+        messages::{
+            heart_beat::{HeartbeatRequest, HeartbeatResponse},
+            start_transaction::{StartTransactionRequest, StartTransactionResponse},
+        },
+        types::{AuthorizationStatus, IdTagInfo},
+    },
+    Action, HandleAction, OcppResult, StationContext,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::common::{self, error_code, message_type, Station};
+
+#[derive(Serialize, Deserialize)]
+struct VendorPingRequest {
+    nonce: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VendorPingResponse {
+    nonce: u32,
+}
+
+struct VendorPing;
+
+impl Action for VendorPing {
+    const NAME: &'static str = "VendorPing";
+    type Request = VendorPingRequest;
+    type Response = VendorPingResponse;
+}
+
+struct EchoHandler;
+
+#[async_trait]
+impl HandleAction<VendorPing> for EchoHandler {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        request: VendorPingRequest,
+    ) -> OcppResult<VendorPingResponse> {
+        Ok(VendorPingResponse {
+            nonce: request.nonce,
+        })
+    }
+}
+
+/// Answers every `StartTransaction` with the same transaction id.
+struct FixedIdHandler;
+
+#[async_trait]
+impl HandleAction<StartTransaction> for FixedIdHandler {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        _request: StartTransactionRequest,
+    ) -> OcppResult<StartTransactionResponse> {
+        Ok(StartTransactionResponse {
+            id_tag_info: IdTagInfo {
+                expiry_date: None,
+                parent_id_tag: None,
+                status: AuthorizationStatus::Accepted,
+            },
+            transaction_id: 7,
+        })
+    }
+}
+
+#[test]
+fn marker_types_carry_the_action_name() {
+    assert_eq!(Authorize::NAME, "Authorize");
+    assert_eq!(RemoteStartTransaction::NAME, "RemoteStartTransaction");
+    assert_eq!(SignCertificate::NAME, "SignCertificate");
+}
+
+#[tokio::test]
+async fn custom_actions_are_dispatched_to_their_handler() {
+    let server =
+        common::start(|builder| builder.with_action_handler::<VendorPing, _>(EchoHandler)).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("VendorPing", json!({ "nonce": 42 })).await;
+    assert_eq!(message_type(&response), Some(3), "expected a CALLRESULT");
+    assert_eq!(response.get(2), Some(&json!({ "nonce": 42 })));
+
+    let malformed = station.call("VendorPing", json!({ "nonce": "42" })).await;
+    assert_eq!(error_code(&malformed), Some("FormationViolation"));
+}
+
+#[tokio::test]
+async fn registered_handlers_replace_the_default_ones() {
+    let server = common::start(|builder| {
+        builder.on_action::<Heartbeat, _>(|_context, _request: HeartbeatRequest| async {
+            Ok(HeartbeatResponse {
+                current_time: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            })
+        })
+    })
+    .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("Heartbeat", json!({})).await;
+    assert_eq!(
+        response
+            .get(2)
+            .and_then(|payload| payload.get("currentTime"))
+            .and_then(Value::as_str),
+        Some("2024-05-01T12:00:00Z"),
+        "default handler answered"
+    );
+}
+
+#[tokio::test]
+async fn calls_without_payload_are_answered() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    station.send(json!([2, "1", "Heartbeat"])).await;
+    let response = station.receive().await;
+    assert_eq!(message_type(&response), Some(3), "expected a CALLRESULT");
+    assert_eq!(
+        response.as_array().map(Vec::len),
+        Some(3),
+        "CALLRESULT is [3, uniqueId, payload]"
+    );
+    assert!(
+        response
+            .get(2)
+            .and_then(|payload| payload.get("currentTime"))
+            .is_some(),
+        "no current time in {response}"
+    );
+}
+
+#[tokio::test]
+async fn crush_allocates_transaction_ids_for_replaced_handlers() {
+    let server =
+        common::start(|builder| builder.with_action_handler::<StartTransaction, _>(FixedIdHandler))
+            .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let mut ids = Vec::new();
+    for connector_id in [1, 2] {
+        let response = station
+            .call(
+                "StartTransaction",
+                json!({
+                    "connectorId": connector_id,
+                    "idTag": "TAG1",
+                    "meterStart": 0,
+                    "timestamp": "2024-05-01T12:00:00Z"
+                }),
+            )
+            .await;
+        let id = response
+            .get(2)
+            .and_then(|payload| payload.get("transactionId"))
+            .and_then(Value::as_i64)
+            .and_then(|id| i32::try_from(id).ok())
+            .expect("no transaction id in the response");
+        ids.push(id);
+    }
+
+    assert_ne!(ids.first(), ids.get(1), "transaction ids were reused");
+    for id in ids {
+        let transaction = server
+            .handle
+            .transaction(id)
+            .expect("transaction was not recorded");
+        assert_eq!(transaction.station_id(), "CP1");
+    }
+}
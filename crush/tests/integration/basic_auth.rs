@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crush::InMemoryCredentialStore;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+use crate::common::{self, Server, Station};
+
+const PASSWORD: &[u8] = b"0123456789abcdef0123";
+
+pub(crate) fn basic(username: &str, password: &[u8]) -> (&'static str, String) {
+    let mut credentials = format!("{username}:").into_bytes();
+    credentials.extend_from_slice(password);
+    (
+        "Authorization",
+        format!("Basic {}", STANDARD.encode(credentials)),
+    )
+}
+
+async fn start() -> Server {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.set_password("CP1", PASSWORD);
+    credentials.set_password("CP2", b"another password");
+    common::start(|builder| builder.with_credential_store(credentials)).await
+}
+
+#[tokio::test]
+async fn stations_with_valid_credentials_connect() {
+    let server = start().await;
+
+    let _station = Station::try_connect_with(server.address, "CP1", &[basic("CP1", PASSWORD)])
+        .await
+        .expect("CP1 was refused");
+    server.wait_for_stations(&["CP1"]).await;
+}
+
+#[tokio::test]
+async fn stations_without_valid_credentials_are_refused() {
+    let server = start().await;
+
+    let attempts = [
+        ("CP1", vec![]),
+        ("CP1", vec![basic("CP1", b"wrong password")]),
+        ("CP1", vec![basic("CP2", b"another password")]),
+        ("CP3", vec![basic("CP3", PASSWORD)]),
+        ("CP1", vec![("Authorization", "Bearer token".to_owned())]),
+    ];
+    for (station_id, headers) in attempts {
+        let attempt = Station::try_connect_with(server.address, station_id, &headers).await;
+        assert_eq!(
+            common::refusal_status(attempt),
+            StatusCode::UNAUTHORIZED,
+            "{station_id} with {headers:?}"
+        );
+    }
+    assert!(
+        server.handle.connected_stations().await.is_empty(),
+        "unauthenticated stations were registered"
+    );
+}
+
+#[tokio::test]
+async fn requests_without_credentials_are_refused_by_default() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    let server = common::start(|builder| builder.with_credential_store(credentials)).await;
+
+    for station_id in ["CP1", "CP2"] {
+        let attempt = Station::try_connect(server.address, station_id).await;
+        assert_eq!(
+            common::refusal_status(attempt),
+            StatusCode::UNAUTHORIZED,
+            "{station_id} connected without credentials"
+        );
+    }
+}
+
+#[tokio::test]
+async fn unsecured_stations_connect_without_credentials_once_enabled() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    credentials.set_password("CP2", PASSWORD);
+    let server = common::start_with_config(
+        |config| config.with_unsecured_stations(true),
+        |builder| builder.with_credential_store(credentials),
+    )
+    .await;
+
+    let _unsecured = Station::try_connect(server.address, "CP1")
+        .await
+        .expect("CP1 was refused");
+    let attempt = Station::try_connect(server.address, "CP2").await;
+    assert_eq!(
+        common::refusal_status(attempt),
+        StatusCode::UNAUTHORIZED,
+        "CP2 has a password and connected without it"
+    );
+    server.wait_for_stations(&["CP1"]).await;
+}
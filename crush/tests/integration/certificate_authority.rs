@@ -0,0 +1,195 @@
+use std::{fs, sync::Arc, time::Duration};
+
+use crush::{
+    actions::SignCertificate,
+    security::{GenericStatus, SignCertificateResponse},
+    CertificateAuthority, CrushBuilder,
+};
+use rcgen::{CertificateParams, DnType, KeyPair};
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    ClientConfig, RootCertStore,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    common::{self, Server, Station},
+    mutual_tls::Authority,
+    tls::{certificate, CertificateFiles},
+};
+
+const ORGANIZATION: &str = "ACME Charging";
+
+fn certificate_authority(authority: &Authority) -> CertificateAuthority {
+    CertificateAuthority::from_pem(
+        &authority.certificate.pem(),
+        &authority.key_pair.serialize_pem(),
+    )
+    .expect("failed to load CA")
+    .with_organization(ORGANIZATION)
+    .with_validity(Duration::from_hours(24))
+}
+
+async fn start(authority: &Authority) -> Server {
+    let certificate_authority = certificate_authority(authority);
+    common::start(|builder| builder.with_certificate_authority(certificate_authority)).await
+}
+
+fn rejecting_handler(builder: CrushBuilder) -> CrushBuilder {
+    builder.on_action::<SignCertificate, _>(|_context, _request| async move {
+        Ok(SignCertificateResponse {
+            status: GenericStatus::Rejected,
+        })
+    })
+}
+
+/// A PEM encoded CSR for the given subject attributes.
+fn csr(key_pair: &KeyPair, subject: &[(DnType, &str)]) -> String {
+    let mut params = CertificateParams::new(Vec::new()).expect("invalid CSR parameters");
+    for (attribute, value) in subject {
+        params
+            .distinguished_name
+            .push(attribute.clone(), value.to_owned());
+    }
+    params
+        .serialize_request(key_pair)
+        .expect("failed to create CSR")
+        .pem()
+        .expect("failed to encode CSR")
+}
+
+#[tokio::test]
+async fn signed_certificates_are_delivered_after_the_response() {
+    let authority = Authority::new();
+    let server = start(&authority).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    let key_pair = KeyPair::generate().expect("failed to generate station key");
+
+    let request = csr(
+        &key_pair,
+        &[
+            (DnType::CommonName, "CP1"),
+            (DnType::OrganizationName, ORGANIZATION),
+        ],
+    );
+    let signing = station
+        .call("SignCertificate", json!({ "csr": request }))
+        .await;
+    assert_eq!(signing, json!([3, "1", { "status": "Accepted" }]));
+
+    let (action, payload) = station.answer(json!({ "status": "Accepted" })).await;
+    assert_eq!(action, "CertificateSigned");
+    let chain = payload
+        .get("certificateChain")
+        .and_then(Value::as_str)
+        .expect("CertificateSigned without a chain");
+    let certificates = CertificateDer::pem_slice_iter(chain.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .expect("chain is not PEM");
+    assert_eq!(certificates.len(), 2, "chain is not leaf and CA");
+    assert_eq!(certificates.get(1), Some(authority.certificate.der()));
+
+    // The signed certificate authenticates the station under Security Profile 3.
+    let files = CertificateFiles::new();
+    let server_key = certificate();
+    files.write(&server_key);
+    fs::write(files.client_ca(), authority.certificate.pem()).expect("failed to write CA");
+    let tls = files
+        .config()
+        .with_client_ca(files.client_ca())
+        .with_reload_interval(Duration::ZERO);
+    let tls_server =
+        common::start_with_config(|config| config.with_tls(tls), |builder| builder).await;
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(server_key.cert.der().clone())
+        .expect("failed to trust certificate");
+    let key = PrivateKeyDer::try_from(key_pair.serialize_der()).expect("invalid station key");
+    let client = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("invalid client protocol versions")
+        .with_root_certificates(roots)
+        .with_client_auth_cert(certificates, key)
+        .expect("invalid client certificate");
+    Station::try_connect_tls(tls_server.address, "CP1", Arc::new(client))
+        .await
+        .expect("station with the signed certificate was refused");
+}
+
+#[tokio::test]
+async fn requests_not_matching_the_station_are_rejected() {
+    let server = start(&Authority::new()).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    let key_pair = KeyPair::generate().expect("failed to generate station key");
+
+    let other_station = csr(
+        &key_pair,
+        &[
+            (DnType::CommonName, "CP2"),
+            (DnType::OrganizationName, ORGANIZATION),
+        ],
+    );
+    let other_organization = csr(
+        &key_pair,
+        &[
+            (DnType::CommonName, "CP1"),
+            (DnType::OrganizationName, "Other Charging"),
+        ],
+    );
+    let malformed = "-----BEGIN CERTIFICATE REQUEST-----\nMIIB\n-----END CERTIFICATE REQUEST-----";
+
+    for request in [other_station.as_str(), &other_organization, malformed] {
+        let signing = station
+            .call("SignCertificate", json!({ "csr": request }))
+            .await;
+        assert_eq!(
+            signing.get(2),
+            Some(&json!({ "status": "Rejected" })),
+            "accepted {request}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_sign_certificate_handler_set_last_answers() {
+    let authority = Authority::new();
+    let key_pair = KeyPair::generate().expect("failed to generate station key");
+    let request = csr(
+        &key_pair,
+        &[
+            (DnType::CommonName, "CP1"),
+            (DnType::OrganizationName, ORGANIZATION),
+        ],
+    );
+
+    let handler_last = certificate_authority(&authority);
+    let rejecting = common::start(|builder| {
+        rejecting_handler(builder.with_certificate_authority(handler_last))
+    })
+    .await;
+    let mut first = Station::connect(rejecting.address, "CP1").await;
+    let rejected = first
+        .call("SignCertificate", json!({ "csr": request }))
+        .await;
+    assert_eq!(
+        rejected.get(2),
+        Some(&json!({ "status": "Rejected" })),
+        "the CA replaced the handler set after it"
+    );
+
+    let authority_last = certificate_authority(&authority);
+    let signing_server = common::start(|builder| {
+        rejecting_handler(builder).with_certificate_authority(authority_last)
+    })
+    .await;
+    let mut second = Station::connect(signing_server.address, "CP1").await;
+    let accepted = second
+        .call("SignCertificate", json!({ "csr": request }))
+        .await;
+    assert_eq!(
+        accepted.get(2),
+        Some(&json!({ "status": "Accepted" })),
+        "the handler replaced the CA set after it"
+    );
+}
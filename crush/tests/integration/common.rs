@@ -0,0 +1,290 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::Duration,
+};
+
+use crush::{Config, CrushBuilder, CrushHandle};
+use futures::{SinkExt, StreamExt};
+use rustls::ClientConfig;
+use serde_json::Value;
+use tokio::{
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+use tokio_tungstenite::{
+    connect_async, connect_async_tls_with_config,
+    tungstenite::{
+        self,
+        client::IntoClientRequest,
+        handshake::client::Request,
+        http::{HeaderValue, StatusCode},
+        protocol::CloseFrame,
+        Message,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port available")
+}
+
+/// A crush instance listening on a local port.
+pub(crate) struct Server {
+    pub(crate) address: SocketAddr,
+    pub(crate) handle: CrushHandle,
+}
+
+impl Server {
+    /// Waits until exactly `expected` are connected, in any order.
+    pub(crate) async fn wait_for_stations(&self, expected: &[&str]) {
+        let mut expected = expected.to_vec();
+        expected.sort_unstable();
+
+        timeout(RECEIVE_TIMEOUT, async {
+            loop {
+                let mut connected = self.handle.connected_stations().await;
+                connected.sort_unstable();
+                if connected == expected {
+                    return;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out waiting for the connected stations");
+    }
+}
+
+/// Starts crush on a free local port with the handlers `configure` registers.
+pub(crate) async fn start(configure: impl FnOnce(CrushBuilder) -> CrushBuilder) -> Server {
+    start_with_config(|config| config, configure).await
+}
+
+/// Like [`start`], additionally adjusting the [`Config`] with `configure_config`.
+pub(crate) async fn start_with_config(
+    configure_config: impl FnOnce(Config) -> Config,
+    configure: impl FnOnce(CrushBuilder) -> CrushBuilder,
+) -> Server {
+    let address = free_address();
+    let config = configure_config(Config::new(address));
+    let crush = configure(CrushBuilder::new(config))
+        .try_build()
+        .await
+        .expect("failed to load the storage");
+    let handle = crush.handle();
+    tokio::spawn(crush.run());
+
+    while TcpStream::connect(address).await.is_err() {
+        sleep(Duration::from_millis(10)).await;
+    }
+    Server { address, handle }
+}
+
+/// A simulated charging station speaking OCPP-J over a WebSocket.
+pub(crate) struct Station {
+    websocket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Station {
+    pub(crate) async fn connect(address: SocketAddr, station_id: &str) -> Self {
+        Self::try_connect(address, station_id)
+            .await
+            .expect("station failed to connect")
+    }
+
+    /// Connects, returning the error if the central system refuses the connection.
+    pub(crate) async fn try_connect(
+        address: SocketAddr,
+        station_id: &str,
+    ) -> Result<Self, tungstenite::Error> {
+        Self::try_connect_with(address, station_id, &[]).await
+    }
+
+    /// Like [`Station::try_connect`], sending additional headers with the upgrade request.
+    pub(crate) async fn try_connect_with(
+        address: SocketAddr,
+        station_id: &str,
+        headers: &[(&'static str, String)],
+    ) -> Result<Self, tungstenite::Error> {
+        let url = format!("ws://{address}/ocpp/{station_id}");
+        let (websocket, _) = connect_async(upgrade_request(&url, headers)).await?;
+        Ok(Self {
+            websocket,
+            next_id: 0,
+        })
+    }
+
+    /// Connects over TLS to `localhost`, trusting the server certificate as `tls` dictates.
+    pub(crate) async fn try_connect_tls(
+        address: SocketAddr,
+        station_id: &str,
+        tls: Arc<ClientConfig>,
+    ) -> Result<Self, tungstenite::Error> {
+        let url = format!("wss://localhost:{}/ocpp/{station_id}", address.port());
+        let (websocket, _) = connect_async_tls_with_config(
+            upgrade_request(&url, &[]),
+            None,
+            false,
+            Some(Connector::Rustls(tls)),
+        )
+        .await?;
+        Ok(Self {
+            websocket,
+            next_id: 0,
+        })
+    }
+
+    /// Sends a raw frame, e.g. the response to a CALL from the central system.
+    pub(crate) async fn send(&mut self, frame: Value) {
+        self.websocket
+            .send(Message::Text(frame.to_string()))
+            .await
+            .expect("failed to send frame");
+    }
+
+    /// Waits for a CALL from the central system and answers it with a CALLRESULT.
+    ///
+    /// Returns the action and payload of the CALL.
+    pub(crate) async fn answer(&mut self, response: Value) -> (String, Value) {
+        let call = self.receive().await;
+        assert_eq!(message_type(&call), Some(2), "expected a CALL");
+        let unique_id = call.get(1).cloned().expect("CALL without unique id");
+        self.send(Value::Array(vec![3.into(), unique_id, response]))
+            .await;
+        let action = call.get(2).and_then(Value::as_str).unwrap_or_default();
+        (action.to_owned(), call.get(3).cloned().unwrap_or_default())
+    }
+
+    /// Sends a CALL and returns the CALLRESULT or CALLERROR frame answering it.
+    pub(crate) async fn call(&mut self, action: &str, payload: Value) -> Value {
+        self.next_id += 1;
+        let unique_id = self.next_id.to_string();
+        let frame = Value::Array(vec![
+            2.into(),
+            unique_id.clone().into(),
+            action.into(),
+            payload,
+        ]);
+        self.websocket
+            .send(Message::Text(frame.to_string()))
+            .await
+            .expect("failed to send CALL");
+
+        let response = self.receive().await;
+        assert_eq!(
+            response.get(1).and_then(Value::as_str),
+            Some(unique_id.as_str()),
+            "response does not answer the CALL"
+        );
+        response
+    }
+
+    /// Closes the connection with a closing handshake.
+    pub(crate) async fn close(mut self) {
+        self.websocket
+            .close(None)
+            .await
+            .expect("failed to send close frame");
+        while let Some(Ok(_)) = self.websocket.next().await {}
+    }
+
+    /// Drops the connection without a closing handshake.
+    pub(crate) fn disconnect(self) {
+        drop(self);
+    }
+
+    /// Resets the TCP connection, as if the station lost power.
+    pub(crate) fn kill(self) {
+        if let MaybeTlsStream::Plain(stream) = self.websocket.get_ref() {
+            stream
+                .set_linger(Some(Duration::ZERO))
+                .expect("failed to set SO_LINGER");
+        }
+        drop(self);
+    }
+
+    /// Keeps reading for `duration`, answering pings, and panics if the connection ends.
+    pub(crate) async fn stay_connected(&mut self, duration: Duration) {
+        let read = async {
+            loop {
+                let message = self
+                    .websocket
+                    .next()
+                    .await
+                    .expect("connection closed")
+                    .expect("failed to read a frame");
+                assert!(!message.is_close(), "connection closed: {message:?}");
+            }
+        };
+        drop(timeout(duration, read).await);
+    }
+
+    /// Waits for the central system to close the connection and returns its close frame.
+    pub(crate) async fn closed(&mut self) -> Option<CloseFrame<'static>> {
+        loop {
+            let message = timeout(RECEIVE_TIMEOUT, self.websocket.next())
+                .await
+                .expect("timed out waiting for the connection to close");
+            match message {
+                Some(Ok(Message::Close(frame))) => return frame,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return None,
+            }
+        }
+    }
+
+    /// Waits for the next text frame from the central system.
+    pub(crate) async fn receive(&mut self) -> Value {
+        loop {
+            let message = timeout(RECEIVE_TIMEOUT, self.websocket.next())
+                .await
+                .expect("timed out waiting for a frame")
+                .expect("connection closed")
+                .expect("failed to read a frame");
+            if let Message::Text(text) = message {
+                return serde_json::from_str(&text).expect("frame is not valid JSON");
+            }
+        }
+    }
+}
+
+/// An upgrade request for `url` offering OCPP 1.6, with additional `headers`.
+fn upgrade_request(url: &str, headers: &[(&'static str, String)]) -> Request {
+    let mut request = url.into_client_request().expect("invalid station url");
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static("ocpp1.6"),
+    );
+    for (name, value) in headers {
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        request.headers_mut().insert(*name, value);
+    }
+    request
+}
+
+/// The status code a refused connection attempt was answered with.
+pub(crate) fn refusal_status(result: Result<Station, tungstenite::Error>) -> StatusCode {
+    let Err(error) = result else {
+        unreachable!("the connection was accepted");
+    };
+    let tungstenite::Error::Http(response) = error else {
+        unreachable!("expected an HTTP error, got {error}");
+    };
+    response.status()
+}
+
+/// The message type id of a frame: 2 for CALL, 3 for CALLRESULT and 4 for CALLERROR.
+pub(crate) fn message_type(frame: &Value) -> Option<u64> {
+    frame.get(0).and_then(Value::as_u64)
+}
+
+/// The error code of a CALLERROR frame.
+pub(crate) fn error_code(frame: &Value) -> Option<&str> {
+    frame.get(2).and_then(Value::as_str)
+}
@@ -0,0 +1,95 @@
+use crush::{
+    actions::DataTransfer,
+    http::StatusCode,
+    rust_ocpp::v1_6::{messages::data_transfer::DataTransferResponse, types::DataTransferStatus},
+    BuildError, Config, ConnectionDecision, CrushBuilder,
+};
+use serde_json::{json, Value};
+
+use crate::common::{self, Station};
+
+#[derive(Clone)]
+struct Tenant(String);
+
+fn known_stations_only(builder: CrushBuilder) -> CrushBuilder {
+    builder
+        .on_connect(|request| async move {
+            let offers_ocpp = request
+                .headers()
+                .get("Sec-WebSocket-Protocol")
+                .is_some_and(|protocol| protocol == "ocpp1.6");
+            match request.station_id() {
+                "CP1" if offers_ocpp => {
+                    ConnectionDecision::accept().with_metadata(Tenant("acme".to_owned()))
+                }
+                "CP2" => ConnectionDecision::reject(StatusCode::FORBIDDEN),
+                _ => ConnectionDecision::reject(StatusCode::NOT_FOUND),
+            }
+        })
+        .on_action::<DataTransfer, _>(|context, _request| async move {
+            Ok(DataTransferResponse {
+                status: DataTransferStatus::Accepted,
+                data: context.metadata::<Tenant>().map(|tenant| tenant.0.clone()),
+            })
+        })
+}
+
+async fn refusal(server: &common::Server, station_id: &str) -> StatusCode {
+    common::refusal_status(Station::try_connect(server.address, station_id).await)
+}
+
+#[tokio::test]
+async fn rejected_stations_never_get_a_session() {
+    let server = common::start(known_stations_only).await;
+
+    assert_eq!(refusal(&server, "CP2").await, StatusCode::FORBIDDEN, "CP2");
+    assert_eq!(refusal(&server, "CP3").await, StatusCode::NOT_FOUND, "CP3");
+    assert!(
+        server.handle.connected_stations().await.is_empty(),
+        "rejected stations were registered"
+    );
+}
+
+#[tokio::test]
+async fn metadata_of_accepted_stations_is_attached_to_the_session() {
+    let server = common::start(known_stations_only).await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let response = station
+        .call("DataTransfer", json!({ "vendorId": "crush" }))
+        .await;
+    assert_eq!(
+        response
+            .get(2)
+            .and_then(|payload| payload.get("data"))
+            .and_then(Value::as_str),
+        Some("acme"),
+        "handler did not see the metadata"
+    );
+
+    let info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 is connected");
+    assert_eq!(
+        info.metadata::<Tenant>().map(|tenant| tenant.0.as_str()),
+        Some("acme"),
+        "registry does not expose the metadata"
+    );
+}
+
+#[tokio::test]
+async fn occupied_address_fails_the_build() {
+    let server = common::start(|builder| builder).await;
+
+    let built = CrushBuilder::new(Config::new(server.address))
+        .try_build()
+        .await;
+    assert!(
+        matches!(built, Err(BuildError::Bind { address, .. }) if address == server.address),
+        "built on an occupied address"
+    );
+}
@@ -0,0 +1,211 @@
+use crush::{
+    rust_ocpp::v1_6::types::{ChargePointErrorCode, ChargePointStatus},
+    ConnectorStatus, EventKind,
+};
+use serde_json::{json, Value};
+
+use crate::common::{self, Station};
+
+/// Waits until crush applied the notifications sent before, which it does before it handles
+/// the next CALL of the station.
+async fn applied(station: &mut Station) {
+    station.call("Heartbeat", json!({})).await;
+}
+
+async fn notify(station: &mut Station, notification: Value) {
+    station.call("StatusNotification", notification).await;
+}
+
+#[tokio::test]
+async fn status_notifications_build_the_connector_table() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 0,
+            "errorCode": "NoError",
+            "status": "Available",
+            "timestamp": "2024-05-01T12:00:00Z"
+        }),
+    )
+    .await;
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 2,
+            "errorCode": "GroundFailure",
+            "status": "Faulted",
+            "info": "RCD tripped",
+            "vendorId": "ACME",
+            "vendorErrorCode": "E42",
+            "timestamp": "2024-05-01T12:00:05Z"
+        }),
+    )
+    .await;
+    notify(
+        &mut station,
+        json!({ "connectorId": 1, "errorCode": "NoError", "status": "Charging" }),
+    )
+    .await;
+    applied(&mut station).await;
+
+    let connectors = server.handle.connectors("CP1");
+    let charge_point = connectors
+        .charge_point()
+        .expect("no status for connector 0");
+    assert_eq!(charge_point.status(), &ChargePointStatus::Available);
+    assert_eq!(connectors.connector(0), Some(charge_point));
+    assert_eq!(
+        connectors
+            .iter()
+            .map(ConnectorStatus::connector_id)
+            .collect::<Vec<_>>(),
+        [1, 2]
+    );
+    let faulted = connectors.connector(2).expect("no status for connector 2");
+    assert_eq!(faulted.status(), &ChargePointStatus::Faulted);
+    assert_eq!(faulted.error_code(), &ChargePointErrorCode::GroundFailure);
+    assert_eq!(faulted.info(), Some("RCD tripped"));
+    assert_eq!(faulted.vendor_id(), Some("ACME"));
+    assert_eq!(faulted.vendor_error_code(), Some("E42"));
+    assert_eq!(
+        faulted.timestamp().map(|timestamp| timestamp.to_rfc3339()),
+        Some("2024-05-01T12:00:05+00:00".to_owned())
+    );
+    assert!(server.handle.connectors("CP2").is_empty());
+}
+
+#[tokio::test]
+async fn stale_notifications_are_ignored() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let preparing = json!({
+        "connectorId": 1,
+        "errorCode": "NoError",
+        "status": "Preparing",
+        "timestamp": "2024-05-01T12:00:00Z"
+    });
+    notify(&mut station, preparing.clone()).await;
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Charging",
+            "timestamp": "2024-05-01T12:01:00Z"
+        }),
+    )
+    .await;
+    // Delivered late, e.g. from the station's offline queue.
+    notify(&mut station, preparing).await;
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Charging",
+            "timestamp": "2024-05-01T12:02:00Z"
+        }),
+    )
+    .await;
+    applied(&mut station).await;
+
+    let mut changes = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EventKind::ConnectorStatusChanged { previous, status } = event.kind() {
+            changes.push((
+                previous.as_ref().map(|previous| previous.status().clone()),
+                status.status().clone(),
+            ));
+        }
+    }
+    assert_eq!(
+        changes,
+        [
+            (None, ChargePointStatus::Preparing),
+            (
+                Some(ChargePointStatus::Preparing),
+                ChargePointStatus::Charging
+            ),
+        ]
+    );
+
+    let connectors = server.handle.connectors("CP1");
+    let charging = connectors.connector(1).expect("no status for connector 1");
+    assert_eq!(charging.status(), &ChargePointStatus::Charging);
+    assert_eq!(
+        charging.timestamp().map(|timestamp| timestamp.to_rfc3339()),
+        Some("2024-05-01T12:02:00+00:00".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn notifications_after_a_boot_replace_newer_ones() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Charging",
+            "timestamp": "2030-01-01T00:00:00Z"
+        }),
+    )
+    .await;
+    // The station rebooted and its clock went back.
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "ACME", "chargePointModel": "One" }),
+        )
+        .await;
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Available",
+            "timestamp": "2024-05-01T12:00:00Z"
+        }),
+    )
+    .await;
+    applied(&mut station).await;
+
+    let connectors = server.handle.connectors("CP1");
+    let available = connectors.connector(1).expect("no status for connector 1");
+    assert_eq!(available.status(), &ChargePointStatus::Available);
+}
+
+#[tokio::test]
+async fn notifications_without_timestamp_are_taken_in_arrival_order() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    notify(
+        &mut station,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Charging",
+            "timestamp": "2030-01-01T00:00:00Z"
+        }),
+    )
+    .await;
+    notify(
+        &mut station,
+        json!({ "connectorId": 1, "errorCode": "NoError", "status": "Finishing" }),
+    )
+    .await;
+    applied(&mut station).await;
+
+    let connectors = server.handle.connectors("CP1");
+    let finishing = connectors.connector(1).expect("no status for connector 1");
+    assert_eq!(finishing.status(), &ChargePointStatus::Finishing);
+    assert_eq!(finishing.timestamp(), None);
+}
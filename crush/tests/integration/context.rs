@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use crush::{
+    chrono::Utc,
+    rust_ocpp::v1_6::messages::heart_beat::{HeartbeatRequest, HeartbeatResponse},
+    ConnectionDecision, HandleHeartbeatRequest, OcppResult, StationContext,
+};
+use serde_json::json;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::common::{self, Station};
+
+#[derive(Clone)]
+struct Tenant(String);
+
+/// Reports the station id and tenant of every heartbeat it answers.
+struct RecordingHeartbeatHandler(UnboundedSender<(String, Option<String>)>);
+
+#[async_trait]
+impl HandleHeartbeatRequest for RecordingHeartbeatHandler {
+    async fn handle(
+        &self,
+        context: StationContext,
+        _request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse> {
+        let tenant = context.metadata::<Tenant>().map(|tenant| tenant.0.clone());
+        self.0
+            .send((context.station_id().to_owned(), tenant))
+            .expect("test stopped listening");
+        Ok(HeartbeatResponse {
+            current_time: Utc::now(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn handlers_see_the_station_that_sent_the_request() {
+    let (sender, mut heartbeats) = unbounded_channel();
+    let server = common::start(|builder| {
+        builder
+            .on_connect(|request| async move {
+                let tenant = format!("tenant of {}", request.station_id());
+                ConnectionDecision::accept().with_metadata(Tenant(tenant))
+            })
+            .with_heartbeat_handler(RecordingHeartbeatHandler(sender))
+    })
+    .await;
+
+    let mut first = Station::connect(server.address, "CP1").await;
+    let mut second = Station::connect(server.address, "CP2").await;
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    for (station, station_id) in [(&mut second, "CP2"), (&mut first, "CP1")] {
+        station.call("Heartbeat", json!({})).await;
+        let (seen_id, seen_tenant) = heartbeats.recv().await.expect("handler was dropped");
+        assert_eq!(seen_id, station_id, "handler saw the wrong station");
+        assert_eq!(
+            seen_tenant,
+            Some(format!("tenant of {station_id}")),
+            "handler saw the wrong metadata"
+        );
+    }
+}
+
+#[tokio::test]
+async fn handlers_see_no_metadata_if_none_was_attached() {
+    let (sender, mut heartbeats) = unbounded_channel();
+    let server =
+        common::start(|builder| builder.with_heartbeat_handler(RecordingHeartbeatHandler(sender)))
+            .await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    station.call("Heartbeat", json!({})).await;
+
+    let (seen_id, seen_tenant) = heartbeats.recv().await.expect("handler was dropped");
+    assert_eq!(seen_id, "CP1", "handler saw the wrong station");
+    assert!(seen_tenant.is_none(), "handler saw metadata");
+}
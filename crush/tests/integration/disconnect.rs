@@ -0,0 +1,59 @@
+use crate::common::{self, Station};
+
+#[tokio::test]
+async fn killed_connections_are_removed_from_the_registry() {
+    let server = common::start(|builder| builder).await;
+
+    let first = Station::connect(server.address, "CP1").await;
+    let second = Station::connect(server.address, "CP2").await;
+    let third = Station::connect(server.address, "CP3").await;
+    server.wait_for_stations(&["CP1", "CP2", "CP3"]).await;
+
+    first.kill();
+    second.kill();
+    server.wait_for_stations(&["CP3"]).await;
+
+    third.kill();
+    server.wait_for_stations(&[]).await;
+}
+
+#[tokio::test]
+async fn connections_dropped_without_closing_handshake_are_removed() {
+    let server = common::start(|builder| builder).await;
+
+    let station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    station.disconnect();
+    server.wait_for_stations(&[]).await;
+}
+
+#[tokio::test]
+async fn closed_connections_are_removed() {
+    let server = common::start(|builder| builder).await;
+
+    let station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    station.close().await;
+    server.wait_for_stations(&[]).await;
+}
+
+#[tokio::test]
+async fn remaining_stations_keep_working_after_others_were_killed() {
+    let server = common::start(|builder| builder).await;
+
+    let killed = Station::connect(server.address, "CP1").await;
+    let mut remaining = Station::connect(server.address, "CP2").await;
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    killed.kill();
+    server.wait_for_stations(&["CP2"]).await;
+
+    let response = remaining.call("Heartbeat", serde_json::json!({})).await;
+    assert_eq!(
+        common::message_type(&response),
+        Some(3),
+        "expected a CALLRESULT"
+    );
+}
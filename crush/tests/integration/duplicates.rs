@@ -0,0 +1,105 @@
+use crush::{
+    actions::Reset,
+    rust_ocpp::v1_6::{messages::reset::ResetRequest, types::ResetResponseStatus},
+    DuplicateConnectionPolicy,
+};
+use serde_json::json;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+use crate::common::{self, Station};
+
+#[tokio::test]
+async fn new_connections_replace_old_ones_by_default() {
+    let server = common::start(|builder| builder).await;
+
+    let mut old = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+    let mut new = Station::connect(server.address, "CP1").await;
+
+    let frame = old.closed().await.expect("expected a close frame");
+    assert!(
+        frame.reason.contains("replaced"),
+        "unexpected close reason: {}",
+        frame.reason
+    );
+    server.wait_for_stations(&["CP1"]).await;
+
+    let response = new.call("Heartbeat", json!({})).await;
+    assert_eq!(
+        common::message_type(&response),
+        Some(3),
+        "expected a CALLRESULT"
+    );
+}
+
+#[tokio::test]
+async fn duplicate_connections_can_be_rejected() {
+    let server = common::start_with_config(
+        |config| config.with_duplicate_connection_policy(DuplicateConnectionPolicy::Reject),
+        |builder| builder,
+    )
+    .await;
+
+    let mut first = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let duplicate = Station::try_connect(server.address, "CP1").await;
+    assert_eq!(
+        common::refusal_status(duplicate),
+        StatusCode::CONFLICT,
+        "unexpected status"
+    );
+
+    let response = first.call("Heartbeat", json!({})).await;
+    assert_eq!(
+        common::message_type(&response),
+        Some(3),
+        "expected a CALLRESULT"
+    );
+}
+
+#[tokio::test]
+async fn outbound_calls_go_to_the_newest_of_both_allowed_sessions() {
+    let server = common::start_with_config(
+        |config| config.with_duplicate_connection_policy(DuplicateConnectionPolicy::AllowBoth),
+        |builder| builder,
+    )
+    .await;
+
+    let mut old = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+    let mut new = Station::connect(server.address, "CP1").await;
+    let response = new.call("Heartbeat", json!({})).await;
+    assert_eq!(
+        common::message_type(&response),
+        Some(3),
+        "expected a CALLRESULT"
+    );
+
+    let (result, (action, _)) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        new.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(action, "Reset", "unexpected action");
+    let reset = result.expect("call failed");
+    assert_eq!(
+        reset.status,
+        ResetResponseStatus::Accepted,
+        "unexpected status"
+    );
+
+    new.close().await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let (result_after_close, (action_after_close, _)) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        old.answer(json!({ "status": "Rejected" })),
+    );
+    assert_eq!(action_after_close, "Reset", "unexpected action");
+    let reset_after_close = result_after_close.expect("call failed");
+    assert_eq!(
+        reset_after_close.status,
+        ResetResponseStatus::Rejected,
+        "unexpected status"
+    );
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use crush::{
+    actions::Reset,
+    rust_ocpp::v1_6::{messages::reset::ResetRequest, types::RegistrationStatus},
+    Direction, Event, EventKind,
+};
+use serde_json::json;
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::timeout,
+};
+
+use crate::common::{self, Station};
+
+async fn next_event(events: &mut Receiver<Event>) -> Event {
+    timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("timed out waiting for an event")
+        .expect("event stream failed")
+}
+
+#[tokio::test]
+async fn session_lifecycle_is_published() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    let connected = next_event(&mut events).await;
+    assert_eq!(connected.station_id(), "CP1", "unexpected station");
+    assert!(
+        matches!(connected.kind(), EventKind::Connected { protocol: Some(protocol), .. } if protocol == "ocpp1.6"),
+        "unexpected event: {connected:?}"
+    );
+
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+    let received = next_event(&mut events).await;
+    assert!(
+        matches!(received.kind(), EventKind::MessageReceived { action, .. } if action == "BootNotification"),
+        "unexpected event: {received:?}"
+    );
+    let responded = next_event(&mut events).await;
+    assert!(
+        matches!(responded.kind(), EventKind::ResponseSent { action, .. } if action == "BootNotification"),
+        "unexpected event: {responded:?}"
+    );
+    let booted = next_event(&mut events).await;
+    assert!(
+        matches!(booted.kind(), EventKind::Booted { request, status: RegistrationStatus::Accepted } if request.charge_point_model == "Model"),
+        "unexpected event: {booted:?}"
+    );
+
+    let (result, _) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    result.expect("call failed");
+    let call_sent = next_event(&mut events).await;
+    assert!(
+        matches!(call_sent.kind(), EventKind::CallSent { action, .. } if action == "Reset"),
+        "unexpected event: {call_sent:?}"
+    );
+    let call_result = next_event(&mut events).await;
+    assert!(
+        matches!(call_result.kind(), EventKind::CallResult { action, .. } if action == "Reset"),
+        "unexpected event: {call_result:?}"
+    );
+
+    station.close().await;
+    let disconnected = next_event(&mut events).await;
+    assert!(
+        matches!(disconnected.kind(), EventKind::Disconnected { .. }),
+        "unexpected event: {disconnected:?}"
+    );
+    assert!(
+        disconnected.timestamp() >= connected.timestamp(),
+        "events are out of order"
+    );
+}
+
+#[tokio::test]
+async fn call_errors_are_published() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    station.call("VendorPing", json!({})).await;
+
+    let error = loop {
+        let event = next_event(&mut events).await;
+        if let EventKind::Error { .. } = event.kind() {
+            break event;
+        }
+    };
+    assert!(
+        matches!(
+            error.kind(),
+            EventKind::Error { direction: Direction::Sent, action, error_code, .. }
+                if action == "VendorPing" && error_code == "NotSupported"
+        ),
+        "unexpected event: {error:?}"
+    );
+}
+
+#[tokio::test]
+async fn slow_subscribers_are_told_how_many_events_they_missed() {
+    let server =
+        common::start_with_config(|config| config.with_event_capacity(2), |builder| builder).await;
+    let mut events = server.handle.events();
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    for _ in 0..3 {
+        station.call("Heartbeat", json!({})).await;
+    }
+
+    // Connected plus a MessageReceived and a ResponseSent per heartbeat, two of them kept.
+    let lagged = timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("timed out waiting for an event");
+    assert!(
+        matches!(lagged, Err(RecvError::Lagged(5))),
+        "unexpected result: {lagged:?}"
+    );
+    let event = next_event(&mut events).await;
+    assert!(
+        matches!(event.kind(), EventKind::MessageReceived { .. }),
+        "unexpected event: {event:?}"
+    );
+}
@@ -0,0 +1,61 @@
+use crush::{CrushBuilder, OcppResponseError};
+use serde_json::{json, Value};
+
+use crate::common::{self, error_code, message_type, Station};
+
+/// Echoes `VendorPing` with the station id, refusing every other unknown action.
+fn vendor_ping(builder: CrushBuilder) -> CrushBuilder {
+    builder.on_fallback(|context, action, payload| async move {
+        match action.as_str() {
+            "VendorPing" => Ok(json!({
+                "stationId": context.station_id(),
+                "nonce": payload.get("nonce"),
+            })),
+            _ => Err(OcppResponseError::UnsupportedMessageType {
+                details: Value::String(action),
+            }),
+        }
+    })
+}
+
+#[tokio::test]
+async fn unknown_actions_reach_the_fallback_handler() {
+    let server = common::start(vendor_ping).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("VendorPing", json!({ "nonce": 7 })).await;
+    assert_eq!(message_type(&response), Some(3), "expected a CALLRESULT");
+    assert_eq!(
+        response.get(2),
+        Some(&json!({ "stationId": "CP1", "nonce": 7 })),
+        "fallback did not answer"
+    );
+
+    let refused = station.call("VendorPong", json!({})).await;
+    assert_eq!(error_code(&refused), Some("NotSupported"));
+    assert_eq!(refused.get(4), Some(&json!("VendorPong")));
+}
+
+#[tokio::test]
+async fn registered_actions_bypass_the_fallback_handler() {
+    let server = common::start(vendor_ping).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("Heartbeat", json!({})).await;
+    assert!(
+        response
+            .get(2)
+            .and_then(|payload| payload.get("currentTime"))
+            .is_some(),
+        "Heartbeat was not answered by its handler: {response}"
+    );
+}
+
+#[tokio::test]
+async fn unknown_actions_are_not_supported_without_fallback_handler() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("VendorPing", json!({ "nonce": 7 })).await;
+    assert_eq!(error_code(&response), Some("NotSupported"));
+}
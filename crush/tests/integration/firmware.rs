@@ -0,0 +1,175 @@
+use chrono::{TimeZone, Utc};
+use crush::{
+    actions::{GetLog, SignedUpdateFirmware},
+    security::{
+        Firmware, FirmwareStatus, GetLogRequest, LogParameters, LogStatus, LogType,
+        SignedUpdateFirmwareRequest, UpdateFirmwareStatus, UploadLogStatus,
+    },
+    Event, EventKind,
+};
+use serde_json::json;
+use std::time::Duration;
+use tokio::{sync::broadcast::Receiver, time::timeout};
+
+use crate::common::{self, Station};
+
+/// Waits for the next event of `station_id` that `select` picks something from.
+async fn next_event<T>(
+    events: &mut Receiver<Event>,
+    station_id: &str,
+    select: impl Fn(&EventKind) -> Option<T>,
+) -> T {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event channel closed");
+            if event.station_id() != station_id {
+                continue;
+            }
+            if let Some(selected) = select(event.kind()) {
+                return selected;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for an event")
+}
+
+#[tokio::test]
+async fn firmware_progress_survives_the_reboot() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let retrieve_date_time = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    let request = SignedUpdateFirmwareRequest {
+        retries: Some(3),
+        retry_interval: None,
+        request_id: 7,
+        firmware: Firmware {
+            location: "https://firmware.example.com/cp-2.1.bin".to_owned(),
+            retrieve_date_time,
+            install_date_time: None,
+            signing_certificate: "-----BEGIN CERTIFICATE-----".to_owned(),
+            signature: "c2lnbmF0dXJl".to_owned(),
+        },
+    };
+    let (response, (action, payload)) = tokio::join!(
+        server.handle.call::<SignedUpdateFirmware>("CP1", request),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(action, "SignedUpdateFirmware");
+    assert_eq!(
+        payload,
+        json!({
+            "retries": 3,
+            "requestId": 7,
+            "firmware": {
+                "location": "https://firmware.example.com/cp-2.1.bin",
+                "retrieveDateTime": "2024-05-01T12:00:00Z",
+                "signingCertificate": "-----BEGIN CERTIFICATE-----",
+                "signature": "c2lnbmF0dXJl"
+            }
+        })
+    );
+    assert_eq!(
+        response.expect("call failed").status,
+        UpdateFirmwareStatus::Accepted
+    );
+    assert_eq!(server.handle.firmware_update("CP1"), None);
+
+    let notification = station
+        .call(
+            "SignedFirmwareStatusNotification",
+            json!({ "status": "InstallRebooting", "requestId": 7 }),
+        )
+        .await;
+    assert_eq!(notification, json!([3, "1", {}]));
+    let update = next_event(&mut events, "CP1", |kind| match kind {
+        EventKind::FirmwareProgress { update } => Some(update.clone()),
+        _ => None,
+    })
+    .await;
+    assert_eq!(update.status(), FirmwareStatus::InstallRebooting);
+    assert_eq!(update.request_id(), Some(7));
+
+    station.close().await;
+    server.wait_for_stations(&[]).await;
+    assert_eq!(server.handle.firmware_update("CP1"), Some(update));
+
+    let mut rebooted = Station::connect(server.address, "CP1").await;
+    rebooted
+        .call(
+            "SignedFirmwareStatusNotification",
+            json!({ "status": "Installed", "requestId": 7 }),
+        )
+        .await;
+    next_event(&mut events, "CP1", |kind| {
+        matches!(kind, EventKind::FirmwareProgress { .. }).then_some(())
+    })
+    .await;
+    let installed = server
+        .handle
+        .firmware_update("CP1")
+        .expect("no firmware progress recorded");
+    assert_eq!(installed.status(), FirmwareStatus::Installed);
+    let info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 not connected");
+    assert_eq!(info.firmware_update(), Some(&installed));
+}
+
+#[tokio::test]
+async fn log_uploads_are_tracked() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let request = GetLogRequest {
+        log: LogParameters {
+            remote_location: "ftp://logs.example.com/CP1".to_owned(),
+            oldest_timestamp: None,
+            latest_timestamp: None,
+        },
+        log_type: LogType::SecurityLog,
+        request_id: 3,
+        retries: None,
+        retry_interval: None,
+    };
+    let (response, (action, payload)) = tokio::join!(
+        server.handle.call::<GetLog>("CP1", request),
+        station.answer(json!({ "status": "Accepted", "filename": "security.log" })),
+    );
+    assert_eq!(action, "GetLog");
+    assert_eq!(
+        payload,
+        json!({
+            "log": { "remoteLocation": "ftp://logs.example.com/CP1" },
+            "logType": "SecurityLog",
+            "requestId": 3
+        })
+    );
+    let accepted = response.expect("call failed");
+    assert_eq!(accepted.status, LogStatus::Accepted);
+    assert_eq!(accepted.filename.as_deref(), Some("security.log"));
+
+    let notification = station
+        .call(
+            "LogStatusNotification",
+            json!({ "status": "Uploaded", "requestId": 3 }),
+        )
+        .await;
+    assert_eq!(notification, json!([3, "1", {}]));
+    let upload = next_event(&mut events, "CP1", |kind| match kind {
+        EventKind::LogProgress { upload } => Some(upload.clone()),
+        _ => None,
+    })
+    .await;
+    assert_eq!(upload.status(), UploadLogStatus::Uploaded);
+    assert_eq!(upload.request_id(), Some(3));
+    assert_eq!(server.handle.log_upload("CP1"), Some(upload));
+    assert_eq!(server.handle.firmware_update("CP1"), None);
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+use crate::common::{self, Station};
+
+const PING_INTERVAL: Duration = Duration::from_millis(50);
+const IDLE_TIMEOUT: Duration = Duration::from_millis(300);
+
+#[tokio::test]
+async fn stations_not_answering_pings_are_removed() {
+    let server = common::start_with_config(
+        |config| {
+            config
+                .with_websocket_ping_interval(PING_INTERVAL)
+                .with_max_missed_pongs(2)
+        },
+        |builder| builder,
+    )
+    .await;
+
+    // A station that never reads never answers the pings either.
+    let _station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    server.wait_for_stations(&[]).await;
+}
+
+#[tokio::test]
+async fn stations_answering_pings_stay_connected() {
+    let server = common::start_with_config(
+        |config| {
+            config
+                .with_websocket_ping_interval(PING_INTERVAL)
+                .with_max_missed_pongs(2)
+        },
+        |builder| builder,
+    )
+    .await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    station.stay_connected(PING_INTERVAL * 10).await;
+    server.wait_for_stations(&["CP1"]).await;
+}
+
+#[tokio::test]
+async fn idle_stations_are_closed() {
+    let server = common::start_with_config(
+        |config| config.with_idle_timeout(IDLE_TIMEOUT),
+        |builder| builder,
+    )
+    .await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let frame = station.closed().await.expect("expected a close frame");
+    assert_eq!(frame.code, CloseCode::Away, "unexpected close code");
+    server.wait_for_stations(&[]).await;
+}
+
+#[tokio::test]
+async fn heartbeats_keep_stations_from_going_idle() {
+    let server = common::start_with_config(
+        |config| config.with_idle_timeout(IDLE_TIMEOUT),
+        |builder| builder,
+    )
+    .await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    for _ in 0..6 {
+        sleep(IDLE_TIMEOUT / 3).await;
+        let response = station.call("Heartbeat", json!({})).await;
+        assert_eq!(
+            common::message_type(&response),
+            Some(3),
+            "expected a CALLRESULT"
+        );
+    }
+    server.wait_for_stations(&["CP1"]).await;
+}
@@ -0,0 +1,137 @@
+use crush::{
+    tower::{
+        layer::layer_fn,
+        util::{MapRequestLayer, MapResponseLayer},
+        Service,
+    },
+    OcppCall, OcppResponseError,
+};
+use futures::future::{self, BoxFuture, FutureExt};
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+};
+
+use crate::common::{self, error_code, message_type, Station};
+
+/// Refuses every CALL but `Heartbeat` without passing it on.
+#[derive(Clone)]
+struct HeartbeatsOnly<S>(S);
+
+impl<S> Service<OcppCall> for HeartbeatsOnly<S>
+where
+    S: Service<OcppCall, Response = Value, Error = OcppResponseError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = OcppResponseError;
+    type Future = BoxFuture<'static, Result<Value, OcppResponseError>>;
+
+    fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(context)
+    }
+
+    fn call(&mut self, call: OcppCall) -> Self::Future {
+        if call.action() == "Heartbeat" {
+            self.0.call(call).boxed()
+        } else {
+            future::ready(Err(OcppResponseError::Generic)).boxed()
+        }
+    }
+}
+
+#[tokio::test]
+async fn layers_see_every_call() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&seen);
+    let server = common::start(move |builder| {
+        builder.layer(MapRequestLayer::new(move |call: OcppCall| {
+            recorded
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((
+                    call.context().station_id().to_owned(),
+                    call.unique_id().to_owned(),
+                    call.action().to_owned(),
+                ));
+            call
+        }))
+    })
+    .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    station.call("Heartbeat", json!({})).await;
+    station.call("VendorPing", json!({})).await;
+
+    let seen = seen.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    assert_eq!(
+        seen,
+        [
+            ("CP1".to_owned(), "1".to_owned(), "Heartbeat".to_owned()),
+            ("CP1".to_owned(), "2".to_owned(), "VendorPing".to_owned()),
+        ],
+        "layer did not see the calls"
+    );
+}
+
+#[tokio::test]
+async fn layers_short_circuit_calls() {
+    let dispatched = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&dispatched);
+    let server = common::start(move |builder| {
+        builder
+            .on_fallback(move |_context, _action, payload| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { Ok(payload) }
+            })
+            .layer(layer_fn(HeartbeatsOnly))
+    })
+    .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let refused = station.call("VendorPing", json!({})).await;
+    assert_eq!(error_code(&refused), Some("GenericError"));
+    assert_eq!(
+        dispatched.load(Ordering::SeqCst),
+        0,
+        "refused call reached its handler"
+    );
+
+    let heartbeat = station.call("Heartbeat", json!({})).await;
+    assert_eq!(message_type(&heartbeat), Some(3), "Heartbeat was refused");
+}
+
+#[tokio::test]
+async fn layers_modify_calls_and_responses() {
+    let server = common::start(|builder| {
+        builder
+            .on_fallback(|_context, _action, payload| async move { Ok(payload) })
+            .layer(MapRequestLayer::new(|mut call: OcppCall| {
+                if let Some(payload) = call.payload_mut().as_object_mut() {
+                    payload.remove("secret");
+                }
+                call
+            }))
+            .layer(MapResponseLayer::new(|mut payload: Value| {
+                if let Some(payload) = payload.as_object_mut() {
+                    payload.insert("echoed".to_owned(), Value::Bool(true));
+                }
+                payload
+            }))
+    })
+    .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station
+        .call("VendorPing", json!({ "nonce": 7, "secret": "hunter2" }))
+        .await;
+    assert_eq!(
+        response.get(2),
+        Some(&json!({ "nonce": 7, "echoed": true })),
+        "layers did not rewrite the call"
+    );
+}
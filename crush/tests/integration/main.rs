@@ -0,0 +1,34 @@
+#![allow(
+    clippy::tests_outside_test_module,
+    reason = "integration tests are only ever compiled for testing"
+)]
+#![allow(
+    unused_crate_dependencies,
+    reason = "the integration tests only use a subset of the crate's dependencies"
+)]
+
+mod actions;
+mod basic_auth;
+mod certificate_authority;
+mod common;
+mod connect;
+mod connectors;
+mod context;
+mod disconnect;
+mod duplicates;
+mod events;
+mod fallback;
+mod firmware;
+mod keepalive;
+mod layers;
+mod mutual_tls;
+mod outbound;
+mod presence;
+mod registration;
+mod registry;
+mod security;
+mod security_profile;
+mod storage;
+mod supervision;
+mod tls;
+mod transactions;
@@ -0,0 +1,146 @@
+use std::{fs, sync::Arc, time::Duration};
+
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, CertifiedKey, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use rustls::{crypto::ring, pki_types::PrivateKeyDer, ClientConfig, RootCertStore};
+use tokio_tungstenite::tungstenite::{self, http::StatusCode};
+
+use crate::{
+    common::{self, Server, Station},
+    tls::{certificate, CertificateFiles},
+};
+
+/// The object identifier of the `serialNumber` attribute of a distinguished name.
+const SERIAL_NUMBER: [u64; 4] = [2, 5, 4, 5];
+
+/// A CA issuing station certificates.
+pub(crate) struct Authority {
+    pub(crate) certificate: Certificate,
+    pub(crate) key_pair: KeyPair,
+}
+
+impl Authority {
+    pub(crate) fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).expect("invalid CA parameters");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "Charge Point CA");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let key_pair = KeyPair::generate().expect("failed to generate CA key");
+        let certificate = params.self_signed(&key_pair).expect("failed to sign CA");
+        Self {
+            certificate,
+            key_pair,
+        }
+    }
+
+    /// Issues a client certificate with the given subject attributes.
+    fn issue(&self, subject: &[(DnType, &str)]) -> (Certificate, KeyPair) {
+        let mut params = CertificateParams::new(Vec::new()).expect("invalid station parameters");
+        for (attribute, value) in subject {
+            params
+                .distinguished_name
+                .push(attribute.clone(), value.to_owned());
+        }
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let key_pair = KeyPair::generate().expect("failed to generate station key");
+        let certificate = params
+            .signed_by(&key_pair, &self.certificate, &self.key_pair)
+            .expect("failed to sign station certificate");
+        (certificate, key_pair)
+    }
+}
+
+/// A client trusting `server` and authenticating with the certificate `client`.
+fn client(
+    server: &CertifiedKey,
+    (certificate, key_pair): (Certificate, KeyPair),
+) -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(server.cert.der().clone())
+        .expect("failed to trust certificate");
+    let key = PrivateKeyDer::try_from(key_pair.serialize_der()).expect("invalid station key");
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("invalid client protocol versions")
+        .with_root_certificates(roots)
+        .with_client_auth_cert(vec![certificate.der().clone()], key)
+        .expect("invalid client certificate");
+    Arc::new(config)
+}
+
+async fn start(files: &CertificateFiles, authority: &Authority) -> Server {
+    fs::write(files.client_ca(), authority.certificate.pem()).expect("failed to write CA");
+    let tls = files
+        .config()
+        .with_client_ca(files.client_ca())
+        .with_reload_interval(Duration::ZERO);
+    common::start_with_config(|config| config.with_tls(tls), |builder| builder).await
+}
+
+#[tokio::test]
+async fn stations_authenticate_with_client_certificates() {
+    let files = CertificateFiles::new();
+    let server_key = certificate();
+    files.write(&server_key);
+    let authority = Authority::new();
+    let server = start(&files, &authority).await;
+
+    let by_common_name = client(&server_key, authority.issue(&[(DnType::CommonName, "CP1")]));
+    let _cp1 = Station::try_connect_tls(server.address, "CP1", by_common_name)
+        .await
+        .expect("CP1 was refused");
+    let by_serial_number = client(
+        &server_key,
+        authority.issue(&[
+            (DnType::CommonName, "ACME Wallbox"),
+            (DnType::CustomDnType(SERIAL_NUMBER.to_vec()), "CP2"),
+        ]),
+    );
+    let _cp2 = Station::try_connect_tls(server.address, "CP2", by_serial_number)
+        .await
+        .expect("CP2 was refused");
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    let station = server.handle.station("CP2").await.expect("CP2 not found");
+    let certificate = station
+        .client_certificate()
+        .expect("CP2 has no client certificate");
+    assert_eq!(certificate.common_name(), Some("ACME Wallbox"));
+    assert_eq!(certificate.subject_serial_number(), Some("CP2"));
+    assert!(certificate.issuer().contains("Charge Point CA"));
+}
+
+#[tokio::test]
+async fn certificates_of_other_stations_are_refused() {
+    let files = CertificateFiles::new();
+    let server_key = certificate();
+    files.write(&server_key);
+    let authority = Authority::new();
+    let server = start(&files, &authority).await;
+
+    let cp1 = client(&server_key, authority.issue(&[(DnType::CommonName, "CP1")]));
+    let attempt = Station::try_connect_tls(server.address, "CP2", cp1).await;
+    assert_eq!(common::refusal_status(attempt), StatusCode::FORBIDDEN);
+    assert!(server.handle.connected_stations().await.is_empty());
+}
+
+#[tokio::test]
+async fn certificates_of_unknown_authorities_fail_the_handshake() {
+    let files = CertificateFiles::new();
+    let server_key = certificate();
+    files.write(&server_key);
+    let server = start(&files, &Authority::new()).await;
+
+    let impostor = Authority::new();
+    let cp1 = client(&server_key, impostor.issue(&[(DnType::CommonName, "CP1")]));
+    let attempt = Station::try_connect_tls(server.address, "CP1", cp1).await;
+    assert!(
+        !matches!(attempt, Ok(_) | Err(tungstenite::Error::Http(_))),
+        "station with an untrusted certificate got past the handshake"
+    );
+    assert!(server.handle.connected_stations().await.is_empty());
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use crush::{
+    actions::Reset,
+    rust_ocpp::v1_6::{messages::reset::ResetRequest, types::ResetResponseStatus},
+    CallError,
+};
+use serde_json::json;
+
+use crate::common::{self, Station};
+
+#[tokio::test]
+async fn calls_are_answered_by_the_station() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let (result, (action, payload)) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(action, "Reset", "unexpected action");
+    assert_eq!(payload, json!({ "type": "Soft" }), "unexpected payload");
+    let reset = result.expect("call failed");
+    assert_eq!(
+        reset.status,
+        ResetResponseStatus::Accepted,
+        "unexpected status"
+    );
+}
+
+#[tokio::test]
+async fn call_errors_are_returned_to_the_caller() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let handle = server.handle.clone();
+    let call =
+        tokio::spawn(async move { handle.call::<Reset>("CP1", ResetRequest::default()).await });
+
+    let frame = station.receive().await;
+    let unique_id = frame.get(1).cloned().expect("CALL without unique id");
+    station
+        .send(json!([4, unique_id, "NotSupported", "No resets here", {}]))
+        .await;
+
+    let result = call.await.expect("call task failed");
+    assert!(
+        matches!(result, Err(CallError::Failed { ref code, .. }) if code == "NotSupported"),
+        "unexpected result: {result:?}"
+    );
+}
+
+#[tokio::test]
+async fn calls_to_unknown_stations_fail() {
+    let server = common::start(|builder| builder).await;
+
+    let result = server
+        .handle
+        .call::<Reset>("CP1", ResetRequest::default())
+        .await;
+    assert!(
+        matches!(result, Err(CallError::NotConnected(_))),
+        "unexpected result: {result:?}"
+    );
+}
+
+#[tokio::test]
+async fn unanswered_calls_time_out() {
+    let server = common::start_with_config(
+        |config| config.with_call_timeout(Duration::from_millis(100)),
+        |builder| builder,
+    )
+    .await;
+    let _station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let result = server
+        .handle
+        .call::<Reset>("CP1", ResetRequest::default())
+        .await;
+    assert!(
+        matches!(result, Err(CallError::Timeout)),
+        "unexpected result: {result:?}"
+    );
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use crush::{
+    chrono::Utc,
+    rust_ocpp::v1_6::{
+        messages::boot_notification::BootNotificationResponse, types::RegistrationStatus,
+    },
+    Event, EventKind,
+};
+use serde_json::json;
+use tokio::{sync::broadcast::Receiver, time::timeout};
+
+use crate::common::{self, message_type, Server, Station};
+
+/// Starts crush telling stations to heartbeat every second, unresponsive after two seconds.
+async fn start() -> Server {
+    common::start_with_config(
+        |config| config.with_unresponsive_after(2),
+        |builder| {
+            builder.on_boot_notification(|_context, _request| async {
+                Ok(BootNotificationResponse {
+                    current_time: Utc::now(),
+                    interval: 1,
+                    status: RegistrationStatus::Accepted,
+                })
+            })
+        },
+    )
+    .await
+}
+
+/// Waits for the next presence change of any station.
+async fn presence_change(events: &mut Receiver<Event>) -> Event {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event stream failed");
+            if matches!(
+                event.kind(),
+                EventKind::Unresponsive { .. } | EventKind::Recovered { .. }
+            ) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for a presence change")
+}
+
+#[tokio::test]
+async fn silent_stations_are_reported_until_they_recover() {
+    let server = start().await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+
+    let unresponsive = presence_change(&mut events).await;
+    assert_eq!(unresponsive.station_id(), "CP1");
+    let last_message_at = match unresponsive.kind() {
+        EventKind::Unresponsive { last_message_at } => *last_message_at,
+        _ => None,
+    };
+    let info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 not connected");
+    assert_eq!(info.heartbeat_interval(), Some(Duration::from_secs(1)));
+    assert!(
+        last_message_at.is_some(),
+        "unexpected event: {unresponsive:?}"
+    );
+    assert_eq!(info.last_message_at(), last_message_at);
+    let unresponsive_since = info.unresponsive_since().expect("CP1 is not unresponsive");
+
+    let heartbeat = station.call("Heartbeat", json!({})).await;
+    assert_eq!(message_type(&heartbeat), Some(3));
+    let recovered = presence_change(&mut events).await;
+    assert!(
+        matches!(recovered.kind(), EventKind::Recovered { unresponsive_since: since } if *since == unresponsive_since),
+        "unexpected event: {recovered:?}"
+    );
+    let recovered_info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 not connected");
+    assert_eq!(recovered_info.unresponsive_since(), None);
+}
+
+#[tokio::test]
+async fn reconnected_stations_keep_their_heartbeat_interval() {
+    let server = start().await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+    station.close().await;
+    server.wait_for_stations(&[]).await;
+
+    let _reconnected = Station::connect(server.address, "CP1").await;
+    let unresponsive = presence_change(&mut events).await;
+    assert!(
+        matches!(
+            unresponsive.kind(),
+            EventKind::Unresponsive {
+                last_message_at: None
+            }
+        ),
+        "unexpected event: {unresponsive:?}"
+    );
+}
@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use crush::{
+    actions::{ChangeConfiguration, Reset, TriggerMessage},
+    chrono::Utc,
+    rust_ocpp::v1_6::{
+        messages::{
+            boot_notification::BootNotificationResponse,
+            change_configuration::ChangeConfigurationRequest, reset::ResetRequest,
+            trigger_message::TriggerMessageRequest,
+        },
+        types::{ConfigurationStatus, MessageTrigger, RegistrationStatus, TriggerMessageStatus},
+    },
+    CallError, Event, EventKind, RegistrationPolicy,
+};
+use serde_json::{json, Value};
+use tokio::{sync::broadcast::Receiver, time::timeout};
+
+use crate::common::{self, error_code, message_type, Server, Station};
+
+/// Starts crush with `policy`, registering stations with the status their model names.
+async fn start(policy: RegistrationPolicy) -> Server {
+    common::start_with_config(
+        |config| config.with_registration_policy(policy),
+        |builder| {
+            builder.on_boot_notification(|_context, request| async move {
+                let status = match request.charge_point_model.as_str() {
+                    "Pending" => RegistrationStatus::Pending,
+                    "Rejected" => RegistrationStatus::Rejected,
+                    _ => RegistrationStatus::Accepted,
+                };
+                Ok(BootNotificationResponse {
+                    current_time: Utc::now(),
+                    interval: 300,
+                    status,
+                })
+            })
+        },
+    )
+    .await
+}
+
+/// Boots `station` as `model` and waits until crush registered it.
+async fn boot(station: &mut Station, events: &mut Receiver<Event>, model: &str) -> Value {
+    let response = station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": model }),
+        )
+        .await;
+    timeout(Duration::from_secs(5), async {
+        while !matches!(
+            events.recv().await.expect("event stream failed").kind(),
+            EventKind::Booted { .. }
+        ) {}
+    })
+    .await
+    .expect("timed out waiting for the boot");
+    response
+}
+
+async fn heartbeat(station: &mut Station) -> Value {
+    station.call("Heartbeat", json!({})).await
+}
+
+#[tokio::test]
+async fn out_of_order_calls_are_flagged_by_default() {
+    let server = start(RegistrationPolicy::default()).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    boot(&mut station, &mut events, "Pending").await;
+
+    let early = heartbeat(&mut station).await;
+    assert_eq!(
+        message_type(&early),
+        Some(3),
+        "unexpected response: {early}"
+    );
+    let flagged = timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("event stream failed");
+            if let EventKind::OutOfOrder { action, status, .. } = event.kind() {
+                return (action.clone(), status.clone());
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for the out of order event");
+    assert_eq!(
+        flagged,
+        ("Heartbeat".to_owned(), Some(RegistrationStatus::Pending))
+    );
+}
+
+#[tokio::test]
+async fn stations_that_did_not_boot_are_served() {
+    let server = start(RegistrationPolicy::Reject).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    // As if crush restarted while the station kept running.
+    let response = heartbeat(&mut station).await;
+    assert_eq!(
+        message_type(&response),
+        Some(3),
+        "unexpected response: {response}"
+    );
+    let (reset, _) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    reset.expect("reset failed");
+}
+
+#[tokio::test]
+async fn triggered_messages_are_accepted_while_pending() {
+    let server = start(RegistrationPolicy::Reject).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    boot(&mut station, &mut events, "Pending").await;
+
+    let request = TriggerMessageRequest {
+        requested_message: MessageTrigger::StatusNotification,
+        connector_id: Some(1),
+    };
+    let (triggered, _) = tokio::join!(
+        server.handle.call::<TriggerMessage>("CP1", request),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(
+        triggered.expect("trigger failed").status,
+        TriggerMessageStatus::Accepted
+    );
+    let status = station
+        .call(
+            "StatusNotification",
+            json!({ "connectorId": 1, "errorCode": "NoError", "status": "Available" }),
+        )
+        .await;
+    assert_eq!(
+        message_type(&status),
+        Some(3),
+        "unexpected response: {status}"
+    );
+
+    let untriggered = heartbeat(&mut station).await;
+    assert_eq!(error_code(&untriggered), Some("SecurityError"));
+}
+
+#[tokio::test]
+async fn calls_are_rejected_until_the_station_is_accepted() {
+    let server = start(RegistrationPolicy::Reject).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let pending = boot(&mut station, &mut events, "Pending").await;
+    assert_eq!(
+        pending.get(2).and_then(|payload| payload.get("status")),
+        Some(&json!("Pending"))
+    );
+    let still_pending = heartbeat(&mut station).await;
+    assert_eq!(error_code(&still_pending), Some("SecurityError"));
+    assert_eq!(
+        still_pending.get(4),
+        Some(&json!({ "registrationStatus": "Pending" }))
+    );
+
+    let request = ChangeConfigurationRequest {
+        key: "HeartbeatInterval".to_owned(),
+        value: "60".to_owned(),
+    };
+    let (configured, _) = tokio::join!(
+        server.handle.call::<ChangeConfiguration>("CP1", request),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(
+        configured.expect("configuration call failed").status,
+        ConfigurationStatus::Accepted
+    );
+    let reset = server
+        .handle
+        .call::<Reset>("CP1", ResetRequest::default())
+        .await;
+    assert!(
+        matches!(
+            reset,
+            Err(CallError::Unregistered {
+                status: Some(RegistrationStatus::Pending)
+            })
+        ),
+        "unexpected result: {reset:?}"
+    );
+
+    boot(&mut station, &mut events, "Model").await;
+    let accepted = heartbeat(&mut station).await;
+    assert_eq!(
+        message_type(&accepted),
+        Some(3),
+        "unexpected response: {accepted}"
+    );
+    let info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 not connected");
+    assert_eq!(
+        info.registration_status(),
+        Some(RegistrationStatus::Accepted)
+    );
+}
+
+#[tokio::test]
+async fn registration_outlives_the_connection() {
+    let server = start(RegistrationPolicy::Reject).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    boot(&mut station, &mut events, "Model").await;
+    station.close().await;
+    server.wait_for_stations(&[]).await;
+
+    let mut reconnected = Station::connect(server.address, "CP1").await;
+    let response = heartbeat(&mut reconnected).await;
+    assert_eq!(
+        message_type(&response),
+        Some(3),
+        "unexpected response: {response}"
+    );
+}
+
+#[tokio::test]
+async fn nothing_is_sent_to_rejected_stations() {
+    let server = start(RegistrationPolicy::Reject).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    boot(&mut station, &mut events, "Rejected").await;
+
+    let request = ChangeConfigurationRequest {
+        key: "HeartbeatInterval".to_owned(),
+        value: "60".to_owned(),
+    };
+    let configured = server
+        .handle
+        .call::<ChangeConfiguration>("CP1", request)
+        .await;
+    assert!(
+        matches!(
+            configured,
+            Err(CallError::Unregistered {
+                status: Some(RegistrationStatus::Rejected)
+            })
+        ),
+        "unexpected result: {configured:?}"
+    );
+    let rejected = heartbeat(&mut station).await;
+    assert_eq!(error_code(&rejected), Some("SecurityError"));
+}
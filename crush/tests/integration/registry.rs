@@ -0,0 +1,75 @@
+use serde_json::json;
+
+use crate::common::{self, Station};
+
+#[tokio::test]
+async fn connected_stations_can_be_inspected() {
+    let server = common::start(|builder| builder).await;
+
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let info = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 is connected");
+    assert_eq!(info.station_id(), "CP1", "unexpected station id");
+    assert!(info.address().ip().is_loopback(), "unexpected address");
+    assert_eq!(info.protocol(), Some("ocpp1.6"), "unexpected protocol");
+    assert!(info.last_message_at().is_none(), "no message was sent yet");
+    assert!(info.boot_notification().is_none(), "no boot was sent yet");
+
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+
+    let booted = server
+        .handle
+        .station("CP1")
+        .await
+        .expect("CP1 is connected");
+    let last_message_at = booted.last_message_at().expect("a message was sent");
+    assert!(
+        last_message_at >= booted.connected_at(),
+        "message predates the connection"
+    );
+    let boot_notification = booted.boot_notification().expect("a boot was sent");
+    assert_eq!(
+        boot_notification.charge_point_model, "Model",
+        "unexpected boot notification"
+    );
+}
+
+#[tokio::test]
+async fn registry_lists_connected_stations_only() {
+    let server = common::start(|builder| builder).await;
+
+    let first = Station::connect(server.address, "CP1").await;
+    let _second = Station::connect(server.address, "CP2").await;
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    let mut station_ids = server
+        .handle
+        .stations()
+        .await
+        .iter()
+        .map(|station| station.station_id().to_owned())
+        .collect::<Vec<_>>();
+    station_ids.sort_unstable();
+    assert_eq!(station_ids, ["CP1", "CP2"], "unexpected stations");
+
+    first.close().await;
+    server.wait_for_stations(&["CP2"]).await;
+    assert!(
+        server.handle.station("CP1").await.is_none(),
+        "CP1 is still listed"
+    );
+    assert!(
+        server.handle.station("CP3").await.is_none(),
+        "CP3 never connected"
+    );
+}
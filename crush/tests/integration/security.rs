@@ -0,0 +1,167 @@
+use std::sync::{Arc, Mutex};
+
+use crush::{
+    actions::{CertificateSigned, ExtendedTriggerMessage, GetInstalledCertificateIds},
+    security::{
+        CertificateHashData, CertificateSignedRequest, CertificateSignedStatus, CertificateUse,
+        ExtendedMessageTrigger, ExtendedTriggerMessageRequest, ExtendedTriggerMessageStatus,
+        GenericStatus, GetInstalledCertificateIdsRequest, GetInstalledCertificateStatus,
+        HashAlgorithm, SecurityEventNotificationResponse, SignCertificateResponse,
+    },
+};
+use serde_json::json;
+
+use crate::common::{self, message_type, Station};
+
+const CSR: &str = "-----BEGIN CERTIFICATE REQUEST-----\nMIIB\n-----END CERTIFICATE REQUEST-----";
+
+#[tokio::test]
+async fn security_messages_are_answered_by_default() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let event = station
+        .call(
+            "SecurityEventNotification",
+            json!({ "type": "SettingSystemTime", "timestamp": "2024-05-01T12:00:00Z" }),
+        )
+        .await;
+    assert_eq!(event, json!([3, "1", {}]));
+
+    let signing = station.call("SignCertificate", json!({ "csr": CSR })).await;
+    assert_eq!(signing, json!([3, "2", { "status": "Rejected" }]));
+}
+
+#[tokio::test]
+async fn security_handlers_receive_typed_requests() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::clone(&events);
+    let server = common::start(move |builder| {
+        builder
+            .on_security_event_notification(move |context, request| {
+                let received = Arc::clone(&received);
+                async move {
+                    received.lock().expect("events poisoned").push((
+                        context.station_id().to_owned(),
+                        request.kind,
+                        request.tech_info,
+                    ));
+                    Ok(SecurityEventNotificationResponse {})
+                }
+            })
+            .on_sign_certificate(|_context, request| async move {
+                assert_eq!(request.csr, CSR);
+                Ok(SignCertificateResponse {
+                    status: GenericStatus::Accepted,
+                })
+            })
+    })
+    .await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let event = station
+        .call(
+            "SecurityEventNotification",
+            json!({
+                "type": "TamperDetectionActivated",
+                "timestamp": "2024-05-01T12:00:00Z",
+                "techInfo": "Enclosure opened"
+            }),
+        )
+        .await;
+    assert_eq!(message_type(&event), Some(3));
+    assert_eq!(
+        *events.lock().expect("events poisoned"),
+        [(
+            "CP1".to_owned(),
+            "TamperDetectionActivated".to_owned(),
+            Some("Enclosure opened".to_owned())
+        )]
+    );
+
+    let signing = station.call("SignCertificate", json!({ "csr": CSR })).await;
+    assert_eq!(signing, json!([3, "2", { "status": "Accepted" }]));
+}
+
+#[tokio::test]
+async fn security_commands_are_sent_to_stations() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let signed_request = CertificateSignedRequest {
+        certificate_chain: "-----BEGIN CERTIFICATE-----".to_owned(),
+    };
+    let (signed, (signed_action, signed_payload)) = tokio::join!(
+        server
+            .handle
+            .call::<CertificateSigned>("CP1", signed_request),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(signed_action, "CertificateSigned");
+    assert_eq!(
+        signed_payload,
+        json!({ "certificateChain": "-----BEGIN CERTIFICATE-----" })
+    );
+    assert_eq!(
+        signed.expect("call failed").status,
+        CertificateSignedStatus::Accepted
+    );
+
+    let installed_request = GetInstalledCertificateIdsRequest {
+        certificate_type: CertificateUse::CentralSystemRootCertificate,
+    };
+    let (installed, (installed_action, installed_payload)) = tokio::join!(
+        server
+            .handle
+            .call::<GetInstalledCertificateIds>("CP1", installed_request),
+        station.answer(json!({
+            "status": "Accepted",
+            "certificateHashData": [{
+                "hashAlgorithm": "SHA256",
+                "issuerNameHash": "aa",
+                "issuerKeyHash": "bb",
+                "serialNumber": "01"
+            }]
+        })),
+    );
+    assert_eq!(installed_action, "GetInstalledCertificateIds");
+    assert_eq!(
+        installed_payload,
+        json!({ "certificateType": "CentralSystemRootCertificate" })
+    );
+    let installed_ids = installed.expect("call failed");
+    assert_eq!(
+        installed_ids.status,
+        GetInstalledCertificateStatus::Accepted
+    );
+    assert_eq!(
+        installed_ids.certificate_hash_data,
+        Some(vec![CertificateHashData {
+            hash_algorithm: HashAlgorithm::Sha256,
+            issuer_name_hash: "aa".to_owned(),
+            issuer_key_hash: "bb".to_owned(),
+            serial_number: "01".to_owned(),
+        }])
+    );
+
+    let trigger_request = ExtendedTriggerMessageRequest {
+        requested_message: ExtendedMessageTrigger::SignChargePointCertificate,
+        connector_id: None,
+    };
+    let (triggered, (triggered_action, triggered_payload)) = tokio::join!(
+        server
+            .handle
+            .call::<ExtendedTriggerMessage>("CP1", trigger_request),
+        station.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(triggered_action, "ExtendedTriggerMessage");
+    assert_eq!(
+        triggered_payload,
+        json!({ "requestedMessage": "SignChargePointCertificate" })
+    );
+    assert_eq!(
+        triggered.expect("call failed").status,
+        ExtendedTriggerMessageStatus::Accepted
+    );
+}
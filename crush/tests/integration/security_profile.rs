@@ -0,0 +1,228 @@
+use std::{sync::Arc, time::Duration};
+
+use crush::{
+    DuplicateConnectionPolicy, InMemoryCredentialStore, SecurityProfile, SecurityProfileError,
+};
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+use crate::{
+    basic_auth::basic,
+    common::{self, Server, Station},
+};
+
+async fn start(credentials: &Arc<InMemoryCredentialStore>) -> Server {
+    let store = Arc::clone(credentials);
+    common::start_with_config(
+        // Hashing the new key takes a while in unoptimized builds.
+        |config| {
+            config
+                .with_reconnect_timeout(Duration::from_secs(2))
+                .with_unsecured_stations(true)
+        },
+        |builder| builder.with_credential_store(store),
+    )
+    .await
+}
+
+/// The password a station derives from the hex encoded `AuthorizationKey` it was sent.
+fn authorization_key(payload: &Value) -> Vec<u8> {
+    let value = payload
+        .get("value")
+        .and_then(Value::as_str)
+        .expect("ChangeConfiguration without a value");
+    assert_eq!(value.len(), 40, "key is not 20 bytes");
+    (0..value.len())
+        .step_by(2)
+        .map(|index| {
+            let digits = value.get(index..index + 2).expect("odd key length");
+            u8::from_str_radix(digits, 16).expect("key is not hex")
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn unsecured_stations_move_to_basic_authentication() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    let server = start(&credentials).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let station_side = async {
+        let (key_action, key_payload) = station.answer(json!({ "status": "Accepted" })).await;
+        assert_eq!(key_action, "ChangeConfiguration");
+        assert_eq!(
+            key_payload.get("key"),
+            Some(&json!("AuthorizationKey")),
+            "AuthorizationKey was not changed first"
+        );
+        let password = authorization_key(&key_payload);
+
+        let (_, profile_payload) = station.answer(json!({ "status": "Accepted" })).await;
+        assert_eq!(
+            profile_payload,
+            json!({ "key": "SecurityProfile", "value": "1" })
+        );
+        station.disconnect();
+        Station::try_connect_with(server.address, "CP1", &[basic("CP1", &password)])
+            .await
+            .expect("station with the new key was refused")
+    };
+    let (upgraded, _reconnected) = tokio::join!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::BasicAuthentication),
+        station_side,
+    );
+    upgraded.expect("upgrade failed");
+
+    let attempt = Station::try_connect(server.address, "CP1").await;
+    assert_eq!(common::refusal_status(attempt), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn credentials_are_restored_if_the_station_does_not_reconnect() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    let server = start(&credentials).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let station_side = async {
+        let (_, key_payload) = station.answer(json!({ "status": "Accepted" })).await;
+        station.answer(json!({ "status": "Accepted" })).await;
+        station.disconnect();
+        authorization_key(&key_payload)
+    };
+    let (upgraded, password) = tokio::join!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::BasicAuthentication),
+        station_side,
+    );
+    assert!(matches!(
+        upgraded,
+        Err(SecurityProfileError::ReconnectTimeout)
+    ));
+
+    let attempt =
+        Station::try_connect_with(server.address, "CP1", &[basic("CP1", &password)]).await;
+    assert_eq!(common::refusal_status(attempt), StatusCode::UNAUTHORIZED);
+    let _fallback = Station::try_connect(server.address, "CP1")
+        .await
+        .expect("station on its previous profile was refused");
+}
+
+#[tokio::test]
+async fn rejected_keys_are_not_stored() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    let server = start(&credentials).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    let (upgraded, (_, key_payload)) = tokio::join!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::BasicAuthentication),
+        station.answer(json!({ "status": "Rejected" })),
+    );
+    assert!(matches!(
+        upgraded,
+        Err(SecurityProfileError::NotAccepted {
+            key: "AuthorizationKey",
+            ..
+        })
+    ));
+
+    let password = authorization_key(&key_payload);
+    let attempt =
+        Station::try_connect_with(server.address, "CP1", &[basic("CP1", &password)]).await;
+    assert_eq!(common::refusal_status(attempt), StatusCode::UNAUTHORIZED);
+    let _unsecured = Station::try_connect(server.address, "CP1")
+        .await
+        .expect("station lost its unsecured access");
+}
+
+#[tokio::test]
+async fn upgrading_without_a_credential_store_fails() {
+    let server = common::start(|builder| builder).await;
+
+    assert!(matches!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::BasicAuthentication)
+            .await,
+        Err(SecurityProfileError::NoCredentialStore)
+    ));
+}
+
+#[tokio::test]
+async fn the_new_key_is_accepted_before_the_station_answers() {
+    const PREVIOUS: &[u8] = b"the previous password";
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.set_password("CP1", PREVIOUS);
+    let store = Arc::clone(&credentials);
+    let server = common::start_with_config(
+        |config| {
+            config
+                .with_reconnect_timeout(Duration::from_secs(2))
+                .with_duplicate_connection_policy(DuplicateConnectionPolicy::AllowBoth)
+        },
+        |builder| builder.with_credential_store(store),
+    )
+    .await;
+    let mut station = Station::try_connect_with(server.address, "CP1", &[basic("CP1", PREVIOUS)])
+        .await
+        .expect("CP1 was refused");
+    server.wait_for_stations(&["CP1"]).await;
+
+    let station_side = async {
+        let key_change = station.receive().await;
+        let password = authorization_key(key_change.get(3).expect("CALL without a payload"));
+        let mut reconnected =
+            Station::try_connect_with(server.address, "CP1", &[basic("CP1", &password)])
+                .await
+                .expect("station with the new key was refused before it accepted the key");
+        reconnected.call("Heartbeat", json!({})).await;
+        let unique_id = key_change.get(1).expect("CALL without a unique id");
+        station
+            .send(json!([3, unique_id, { "status": "Accepted" }]))
+            .await;
+
+        let (_, profile_payload) = reconnected.answer(json!({ "status": "Accepted" })).await;
+        assert_eq!(
+            profile_payload,
+            json!({ "key": "SecurityProfile", "value": "1" })
+        );
+        reconnected
+    };
+    let (upgraded, _reconnected) = tokio::join!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::BasicAuthentication),
+        station_side,
+    );
+    upgraded.expect("upgrade failed");
+
+    let attempt = Station::try_connect_with(server.address, "CP1", &[basic("CP1", PREVIOUS)]).await;
+    assert_eq!(common::refusal_status(attempt), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tls_profiles_are_refused_without_tls() {
+    let credentials = Arc::new(InMemoryCredentialStore::new());
+    credentials.allow_unsecured("CP1");
+    let server = start(&credentials).await;
+    let _station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    assert!(matches!(
+        server
+            .handle
+            .upgrade_security_profile("CP1", SecurityProfile::TlsWithBasicAuthentication)
+            .await,
+        Err(SecurityProfileError::TlsNotConfigured)
+    ));
+}
@@ -0,0 +1,585 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use crush::{
+    actions::Reset,
+    chrono::Utc,
+    rust_ocpp::v1_6::{
+        messages::{boot_notification::BootNotificationResponse, reset::ResetRequest},
+        types::{
+            AuthorizationStatus, ChargePointStatus, IdTagInfo, MeterValue, RegistrationStatus,
+        },
+    },
+    BuildError, Config, ConnectorStatus, CrushBuilder, EventKind, FileStorage, InMemoryStorage,
+    PendingCall, RegistrationPolicy, Snapshot, Storage, StorageError, StoredStation, Transaction,
+};
+#[cfg(feature = "sqlite")]
+use crush::{chrono::DateTime, SqliteStorage, TransactionFilter};
+use serde_json::{json, Value};
+
+use crate::common::{self, error_code, message_type, Server, Station};
+
+static NEXT_LOG: AtomicUsize = AtomicUsize::new(0);
+
+/// A path for a storage log, removed again on drop.
+struct LogFile {
+    path: PathBuf,
+}
+
+impl LogFile {
+    fn new() -> Self {
+        let path = env::temp_dir().join(format!(
+            "crush-storage-{}-{}.jsonl",
+            process::id(),
+            NEXT_LOG.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self { path }
+    }
+
+    fn lines(&self) -> usize {
+        fs::read_to_string(&self.path)
+            .expect("failed to read the log")
+            .lines()
+            .count()
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        drop(fs::remove_file(&self.path));
+    }
+}
+
+/// Starts crush on `storage`, accepting stations that boot.
+async fn start(storage: impl Storage + 'static) -> Server {
+    common::start_with_config(
+        |config| config.with_registration_policy(RegistrationPolicy::Reject),
+        |builder: CrushBuilder| {
+            builder
+                .with_storage(storage)
+                .on_boot_notification(|_context, _request| async {
+                    Ok(BootNotificationResponse {
+                        current_time: Utc::now(),
+                        interval: 300,
+                        status: RegistrationStatus::Accepted,
+                    })
+                })
+        },
+    )
+    .await
+}
+
+/// Waits until crush recorded the messages sent before, which it does before it handles the
+/// next CALL of the station.
+async fn applied(station: &mut Station) -> Value {
+    station.call("Heartbeat", json!({})).await
+}
+
+/// Boots `station`, reports connector 1 charging and starts a transaction on it.
+async fn start_charging(station: &mut Station) -> i32 {
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+    station
+        .call(
+            "StatusNotification",
+            json!({ "connectorId": 1, "errorCode": "NoError", "status": "Charging" }),
+        )
+        .await;
+    let started = station
+        .call(
+            "StartTransaction",
+            json!({
+                "connectorId": 1,
+                "idTag": "TAG1",
+                "meterStart": 1_000,
+                "timestamp": "2024-05-01T12:00:00Z"
+            }),
+        )
+        .await;
+    applied(station).await;
+    started
+        .get(2)
+        .and_then(|payload| payload.get("transactionId"))
+        .and_then(Value::as_i64)
+        .and_then(|id| i32::try_from(id).ok())
+        .expect("no transaction id in the response")
+}
+
+/// Sends a meter value for `transaction_id` on connector 1.
+async fn meter_values(station: &mut Station, transaction_id: i32) {
+    station
+        .call(
+            "MeterValues",
+            json!({
+                "connectorId": 1,
+                "transactionId": transaction_id,
+                "meterValue": [{
+                    "timestamp": "2024-05-01T12:30:00Z",
+                    "sampledValue": [{ "value": "1500" }]
+                }]
+            }),
+        )
+        .await;
+}
+
+fn blocked() -> IdTagInfo {
+    IdTagInfo {
+        expiry_date: None,
+        parent_id_tag: None,
+        status: AuthorizationStatus::Blocked,
+    }
+}
+
+#[tokio::test]
+async fn state_survives_a_restart() {
+    let storage = Arc::new(InMemoryStorage::new());
+    let before = start(Arc::clone(&storage)).await;
+    let mut station = Station::connect(before.address, "CP1").await;
+    let started = start_charging(&mut station).await;
+    before.handle.set_id_tag("TAG2", blocked());
+    before.handle.flush_storage().await;
+
+    let after = start(Arc::clone(&storage)).await;
+    assert!(after.handle.boot_notification("CP1").is_some());
+    assert_eq!(
+        after
+            .handle
+            .connectors("CP1")
+            .connector(1)
+            .map(|connector| connector.status().clone()),
+        Some(ChargePointStatus::Charging)
+    );
+    assert_eq!(after.handle.id_tag("TAG2"), Some(blocked()));
+    let restored = after
+        .handle
+        .transaction(started)
+        .expect("transaction was not restored");
+    assert!(restored.is_active());
+
+    // Still accepted, although it does not boot again.
+    let mut reconnected = Station::connect(after.address, "CP1").await;
+    let heartbeat = applied(&mut reconnected).await;
+    assert_eq!(
+        message_type(&heartbeat),
+        Some(3),
+        "unexpected response: {heartbeat}"
+    );
+    reconnected
+        .call(
+            "StopTransaction",
+            json!({
+                "transactionId": started,
+                "meterStop": 3_000,
+                "timestamp": "2024-05-01T13:00:00Z"
+            }),
+        )
+        .await;
+    applied(&mut reconnected).await;
+    let stopped = after
+        .handle
+        .transaction(started)
+        .expect("unknown transaction");
+    assert_eq!(stopped.energy(), Some(2_000));
+    after.handle.flush_storage().await;
+    let snapshot = storage.load().await.expect("failed to load the storage");
+    assert_eq!(snapshot.transactions, [stopped]);
+}
+
+/// Keeps everything in memory like [`InMemoryStorage`], except what it is told to fail.
+#[derive(Default)]
+struct Failing {
+    load: bool,
+    transactions: bool,
+    records: InMemoryStorage,
+}
+
+fn disk_full() -> StorageError {
+    StorageError::Other("disk full".into())
+}
+
+#[async_trait]
+impl Storage for Failing {
+    async fn load(&self) -> Result<Snapshot, StorageError> {
+        if self.load {
+            return Err(disk_full());
+        }
+        self.records.load().await
+    }
+
+    async fn save_station(&self, station: &StoredStation) -> Result<(), StorageError> {
+        self.records.save_station(station).await
+    }
+
+    async fn save_connector_status(
+        &self,
+        station_id: &str,
+        status: &ConnectorStatus,
+    ) -> Result<(), StorageError> {
+        self.records.save_connector_status(station_id, status).await
+    }
+
+    async fn save_transactions(&self, transactions: &[Transaction]) -> Result<(), StorageError> {
+        if self.transactions {
+            return Err(disk_full());
+        }
+        self.records.save_transactions(transactions).await
+    }
+
+    async fn save_meter_values(
+        &self,
+        transaction_id: i32,
+        meter_values: &[MeterValue],
+    ) -> Result<(), StorageError> {
+        self.records
+            .save_meter_values(transaction_id, meter_values)
+            .await
+    }
+
+    async fn save_id_tag(&self, id_tag: &str, info: &IdTagInfo) -> Result<(), StorageError> {
+        self.records.save_id_tag(id_tag, info).await
+    }
+
+    async fn remove_id_tag(&self, id_tag: &str) -> Result<(), StorageError> {
+        self.records.remove_id_tag(id_tag).await
+    }
+
+    async fn save_pending_call(&self, call: &PendingCall) -> Result<(), StorageError> {
+        self.records.save_pending_call(call).await
+    }
+
+    async fn remove_pending_call(
+        &self,
+        station_id: &str,
+        unique_id: &str,
+    ) -> Result<(), StorageError> {
+        self.records
+            .remove_pending_call(station_id, unique_id)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn changes_made_right_after_loading_are_kept() {
+    let storage = Arc::new(InMemoryStorage::new());
+    storage
+        .save_id_tag("TAG2", &blocked())
+        .await
+        .expect("failed to save the id tag");
+
+    let crush = CrushBuilder::new(Config::new(common::free_address()))
+        .with_storage(Arc::clone(&storage))
+        .try_build()
+        .await
+        .expect("failed to load the storage");
+    let handle = crush.handle();
+    assert_eq!(handle.id_tag("TAG2"), Some(blocked()));
+    assert!(handle.remove_id_tag("TAG2"));
+    assert_eq!(handle.id_tag("TAG2"), None);
+    handle.flush_storage().await;
+    let snapshot = storage.load().await.expect("failed to load the storage");
+    assert!(snapshot.id_tags.is_empty(), "{snapshot:?}");
+}
+
+#[tokio::test]
+async fn failing_to_load_the_storage_is_reported() {
+    let built = CrushBuilder::new(Config::new(common::free_address()))
+        .with_storage(Failing {
+            load: true,
+            ..Failing::default()
+        })
+        .try_build()
+        .await;
+    assert!(
+        matches!(built, Err(BuildError::Storage(StorageError::Other(_)))),
+        "storage was loaded"
+    );
+}
+
+#[tokio::test]
+async fn transactions_are_saved_before_they_are_answered() {
+    let storage = Arc::new(InMemoryStorage::new());
+    let server = start(Arc::clone(&storage)).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    let started = start_charging(&mut station).await;
+
+    let saved = storage.load().await.expect("failed to load the storage");
+    assert_eq!(
+        saved
+            .transactions
+            .iter()
+            .map(Transaction::id)
+            .collect::<Vec<_>>(),
+        [started]
+    );
+
+    station
+        .call(
+            "StopTransaction",
+            json!({
+                "transactionId": started,
+                "meterStop": 3_000,
+                "timestamp": "2024-05-01T13:00:00Z"
+            }),
+        )
+        .await;
+    let stopped = storage.load().await.expect("failed to load the storage");
+    assert!(
+        stopped
+            .transactions
+            .iter()
+            .all(|transaction| transaction.stop().is_some()),
+        "{stopped:?}"
+    );
+}
+
+#[tokio::test]
+async fn transactions_that_were_not_saved_are_answered_with_an_error() {
+    let server = start(Failing {
+        transactions: true,
+        ..Failing::default()
+    })
+    .await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+
+    let response = station
+        .call(
+            "StartTransaction",
+            json!({
+                "connectorId": 1,
+                "idTag": "TAG1",
+                "meterStart": 1_000,
+                "timestamp": "2024-05-01T12:00:00Z"
+            }),
+        )
+        .await;
+    assert_eq!(error_code(&response), Some("InternalError"));
+    applied(&mut station).await;
+    while let Ok(event) = events.try_recv() {
+        assert!(
+            !matches!(event.kind(), EventKind::TransactionStarted { .. }),
+            "unexpected event: {event:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn file_storage_replays_and_compacts_its_log() {
+    let log = LogFile::new();
+    let server = start(FileStorage::open(&log.path).expect("failed to open the log")).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    let started = start_charging(&mut station).await;
+    for _ in 0..3 {
+        meter_values(&mut station, started).await;
+    }
+    applied(&mut station).await;
+    server.handle.flush_storage().await;
+
+    let reopened = FileStorage::open(&log.path).expect("failed to reopen the log");
+    let snapshot = reopened.load().await.expect("failed to load the log");
+    assert_eq!(snapshot.stations.len(), 1);
+    assert_eq!(snapshot.connector_statuses.len(), 1);
+    let transaction = server
+        .handle
+        .transaction(started)
+        .expect("unknown transaction");
+    assert_eq!(transaction.meter_values().len(), 3);
+    assert_eq!(snapshot.transactions, [transaction]);
+    // The start saved the whole transaction, each MeterValues only appended its values.
+    let written = fs::read_to_string(&log.path).expect("failed to read the log");
+    assert_eq!(written.matches(r#""record":"transactions""#).count(), 1);
+    assert_eq!(written.matches(r#""record":"meter_values""#).count(), 3);
+
+    // Every record was written more than once.
+    assert!(log.lines() > 3, "{} lines", log.lines());
+    reopened.compact().expect("failed to compact the log");
+    assert_eq!(log.lines(), 3);
+
+    // As if crush crashed while appending.
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&log.path)
+        .and_then(|mut file| file.write_all(b"{\"record\":\"id_tag\""))
+        .expect("failed to tear the log");
+    let recovered = FileStorage::open(&log.path).expect("failed to recover the log");
+    recovered
+        .save_id_tag("TAG2", &blocked())
+        .await
+        .expect("failed to append to the recovered log");
+    let recovered_snapshot = FileStorage::open(&log.path)
+        .expect("failed to reopen the recovered log")
+        .load()
+        .await
+        .expect("failed to load the recovered log");
+    assert_eq!(recovered_snapshot.transactions, snapshot.transactions);
+    assert_eq!(recovered_snapshot.id_tags, [("TAG2".to_owned(), blocked())]);
+}
+
+#[tokio::test]
+async fn unanswered_calls_are_reported_after_a_restart() {
+    let storage = Arc::new(InMemoryStorage::new());
+    let before = start(Arc::clone(&storage)).await;
+    let mut station = Station::connect(before.address, "CP1").await;
+    start_charging(&mut station).await;
+
+    let handle = before.handle.clone();
+    let reset =
+        tokio::spawn(async move { handle.call::<Reset>("CP1", ResetRequest::default()).await });
+    let call = station.receive().await;
+    before.handle.flush_storage().await;
+
+    let after = start(Arc::clone(&storage)).await;
+    let interrupted = after.handle.interrupted_calls();
+    assert_eq!(interrupted.len(), 1, "{interrupted:?}");
+    let unanswered = interrupted.first().expect("no interrupted call");
+    assert_eq!(unanswered.station_id(), "CP1");
+    assert_eq!(unanswered.action(), "Reset");
+    assert_eq!(
+        call.get(1).and_then(Value::as_str),
+        Some(unanswered.unique_id())
+    );
+
+    after.handle.flush_storage().await;
+    let snapshot = storage.load().await.expect("failed to load the storage");
+    assert!(snapshot.pending_calls.is_empty(), "{snapshot:?}");
+    reset.abort();
+}
+
+#[cfg(feature = "sqlite")]
+fn at(time: &str) -> DateTime<Utc> {
+    time.parse().expect("invalid time")
+}
+
+/// The ids of the transactions `storage` finds with `filter`.
+#[cfg(feature = "sqlite")]
+async fn found(storage: &SqliteStorage, filter: TransactionFilter) -> Vec<i32> {
+    storage
+        .transactions(&filter)
+        .await
+        .expect("failed to query the database")
+        .iter()
+        .map(Transaction::id)
+        .collect()
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_storage_finds_transactions_by_station_id_tag_and_time() {
+    let database = LogFile::new();
+    let storage =
+        Arc::new(SqliteStorage::open(&database.path).expect("failed to open the database"));
+    let server = start(Arc::clone(&storage)).await;
+    let mut first = Station::connect(server.address, "CP1").await;
+    let mut second = Station::connect(server.address, "CP2").await;
+    let stopped = start_charging(&mut first).await;
+    let elsewhere = start_charging(&mut second).await;
+    meter_values(&mut second, elsewhere).await;
+    applied(&mut second).await;
+    first
+        .call(
+            "StopTransaction",
+            json!({
+                "transactionId": stopped,
+                "meterStop": 3_000,
+                "timestamp": "2024-05-01T13:00:00Z"
+            }),
+        )
+        .await;
+    first
+        .call(
+            "StartTransaction",
+            json!({
+                "connectorId": 1,
+                "idTag": "TAG2",
+                "meterStart": 3_000,
+                "timestamp": "2024-05-01T14:00:00Z"
+            }),
+        )
+        .await;
+    applied(&mut first).await;
+    server.handle.flush_storage().await;
+    let later = server
+        .handle
+        .transactions("CP1")
+        .into_iter()
+        .find(|transaction| transaction.id_tag() == "TAG2")
+        .expect("transaction was not started");
+
+    assert_eq!(
+        found(&storage, TransactionFilter::new().with_station("CP1")).await,
+        [stopped, later.id()]
+    );
+    assert_eq!(
+        found(&storage, TransactionFilter::new().with_id_tag("TAG1")).await,
+        [stopped, elsewhere]
+    );
+    assert_eq!(
+        found(
+            &storage,
+            TransactionFilter::new()
+                .with_station("CP2")
+                .with_id_tag("TAG1")
+        )
+        .await,
+        [elsewhere]
+    );
+    assert_eq!(
+        found(
+            &storage,
+            TransactionFilter::new().with_started_after(at("2024-05-01T13:00:00Z"))
+        )
+        .await,
+        [later.id()]
+    );
+    assert_eq!(
+        found(
+            &storage,
+            TransactionFilter::new()
+                .with_started_after(at("2024-05-01T12:00:00Z"))
+                .with_started_before(at("2024-05-01T14:00:00Z"))
+        )
+        .await,
+        [stopped, elsewhere]
+    );
+
+    // Opening it again finds the schema migrated.
+    let snapshot = SqliteStorage::open(&database.path)
+        .expect("failed to reopen the database")
+        .load()
+        .await
+        .expect("failed to load the database");
+    assert_eq!(snapshot.stations.len(), 2);
+    assert_eq!(snapshot.connector_statuses.len(), 2);
+    let stopped_transaction = snapshot
+        .transactions
+        .iter()
+        .find(|transaction| transaction.id() == stopped)
+        .expect("stopped transaction was not saved");
+    assert_eq!(stopped_transaction.energy(), Some(2_000));
+    let ongoing = snapshot
+        .transactions
+        .iter()
+        .find(|transaction| transaction.id() == elsewhere)
+        .expect("ongoing transaction was not saved");
+    assert_eq!(ongoing.meter_values().len(), 1);
+    assert_eq!(snapshot.transactions.len(), 3);
+}
@@ -0,0 +1,127 @@
+use crush::{
+    actions::{Authorize, Reset},
+    rust_ocpp::v1_6::{
+        messages::{authorize::AuthorizeResponse, reset::ResetRequest},
+        types::{AuthorizationStatus, IdTagInfo, ResetResponseStatus},
+    },
+    CrushBuilder,
+};
+use serde_json::json;
+
+use crate::common::{self, error_code, message_type, Station};
+
+fn crashing_authorize(builder: CrushBuilder) -> CrushBuilder {
+    builder.on_action::<Authorize, _>(|_context, request| async move {
+        assert!(request.id_tag != "crash", "simulated handler crash");
+        Ok(AuthorizeResponse {
+            id_tag_info: IdTagInfo {
+                expiry_date: None,
+                parent_id_tag: None,
+                status: AuthorizationStatus::Accepted,
+            },
+        })
+    })
+}
+
+#[tokio::test]
+async fn panicking_handler_is_answered_with_internal_error() {
+    let server = common::start(crashing_authorize).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let response = station.call("Authorize", json!({ "idTag": "crash" })).await;
+
+    assert_eq!(message_type(&response), Some(4), "expected a CALLERROR");
+    assert_eq!(error_code(&response), Some("InternalError"));
+}
+
+#[tokio::test]
+async fn station_is_served_after_its_handler_panicked() {
+    let server = common::start(crashing_authorize).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    station.call("Authorize", json!({ "idTag": "crash" })).await;
+    let heartbeat = station.call("Heartbeat", json!({})).await;
+    let authorize = station.call("Authorize", json!({ "idTag": "tag" })).await;
+
+    assert_eq!(message_type(&heartbeat), Some(3), "expected a CALLRESULT");
+    assert_eq!(message_type(&authorize), Some(3), "expected a CALLRESULT");
+}
+
+#[tokio::test]
+async fn other_stations_are_served_while_a_handler_panics() {
+    let server = common::start(crashing_authorize).await;
+    let mut crashing = Station::connect(server.address, "CP1").await;
+    let mut healthy = Station::connect(server.address, "CP2").await;
+
+    for _ in 0..3 {
+        crashing
+            .call("Authorize", json!({ "idTag": "crash" }))
+            .await;
+        let response = healthy.call("Authorize", json!({ "idTag": "tag" })).await;
+        assert_eq!(message_type(&response), Some(3), "expected a CALLRESULT");
+    }
+}
+
+#[tokio::test]
+async fn station_is_served_after_its_controller_was_aborted() {
+    let server = common::start(|builder| builder).await;
+    let mut crashing = Station::connect(server.address, "CP1").await;
+    let mut healthy = Station::connect(server.address, "CP2").await;
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    assert!(server.handle.abort_controller("CP1").await);
+
+    let restarted = crashing.call("Heartbeat", json!({})).await;
+    let untouched = healthy.call("Heartbeat", json!({})).await;
+    assert_eq!(message_type(&restarted), Some(3), "expected a CALLRESULT");
+    assert_eq!(message_type(&untouched), Some(3), "expected a CALLRESULT");
+}
+
+#[tokio::test]
+async fn sessions_rejoin_after_the_server_loop_was_aborted() {
+    let server = common::start(|builder| builder).await;
+    let mut first = Station::connect(server.address, "CP1").await;
+    let mut second = Station::connect(server.address, "CP2").await;
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    server.handle.abort_server_loop();
+
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+    let heartbeat = second.call("Heartbeat", json!({})).await;
+    assert_eq!(message_type(&heartbeat), Some(3), "expected a CALLRESULT");
+    // Outbound calls find the session in the rebuilt registry.
+    let (result, (action, _payload)) = tokio::join!(
+        server.handle.call::<Reset>("CP1", ResetRequest::default()),
+        first.answer(json!({ "status": "Accepted" })),
+    );
+    assert_eq!(action, "Reset", "unexpected action");
+    assert_eq!(
+        result.expect("call failed").status,
+        ResetResponseStatus::Accepted
+    );
+    // New stations are admitted again.
+    Station::connect(server.address, "CP3").await;
+    server.wait_for_stations(&["CP1", "CP2", "CP3"]).await;
+}
+
+#[tokio::test]
+async fn session_ends_once_its_controller_crashed_too_often() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+    server.wait_for_stations(&["CP1"]).await;
+
+    for _ in 0..5 {
+        assert!(server.handle.abort_controller("CP1").await);
+        let heartbeat = station.call("Heartbeat", json!({})).await;
+        assert_eq!(
+            message_type(&heartbeat),
+            Some(3),
+            "controller was not restarted"
+        );
+    }
+
+    assert!(server.handle.abort_controller("CP1").await);
+    station.send(json!([2, "last", "Heartbeat", {}])).await;
+    station.closed().await;
+    server.wait_for_stations(&[]).await;
+}
@@ -0,0 +1,253 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crush::{BuildError, Config, CrushBuilder, TlsConfig, TlsError};
+use rcgen::{generate_simple_self_signed, CertifiedKey};
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    version::{TLS12, TLS13},
+    ClientConfig, RootCertStore, SupportedCipherSuite, SupportedProtocolVersion,
+};
+use serde_json::json;
+use tokio::time::{sleep, timeout};
+
+use crate::common::{self, message_type, Server, Station};
+
+static NEXT_DIRECTORY: AtomicUsize = AtomicUsize::new(0);
+
+/// A server certificate and key written to PEM files, removed again on drop.
+pub(crate) struct CertificateFiles {
+    directory: PathBuf,
+}
+
+impl CertificateFiles {
+    pub(crate) fn new() -> Self {
+        let directory = env::temp_dir().join(format!(
+            "crush-tls-{}-{}",
+            process::id(),
+            NEXT_DIRECTORY.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&directory).expect("failed to create certificate directory");
+        Self { directory }
+    }
+
+    fn certificate(&self) -> PathBuf {
+        self.directory.join("server.crt")
+    }
+
+    fn private_key(&self) -> PathBuf {
+        self.directory.join("server.key")
+    }
+
+    pub(crate) fn write(&self, certified_key: &CertifiedKey) {
+        fs::write(self.certificate(), certified_key.cert.pem()).expect("failed to write cert");
+        fs::write(self.private_key(), certified_key.key_pair.serialize_pem())
+            .expect("failed to write key");
+    }
+
+    /// The CA bundle stations' client certificates are verified against.
+    pub(crate) fn client_ca(&self) -> PathBuf {
+        self.directory.join("client-ca.crt")
+    }
+
+    pub(crate) fn config(&self) -> TlsConfig {
+        TlsConfig::from_pem_files(self.certificate(), self.private_key())
+    }
+}
+
+impl Drop for CertificateFiles {
+    fn drop(&mut self) {
+        drop(fs::remove_dir_all(&self.directory));
+    }
+}
+
+pub(crate) fn certificate() -> CertifiedKey {
+    generate_simple_self_signed(vec!["localhost".to_owned()])
+        .expect("failed to generate certificate")
+}
+
+/// A client trusting only `trusted`, limited to `versions` and `cipher_suites`.
+fn client_with(
+    trusted: &CertifiedKey,
+    versions: &[&'static SupportedProtocolVersion],
+    cipher_suites: Vec<SupportedCipherSuite>,
+) -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(trusted.cert.der().clone())
+        .expect("failed to trust certificate");
+    let provider = CryptoProvider {
+        cipher_suites,
+        ..ring::default_provider()
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(versions)
+        .expect("invalid client protocol versions")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Arc::new(config)
+}
+
+fn client(trusted: &CertifiedKey) -> Arc<ClientConfig> {
+    client_with(
+        trusted,
+        &[&TLS13, &TLS12],
+        ring::default_provider().cipher_suites,
+    )
+}
+
+async fn start(files: &CertificateFiles, reload_interval: Duration) -> Server {
+    let tls = files.config().with_reload_interval(reload_interval);
+    common::start_with_config(|config| config.with_tls(tls), |builder| builder).await
+}
+
+#[tokio::test]
+async fn stations_connect_over_tls() {
+    let files = CertificateFiles::new();
+    let certified_key = certificate();
+    files.write(&certified_key);
+    let server = start(&files, Duration::ZERO).await;
+
+    let _tls13 = Station::try_connect_tls(server.address, "CP1", client(&certified_key))
+        .await
+        .expect("TLS 1.3 station failed to connect");
+    let tls12 = client_with(
+        &certified_key,
+        &[&TLS12],
+        vec![ring::cipher_suite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256],
+    );
+    let _tls12 = Station::try_connect_tls(server.address, "CP2", tls12)
+        .await
+        .expect("TLS 1.2 station failed to connect");
+    server.wait_for_stations(&["CP1", "CP2"]).await;
+
+    assert!(
+        Station::try_connect(server.address, "CP3").await.is_err(),
+        "unencrypted station was accepted"
+    );
+}
+
+#[tokio::test]
+async fn cipher_suites_outside_the_whitepaper_are_refused() {
+    let files = CertificateFiles::new();
+    let certified_key = certificate();
+    files.write(&certified_key);
+    let server = start(&files, Duration::ZERO).await;
+
+    let chacha = client_with(
+        &certified_key,
+        &[&TLS12],
+        vec![ring::cipher_suite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256],
+    );
+    assert!(
+        Station::try_connect_tls(server.address, "CP1", chacha)
+            .await
+            .is_err(),
+        "station negotiated ChaCha20-Poly1305"
+    );
+}
+
+#[tokio::test]
+async fn reloading_the_certificate_keeps_sessions_open() {
+    let files = CertificateFiles::new();
+    let old = certificate();
+    files.write(&old);
+    let server = start(&files, Duration::ZERO).await;
+    let mut station = Station::try_connect_tls(server.address, "CP1", client(&old))
+        .await
+        .expect("station failed to connect");
+
+    fs::write(files.private_key(), "not a key").expect("failed to write key");
+    assert!(
+        server.handle.reload_tls().await.is_err(),
+        "invalid key was loaded"
+    );
+    let _old_still_served = Station::try_connect_tls(server.address, "CP2", client(&old))
+        .await
+        .expect("failed reload replaced the certificate");
+
+    let new = certificate();
+    files.write(&new);
+    server
+        .handle
+        .reload_tls()
+        .await
+        .expect("failed to reload certificate");
+
+    let _renewed = Station::try_connect_tls(server.address, "CP3", client(&new))
+        .await
+        .expect("station trusting the new certificate failed to connect");
+    assert!(
+        Station::try_connect_tls(server.address, "CP4", client(&old))
+            .await
+            .is_err(),
+        "old certificate is still served"
+    );
+
+    let response = station.call("Heartbeat", json!({})).await;
+    assert_eq!(message_type(&response), Some(3));
+}
+
+#[tokio::test]
+async fn changed_certificate_files_are_reloaded() {
+    let files = CertificateFiles::new();
+    files.write(&certificate());
+    let server = start(&files, Duration::from_millis(50)).await;
+
+    let new = certificate();
+    files.write(&new);
+    timeout(Duration::from_secs(5), async {
+        while Station::try_connect_tls(server.address, "CP1", client(&new))
+            .await
+            .is_err()
+        {
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("changed certificate was not picked up");
+}
+
+#[tokio::test]
+async fn reloading_without_tls_fails() {
+    let server = common::start(|builder| builder).await;
+
+    assert!(matches!(
+        server.handle.reload_tls().await,
+        Err(TlsError::NotConfigured)
+    ));
+}
+
+#[tokio::test]
+async fn missing_certificate_fails_the_build() {
+    let files = CertificateFiles::new();
+    let config = Config::new(common::free_address()).with_tls(files.config());
+
+    let built = CrushBuilder::new(config).try_build().await;
+    assert!(
+        matches!(built, Err(BuildError::Tls(TlsError::Read { .. }))),
+        "built without a certificate"
+    );
+}
+
+#[tokio::test]
+async fn invalid_certificate_fails_the_build() {
+    let files = CertificateFiles::new();
+    files.write(&certificate());
+    fs::write(files.certificate(), "not a certificate").expect("failed to overwrite cert");
+    let config = Config::new(common::free_address()).with_tls(files.config());
+
+    let built = CrushBuilder::new(config).try_build().await;
+    assert!(
+        matches!(built, Err(BuildError::Tls(_))),
+        "built with an invalid certificate"
+    );
+}
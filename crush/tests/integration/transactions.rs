@@ -0,0 +1,217 @@
+use crush::{
+    rust_ocpp::v1_6::{
+        messages::start_transaction::StartTransactionResponse,
+        types::{AuthorizationStatus, IdTagInfo, Reason},
+    },
+    EventKind, Transaction,
+};
+use serde_json::{json, Value};
+
+use crate::common::{self, Station};
+
+/// Waits until crush recorded the messages sent before, which it does before it handles the
+/// next CALL of the station.
+async fn applied(station: &mut Station) {
+    station.call("Heartbeat", json!({})).await;
+}
+
+/// Starts a transaction on `connector_id` and returns the id crush allocated.
+async fn start_transaction(station: &mut Station, connector_id: u32, meter_start: i32) -> i32 {
+    let response = station
+        .call(
+            "StartTransaction",
+            json!({
+                "connectorId": connector_id,
+                "idTag": "TAG1",
+                "meterStart": meter_start,
+                "timestamp": "2024-05-01T12:00:00Z"
+            }),
+        )
+        .await;
+    transaction_id(&response).expect("no transaction id in the response")
+}
+
+fn transaction_id(response: &Value) -> Option<i32> {
+    response
+        .get(2)?
+        .get("transactionId")?
+        .as_i64()?
+        .try_into()
+        .ok()
+}
+
+#[tokio::test]
+async fn transactions_are_recorded_from_start_to_stop() {
+    let server = common::start(|builder| {
+        builder.on_start_transaction(|_context, _request| async {
+            Ok(StartTransactionResponse {
+                id_tag_info: IdTagInfo {
+                    expiry_date: None,
+                    parent_id_tag: None,
+                    status: AuthorizationStatus::Accepted,
+                },
+                transaction_id: 42,
+            })
+        })
+    })
+    .await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let id = start_transaction(&mut station, 1, 1_000).await;
+    assert_ne!(id, 42, "the handler's transaction id was not replaced");
+    let second = start_transaction(&mut station, 2, 0).await;
+    assert!(second > id, "{second} was allocated after {id}");
+    station
+        .call(
+            "MeterValues",
+            json!({
+                "connectorId": 1,
+                "transactionId": id,
+                "meterValue": [{
+                    "timestamp": "2024-05-01T12:30:00Z",
+                    "sampledValue": [{ "value": "4500" }]
+                }]
+            }),
+        )
+        .await;
+    station
+        .call(
+            "StopTransaction",
+            json!({
+                "transactionId": id,
+                "idTag": "TAG1",
+                "meterStop": 8_500,
+                "timestamp": "2024-05-01T13:00:00Z",
+                "reason": "EVDisconnected",
+                "transactionData": [{
+                    "timestamp": "2024-05-01T13:00:00Z",
+                    "sampledValue": [{ "value": "8500" }]
+                }]
+            }),
+        )
+        .await;
+    applied(&mut station).await;
+
+    let stopped = server.handle.transaction(id).expect("unknown transaction");
+    assert_eq!(stopped.station_id(), "CP1");
+    assert_eq!(stopped.connector_id(), 1);
+    assert_eq!(stopped.id_tag(), "TAG1");
+    assert_eq!(stopped.meter_values().len(), 2);
+    assert!(!stopped.is_active());
+    assert_eq!(stopped.energy(), Some(7_500));
+    let stop = stopped.stop().expect("transaction not stopped");
+    assert_eq!(stop.reason(), Some(&Reason::EVDisconnected));
+    assert_eq!(stop.stopped_at().to_rfc3339(), "2024-05-01T13:00:00+00:00");
+
+    assert_eq!(
+        server
+            .handle
+            .transactions("CP1")
+            .iter()
+            .map(Transaction::id)
+            .collect::<Vec<_>>(),
+        [id, second]
+    );
+    assert_eq!(
+        server
+            .handle
+            .active_transactions()
+            .iter()
+            .map(Transaction::id)
+            .collect::<Vec<_>>(),
+        [second]
+    );
+
+    let mut recorded = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        match event.kind() {
+            EventKind::TransactionStarted { transaction } => {
+                recorded.push(("started", transaction.id()));
+            }
+            EventKind::TransactionStopped { transaction } => {
+                recorded.push(("stopped", transaction.id()));
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        recorded,
+        [("started", id), ("started", second), ("stopped", id)]
+    );
+}
+
+#[tokio::test]
+async fn rebooted_stations_orphan_their_transactions() {
+    let server = common::start(|builder| builder).await;
+    let mut events = server.handle.events();
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    let lost = start_transaction(&mut station, 1, 0).await;
+    let replaced = start_transaction(&mut station, 2, 0).await;
+    let replacement = start_transaction(&mut station, 2, 100).await;
+    station
+        .call(
+            "BootNotification",
+            json!({ "chargePointVendor": "Vendor", "chargePointModel": "Model" }),
+        )
+        .await;
+    applied(&mut station).await;
+
+    let mut orphaned = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EventKind::TransactionOrphaned { transaction } = event.kind() {
+            orphaned.push(transaction.id());
+        }
+    }
+    assert_eq!(orphaned, [replaced, lost, replacement]);
+    assert!(server.handle.active_transactions().is_empty());
+
+    // The station may still report how the transaction ended.
+    station
+        .call(
+            "StopTransaction",
+            json!({
+                "transactionId": lost,
+                "meterStop": 250,
+                "timestamp": "2024-05-01T13:00:00Z"
+            }),
+        )
+        .await;
+    applied(&mut station).await;
+    let orphan = server
+        .handle
+        .transaction(lost)
+        .expect("unknown transaction");
+    assert!(orphan.orphaned_at().is_some());
+    assert_eq!(orphan.energy(), Some(250));
+}
+
+#[tokio::test]
+async fn only_the_transactions_that_ended_last_are_kept() {
+    let server = common::start(|builder| builder).await;
+    let mut station = Station::connect(server.address, "CP1").await;
+
+    // Every start orphans the transaction before it on the connector.
+    let first = start_transaction(&mut station, 1, 0).await;
+    let second = start_transaction(&mut station, 1, 0).await;
+    let mut last = second;
+    for _ in 0..1_024 {
+        last = start_transaction(&mut station, 1, 0).await;
+    }
+    applied(&mut station).await;
+
+    assert_eq!(server.handle.transaction(first), None, "oldest was kept");
+    assert!(
+        server.handle.transaction(second).is_some(),
+        "too few were kept"
+    );
+    assert!(
+        server
+            .handle
+            .transaction(last)
+            .is_some_and(|transaction| transaction.is_active()),
+        "active transaction was forgotten"
+    );
+    assert_eq!(server.handle.transactions("CP1").len(), 1_025);
+}
@@ -7,10 +7,13 @@ use crush::{
         messages::{
             boot_notification::{BootNotificationRequest, BootNotificationResponse},
             heart_beat::{HeartbeatRequest, HeartbeatResponse},
+            status_notification::StatusNotificationResponse,
         },
         types::RegistrationStatus,
     },
-    Config, CrushBuilder, HandleBootNotificationRequest, HandleHeartbeatRequest, OcppResult,
+    tower::util::MapRequestLayer,
+    Config, CrushBuilder, HandleBootNotificationRequest, HandleHeartbeatRequest, OcppCall,
+    OcppResult, StationContext,
 };
 use tracing::{subscriber, Level};
 use tracing_subscriber::FmtSubscriber;
@@ -19,7 +22,11 @@ struct MyHeartbeatHandler;
 
 #[async_trait]
 impl HandleHeartbeatRequest for MyHeartbeatHandler {
-    async fn handle(&self, request: HeartbeatRequest) -> OcppResult<HeartbeatResponse> {
+    async fn handle(
+        &self,
+        _context: StationContext,
+        request: HeartbeatRequest,
+    ) -> OcppResult<HeartbeatResponse> {
         tracing::info!("Handling: {request:#?}");
         let current_time = Utc::now();
         Ok(HeartbeatResponse { current_time })
@@ -32,6 +39,7 @@ struct MyBootNotificationHandler;
 impl HandleBootNotificationRequest for MyBootNotificationHandler {
     async fn handle(
         &self,
+        _context: StationContext,
         request: BootNotificationRequest,
     ) -> OcppResult<BootNotificationResponse> {
         tracing::info!("Handling: {request:#?}");
@@ -63,9 +71,25 @@ async fn main() {
     let config = Config::new(address);
 
     let crush = CrushBuilder::new(config)
+        .layer(MapRequestLayer::new(|call: OcppCall| {
+            tracing::debug!("{} sent: {}", call.context().station_id(), call.frame());
+            call
+        }))
         .with_heartbeat_handler(MyHeartbeatHandler)
         .with_boot_notification_handler(MyBootNotificationHandler)
-        .build();
+        .on_status_notification(|context, request| async move {
+            tracing::info!("Handling for {}: {request:#?}", context.station_id());
+            Ok(StatusNotificationResponse {})
+        })
+        .try_build()
+        .await;
+    let crush = match crush {
+        Ok(crush) => crush,
+        Err(error) => {
+            tracing::error!("Error building Crush: {error}");
+            return;
+        }
+    };
 
     if let Err(error) = crush.run().await {
         tracing::error!("Error running Crush: {error}");